@@ -8,6 +8,7 @@
 #include <CGAL/Polygon_2.h>
 #include <CGAL/draw_polygon_2.h>
 #include <fstream>
+#include <limits>
 #include <list>
 #include <vector>
 
@@ -50,6 +51,10 @@ void constructArrang(vector<Polygon_2> &myPolys, Arrangement_2 &arr,
 
 void readPolysFromIS(char *fname, vector<Polygon_2> &myPolys) {
   ifstream myFile(fname);
+  // skip header comments (e.g. the metric names)
+  while ((myFile >> ws).peek() == '#') {
+    myFile.ignore(numeric_limits<streamsize>::max(), '\n');
+  }
   int nofPolys;
   myFile >> nofPolys;
   cout << "Reading " << nofPolys << " polygons" << endl;
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use pref_polys::graph::{self, contraction::contract_graph};
+
+use anyhow::Result;
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the uncontracted graph file
+    graph: PathBuf,
+    /// Path to write the contracted graph to (minimal format)
+    output: PathBuf,
+    /// Read the input graph in the legacy format with osm ids and coordinates
+    #[structopt(short = "l", long)]
+    legacy: bool,
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+
+    let graph = if opts.legacy {
+        graph::parse_graph_file(&opts.graph)?
+    } else {
+        graph::parse_minimal_graph_file(&opts.graph)?
+    };
+
+    let contracted = contract_graph(&graph);
+    contracted.graph.write_minimal_graph_file(&opts.output)?;
+    println!("Wrote contracted graph to {}", opts.output.display());
+    Ok(())
+}
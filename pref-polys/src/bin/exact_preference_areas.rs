@@ -1,18 +1,26 @@
 use std::{fs::File, io::BufReader, path::PathBuf, time::Instant};
 
-use graph::path::Path;
+use graph::path::{check_loaded_paths, Path};
 use structopt::StructOpt;
 
+use pref_polys::geom::intersection;
 use pref_polys::graph;
-use pref_polys::graph::dijkstra::Dijkstra;
-use pref_polys::utils::randomized_preference;
-
-use anyhow::Result;
+use pref_polys::graph::dijkstra::{Dijkstra, QueryPool, TieBreaking};
+use pref_polys::graph::{DistanceBand, PairSampler};
+use pref_polys::tolerance::Tolerance;
+use pref_polys::utils::{
+    io::space_header,
+    number::{Number, Rational},
+    parallel::work_stealing_map,
+    randomized_preference,
+};
+
+use anyhow::{bail, Context, Result};
 use rand::distributions::{Distribution, Uniform};
 use rand::rngs::StdRng;
 use rand::{thread_rng, RngCore, SeedableRng};
 
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 use std::io::Write;
 
@@ -47,15 +55,42 @@ struct Opts {
     /// Path to paths files
     #[structopt(short = "f", long)]
     path_file: Option<PathBuf>,
+    /// Minimum length of generated trajectories in the distance metric
+    #[structopt(long)]
+    min_distance: Option<f64>,
+    /// Maximum length of generated trajectories in the distance metric
+    #[structopt(long)]
+    max_distance: Option<f64>,
+    /// Metric for min and max distance (default: the first metric)
+    #[structopt(long)]
+    distance_metric: Option<String>,
+    /// Rule to choose between equal cost paths. Possible values are: none,
+    /// costs (lexicographically smallest cost vector)
+    #[structopt(long)]
+    tie_breaking: Option<TieBreaking>,
+    /// Recompute nodes and costs of loaded paths from their edges instead of
+    /// failing when they do not match the graph
+    #[structopt(long)]
+    repair_paths: bool,
+    /// Paths within (1 + epsilon) times the optimal cost count as optimal
+    #[structopt(long, default_value = "0")]
+    epsilon: f64,
+    /// Paths within this absolute cost above the optimum count as optimal
+    #[structopt(long, default_value = "0")]
+    slack: f64,
+    /// Compute the preference spaces of modus 0 and 3 with rationals instead
+    /// of floating point numbers and write the exact vertex coordinates
+    #[structopt(long)]
+    exact: bool,
 }
 
-pub struct Corner {
-    coords: Vec<f64>,
+pub struct Corner<T> {
+    coords: Vec<T>,
     is_checked: bool,
     neighbor_indices: Vec<usize>,
     constraint_indices: Vec<usize>,
 }
-impl Corner {
+impl<T> Corner<T> {
     fn new() -> Self {
         Corner {
             coords: Vec::new(),
@@ -66,18 +101,21 @@ impl Corner {
     }
 }
 
-pub struct AreaCalculator {
+/// Computes preference spaces with the corner cutting approach. With
+/// rationals as `T` the corners are computed exactly.
+pub struct AreaCalculator<T = f64> {
     source: u32,
     target: u32,
     costs: Vec<u32>,
-    corners: Vec<Corner>,
+    corners: Vec<Corner<T>>,
     hull_indices: Vec<usize>,
     ch_counter: usize,
-    constraints: Vec<Vec<i32>>,
+    constraints: Vec<Vec<i64>>,
+    tolerance: Tolerance,
 }
 
-impl AreaCalculator {
-    fn new(source: u32, target: u32, costs: Vec<u32>) -> Self {
+impl<T: Number> AreaCalculator<T> {
+    fn new(source: u32, target: u32, costs: Vec<u32>, tolerance: Tolerance) -> Self {
         let mut corners = Vec::new();
         let mut hull_indices = Vec::new();
         let mut constraints = Vec::new();
@@ -85,10 +123,10 @@ impl AreaCalculator {
             let mut constraint = vec![0; 3];
             constraint[i] = -1;
             constraints.push(constraint);
-            let mut coords = vec![0.; 3];
+            let mut coords = vec![T::zero(); 3];
             let neighbor_indices = vec![(i + 1) % 3, (i + 2) % 3];
             let constraint_indices = vec![(i + 2) % 3, (i + 1) % 3];
-            coords[i] = 1.;
+            coords[i] = T::one();
             corners.push(Corner {
                 coords,
                 is_checked: false,
@@ -105,40 +143,33 @@ impl AreaCalculator {
             hull_indices,
             ch_counter: 0,
             constraints,
+            tolerance,
         }
     }
 
     fn calculate_area(&mut self, dijkstra: &mut Dijkstra, debug: bool) {
+        let precision = T::tolerance(PRECISION);
         let mut hull_index: usize = 0;
         let mut counter = 0;
         while hull_index < self.hull_indices.len() {
             let corner_index = self.hull_indices[hull_index];
-            let cost = get_cost_vector_for_pref(
-                dijkstra,
-                &self.corners[corner_index].coords,
-                self.source,
-                self.target,
-            );
+            let pref: Vec<f64> = self.corners[corner_index]
+                .coords
+                .iter()
+                .map(Number::to_f64)
+                .collect();
+            let cost = get_cost_vector_for_pref(dijkstra, &pref, self.source, self.target);
             self.ch_counter += 1;
             self.corners[corner_index].is_checked = true;
-            let mut cost_diff = Vec::new();
-            for i in 0..cost.len() {
-                cost_diff.push(self.costs[i] as i32 - cost[i] as i32);
-            }
-            let mut aggregated_diff = 0.;
-            for i in 0..cost_diff.len() {
-                aggregated_diff += cost_diff[i] as f64 * self.corners[corner_index].coords[i];
-            }
-            if aggregated_diff > PRECISION {
-                let mut dot_products: Vec<f64> = vec![0.; self.corners.len()];
+            let cost_diff = self.cost_diff(&cost);
+            let aggregated_diff = dot(&cost_diff, &self.corners[corner_index].coords);
+            if aggregated_diff > precision {
+                let mut dot_products: Vec<T> = vec![T::zero(); self.corners.len()];
                 let mut new_hull_indices = Vec::new();
                 for hi in &self.hull_indices {
-                    let mut dot_p = 0.;
-                    for i in 0..cost_diff.len() {
-                        dot_p += cost_diff[i] as f64 * self.corners[*hi].coords[i];
-                    }
-                    dot_products[*hi] = dot_p;
-                    if dot_p <= PRECISION {
+                    let dot_p = dot(&cost_diff, &self.corners[*hi].coords);
+                    dot_products[*hi] = dot_p.clone();
+                    if dot_p <= precision {
                         new_hull_indices.push(*hi);
                     }
                 }
@@ -147,10 +178,12 @@ impl AreaCalculator {
                 let mut index_left = self.corners.len() + 1;
                 {
                     let mut first_in = corner_index;
-                    while dot_products[first_in] > PRECISION {
+                    while dot_products[first_in] > precision {
                         first_in = self.corners[first_in].neighbor_indices[0];
                     }
-                    if dot_products[first_in] >= 0. || -dot_products[first_in] <= PRECISION {
+                    if dot_products[first_in] >= T::zero()
+                        || -dot_products[first_in].clone() <= precision
+                    {
                         index_right = first_in;
                         index_left = self.corners.len();
                     } else {
@@ -165,26 +198,21 @@ impl AreaCalculator {
                             .push(self.constraints.len() - 1);
                         let out_index = self.corners[first_in].neighbor_indices[1];
                         self.corners[first_in].neighbor_indices[1] = index_right;
-                        let dot1 = dot_products[first_in];
-                        let dot2 = dot_products[out_index];
-                        {
-                            let p = dot2 / (dot2 - dot1);
-                            for i in 0..self.corners[first_in].coords.len() {
-                                let x = p * self.corners[first_in].coords[i]
-                                    + (1. - p) * self.corners[out_index].coords[i];
-                                new_corner.coords.push(x);
-                            }
-                        }
+                        let dot1 = dot_products[first_in].clone();
+                        let dot2 = dot_products[out_index].clone();
+                        new_corner.coords = self.between(first_in, out_index, dot1, dot2);
                         self.corners.push(new_corner);
                         new_hull_indices.push(index_right);
                     }
                 }
                 {
                     let mut first_in = corner_index;
-                    while dot_products[first_in] > PRECISION {
+                    while dot_products[first_in] > precision {
                         first_in = self.corners[first_in].neighbor_indices[1];
                     }
-                    if dot_products[first_in] >= 0. || -dot_products[first_in] <= PRECISION {
+                    if dot_products[first_in] >= T::zero()
+                        || -dot_products[first_in].clone() <= precision
+                    {
                         index_left = first_in;
                     } else {
                         let mut new_corner = Corner::new();
@@ -198,14 +226,9 @@ impl AreaCalculator {
                             .push(self.corners[first_in].constraint_indices[0]);
                         let out_index = self.corners[first_in].neighbor_indices[0];
                         self.corners[first_in].neighbor_indices[0] = index_left;
-                        let dot1 = dot_products[first_in];
-                        let dot2 = dot_products[out_index];
-                        for i in 0..self.corners[first_in].coords.len() {
-                            let p = dot2 / (dot2 - dot1);
-                            let x = p * self.corners[first_in].coords[i]
-                                + (1. - p) * self.corners[out_index].coords[i];
-                            new_corner.coords.push(x);
-                        }
+                        let dot1 = dot_products[first_in].clone();
+                        let dot2 = dot_products[out_index].clone();
+                        new_corner.coords = self.between(first_in, out_index, dot1, dot2);
                         self.corners.push(new_corner);
                         new_hull_indices.push(index_left);
                     }
@@ -234,6 +257,31 @@ impl AreaCalculator {
         }
     }
 
+    /// The point on the edge between the corners `inside` and `outside`
+    /// where the dot product with the new constraint is zero.
+    fn between(&self, inside: usize, outside: usize, dot1: T, dot2: T) -> Vec<T> {
+        let p = dot2.clone() / (dot2 - dot1);
+        self.corners[inside]
+            .coords
+            .iter()
+            .zip(&self.corners[outside].coords)
+            .map(|(a, b)| p.clone() * a.clone() + (T::one() - p.clone()) * b.clone())
+            .collect()
+    }
+
+    /// The relaxed difference between the costs of the path and `costs`,
+    /// positive for preferences where the path is not optimal within the
+    /// tolerance.
+    fn cost_diff(&self, costs: &[u32]) -> Vec<i64> {
+        let path_costs: Vec<i64> = self.costs.iter().map(|&c| c as i64).collect();
+        let costs: Vec<i64> = costs.iter().map(|&c| c as i64).collect();
+        self.tolerance
+            .integer_constraint(&path_costs, &costs)
+            .into_iter()
+            .map(|c| -c)
+            .collect()
+    }
+
     fn print_hull(&self) {
         print!("hull:");
         for hi in &self.hull_indices {
@@ -249,7 +297,7 @@ impl AreaCalculator {
         }
     }
 
-    fn get_intersections(&self) -> Vec<Vec<Vec<i32>>> {
+    fn get_intersections(&self) -> Vec<Vec<Vec<i64>>> {
         let mut intersections = Vec::new();
         if self.hull_indices.len() == 0 {
             return intersections;
@@ -258,7 +306,7 @@ impl AreaCalculator {
         let mut index = first_index;
         let mut finished = false;
         while !finished {
-            let mut intersection: Vec<Vec<i32>> = Vec::new();
+            let mut intersection: Vec<Vec<i64>> = Vec::new();
             intersection.push(self.constraints[self.corners[index].constraint_indices[0]].clone());
             intersection.push(self.constraints[self.corners[index].constraint_indices[1]].clone());
             intersections.push(intersection);
@@ -338,14 +386,14 @@ impl AreaCalculator {
 
     fn debug_constraints(&self) {
         for corner in &self.corners {
-            let mut dot_p1 = 0.;
-            let mut dot_p2 = 0.;
-            let index1 = corner.constraint_indices[0];
-            let index2 = corner.constraint_indices[1];
-            for i in 0..self.constraints[index1].len() {
-                dot_p1 += self.constraints[index1][i] as f64 * corner.coords[i];
-                dot_p2 += self.constraints[index2][i] as f64 * corner.coords[i];
-            }
+            let dot_p1 = dot(
+                &self.constraints[corner.constraint_indices[0]],
+                &corner.coords,
+            );
+            let dot_p2 = dot(
+                &self.constraints[corner.constraint_indices[1]],
+                &corner.coords,
+            );
             println!("This should be 0: {} {}", dot_p1, dot_p2);
         }
     }
@@ -374,8 +422,8 @@ impl AreaCalculator {
                 }
             }
             let mut dot_p = 0.;
-            for i in 0..self.costs.len() {
-                dot_p += pref[i] * (self.costs[i] as i32 - new_costs[i] as i32) as f64;
+            for (p, c) in pref.iter().zip(self.cost_diff(&new_costs)) {
+                dot_p += p * c as f64;
             }
             let is_really_inside = dot_p <= 0.;
             if is_really_inside != is_inside {
@@ -417,13 +465,18 @@ fn get_all_optimal_areas_as_string(
     let mut index: usize = 0;
     let mut num_corners = 0;
     while index < optimal_paths.len() {
-        let mut calculator = AreaCalculator::new(source, target, optimal_paths[index].clone());
+        let mut calculator: AreaCalculator = AreaCalculator::new(
+            source,
+            target,
+            optimal_paths[index].clone(),
+            Tolerance::EXACT,
+        );
         calculator.calculate_area(dijk, false);
         for i in 3..calculator.constraints.len() {
             let constraint = &calculator.constraints[i];
             let mut new_costs = vec![0; 3];
             for j in 0..3 {
-                new_costs[j] = (optimal_paths[index][j] as i32 - constraint[j]) as u32;
+                new_costs[j] = (optimal_paths[index][j] as i64 - constraint[j]) as u32;
             }
             let mut is_new = true;
             for j in 0..optimal_paths.len() {
@@ -468,13 +521,18 @@ fn get_all_optimal_costs_as_string(
     ));
     let mut index: usize = 0;
     while index < optimal_paths.len() {
-        let mut calculator = AreaCalculator::new(source, target, optimal_paths[index].clone());
+        let mut calculator: AreaCalculator = AreaCalculator::new(
+            source,
+            target,
+            optimal_paths[index].clone(),
+            Tolerance::EXACT,
+        );
         calculator.calculate_area(dijk, false);
         for i in 3..calculator.constraints.len() {
             let constraint = &calculator.constraints[i];
             let mut new_costs = vec![0; 3];
             for j in 0..3 {
-                new_costs[j] = (optimal_paths[index][j] as i32 - constraint[j]) as u32;
+                new_costs[j] = (optimal_paths[index][j] as i64 - constraint[j]) as u32;
             }
             let mut is_new = true;
             for j in 0..optimal_paths.len() {
@@ -500,6 +558,32 @@ fn get_all_optimal_costs_as_string(
     content
 }
 
+/// Scalar product of an integer constraint and the coordinates of a corner.
+fn dot<T: Number>(constraint: &[i64], coords: &[T]) -> T {
+    constraint
+        .iter()
+        .zip(coords)
+        .fold(T::zero(), |sum, (&c, x)| sum + T::from_i64(c) * x.clone())
+}
+
+/// Computes the preference space of the path from `source` to `target` with
+/// `costs`. Returns the constraints intersecting in its vertices and the
+/// number of shortest path queries.
+fn preference_space<T: Number>(
+    dijkstra: &mut Dijkstra,
+    source: u32,
+    target: u32,
+    costs: Vec<u32>,
+    tolerance: Tolerance,
+) -> (Vec<Vec<Vec<i64>>>, usize) {
+    let mut area_calculator = AreaCalculator::<T>::new(source, target, costs, tolerance);
+    area_calculator.calculate_area(dijkstra, false);
+    (
+        area_calculator.get_intersections(),
+        area_calculator.ch_counter,
+    )
+}
+
 fn get_cost_vector_for_pref(
     dijk: &mut graph::dijkstra::Dijkstra,
     preference: &[f64],
@@ -514,23 +598,49 @@ fn get_cost_vector_for_pref(
     costs
 }
 
+/// Writes the spaces as .space file. With `exact` the rational coordinates of
+/// every vertex follow its constraints.
 fn print_intersections_to_file(
     file_name: String,
-    intersections: Vec<Vec<Vec<Vec<i32>>>>,
-) -> std::io::Result<()> {
+    metric_names: &[String],
+    tolerance: Tolerance,
+    exact: bool,
+    intersections: Vec<Vec<Vec<Vec<i64>>>>,
+) -> Result<()> {
     let mut content: String;
-    content = format!("{}\n", intersections.len(),);
+    content = format!("{}\n", space_header(metric_names, tolerance));
+    if exact {
+        content = format!("{}# exact vertex coordinates\n", content);
+    }
+    content = format!("{}{}\n", content, intersections.len());
     for poly in intersections {
         content = format!("{}{}", content, poly.len(),);
-        for intersection in poly {
-            for constraint in intersection {
-                content = format!(
-                    "{} {} {} {}",
-                    content,
+        for vertex in poly {
+            let mut lines = Vec::new();
+            for constraint in &vertex {
+                let line = [
                     constraint[0] - constraint[2],
                     constraint[1] - constraint[2],
                     constraint[2],
-                );
+                ];
+                if let Some(c) = line.iter().find(|&&c| i32::try_from(c).is_err()) {
+                    bail!(
+                        "coefficient {} does not fit into 32 bits, the costs are too large for .space files",
+                        c
+                    );
+                }
+                content = format!("{} {} {} {}", content, line[0], line[1], line[2]);
+                // a * x + b * y = -c
+                lines.push(vec![
+                    Rational::from_i64(line[0]),
+                    Rational::from_i64(line[1]),
+                    Rational::from_i64(-line[2]),
+                ]);
+            }
+            if exact {
+                let point = intersection(&lines[0], &lines[1])
+                    .expect("the constraints of a vertex are not parallel");
+                content = format!("{} {} {}", content, point[0], point[1]);
             }
         }
         content = format!("{}\n", content,);
@@ -542,7 +652,7 @@ fn print_intersections_to_file(
 
 fn main() -> Result<()> {
     let opts = Opts::from_args();
-    let graph = graph::parse_minimal_graph_file(&opts.graph)?;
+    let graph = graph::parse_minimal_graph_file_cached(&opts.graph)?;
     let mut t_rng = thread_rng();
     let seed = opts.seed.unwrap_or_else(|| t_rng.next_u64());
     println!("using seed {}", seed);
@@ -551,6 +661,24 @@ fn main() -> Result<()> {
     let output = opts.output.unwrap_or_else(|| String::from("output.txt"));
 
     let mut dijk = graph::dijkstra::Dijkstra::new(&graph);
+    if let Some(tie_breaking) = opts.tie_breaking {
+        dijk.set_tie_breaking(tie_breaking);
+    }
+    let mut sampler = PairSampler::new(&graph);
+    if let Some(band) = DistanceBand::from_options(
+        &graph,
+        opts.distance_metric.as_deref(),
+        opts.min_distance,
+        opts.max_distance,
+    )? {
+        sampler = sampler.with_distance_band(band);
+    }
+    let no_pair_found = "Could not find a source and target within the distance band";
+    let tolerance = Tolerance::new(opts.epsilon, opts.slack)?;
+    if !tolerance.is_exact() {
+        println!("using tolerance: {}", tolerance);
+    }
+
     if modus == 0 {
         let num_paths = opts.num_paths.unwrap_or_else(|| 10);
         let num_prefs = opts.num_prefs.unwrap_or_else(|| 5);
@@ -562,14 +690,14 @@ fn main() -> Result<()> {
         let mut sum_ch = 0;
         let mut intersections = Vec::new();
         for i in 0..num_paths {
-            let nodes_dist = Uniform::from(0..dijk.graph.nodes.len() as u32);
             let pref_dist = Uniform::from(0..prefs.len() as u32);
             let mut s = 0;
             let mut t = 0;
             let mut costs = Vec::new();
             while costs.len() == 0 {
-                s = nodes_dist.sample(&mut rng);
-                t = nodes_dist.sample(&mut rng);
+                let pair = sampler.sample(&mut dijk, &mut rng).context(no_pair_found)?;
+                s = pair.0;
+                t = pair.1;
                 let pref_index = pref_dist.sample(&mut rng);
                 let pref = prefs[pref_index as usize].clone();
                 if let Some(result) = dijk.run(s, t, &pref) {
@@ -578,34 +706,43 @@ fn main() -> Result<()> {
                     }
                 }
             }
-            let mut area_calculator = AreaCalculator::new(s, t, costs.clone());
-            area_calculator.calculate_area(&mut dijk, false);
-            intersections.push(area_calculator.get_intersections());
-            sum_ch += area_calculator.ch_counter;
-            println!("{}: {}", i, area_calculator.ch_counter);
+            let (intersection, ch_counter) = if opts.exact {
+                preference_space::<Rational>(&mut dijk, s, t, costs, tolerance)
+            } else {
+                preference_space::<f64>(&mut dijk, s, t, costs, tolerance)
+            };
+            intersections.push(intersection);
+            sum_ch += ch_counter;
+            println!("{}: {}", i, ch_counter);
         }
-        print_intersections_to_file(output, intersections)?;
+        print_intersections_to_file(
+            output,
+            &graph.metric_names,
+            tolerance,
+            opts.exact,
+            intersections,
+        )?;
         println!(
             "Finished. Average CH calls: {}",
             sum_ch / num_paths as usize
         );
     } else if modus == 1 {
-        let nodes_dist = Uniform::from(0..dijk.graph.nodes.len() as u32);
         let mut s = 0;
         let mut t = 0;
         let mut costs = Vec::new();
         while costs.len() == 0 {
-            s = nodes_dist.sample(&mut rng);
-            t = nodes_dist.sample(&mut rng);
+            let pair = sampler.sample(&mut dijk, &mut rng).context(no_pair_found)?;
+            s = pair.0;
+            t = pair.1;
             let pref = randomized_preference(&mut rng, dijk.graph.dim.try_into().unwrap());
             if let Some(result) = dijk.run(s, t, &pref) {
                 for i in 0..result.costs.len() {
                     costs.push(result.costs[i] as u32);
                 }
             }
-            println!("pref: {:?}", pref);
+            println!("pref: {}", graph.describe_preference(&pref));
         }
-        let mut area_calculator = AreaCalculator::new(s, t, costs);
+        let mut area_calculator: AreaCalculator = AreaCalculator::new(s, t, costs, tolerance);
         println!("Compute area...");
         area_calculator.calculate_area(&mut dijk, false);
         println!("Finished. CH calls: {}", area_calculator.ch_counter);
@@ -619,22 +756,23 @@ fn main() -> Result<()> {
         let num_paths = opts.num_paths.unwrap_or_else(|| 100);
         let mut s = 0;
         let mut t = 0;
-        let mut most_complicated_area = AreaCalculator::new(s, t, Vec::new());
+        let mut most_complicated_area: AreaCalculator =
+            AreaCalculator::new(s, t, Vec::new(), tolerance);
         for i in 0..num_paths {
-            let nodes_dist = Uniform::from(0..graph.nodes.len() as u32);
             let mut costs = Vec::new();
             while costs.len() == 0 {
-                s = nodes_dist.sample(&mut rng);
-                t = nodes_dist.sample(&mut rng);
+                let pair = sampler.sample(&mut dijk, &mut rng).context(no_pair_found)?;
+                s = pair.0;
+                t = pair.1;
                 let pref = randomized_preference(&mut rng, graph.dim.try_into().unwrap());
                 if let Some(result) = dijk.run(s, t, &pref) {
                     for i in 0..result.costs.len() {
                         costs.push(result.costs[i] as u32);
                     }
                 }
-                println!("pref: {:?}", pref);
+                println!("pref: {}", graph.describe_preference(&pref));
             }
-            let mut area_calculator = AreaCalculator::new(s, t, costs.clone());
+            let mut area_calculator = AreaCalculator::new(s, t, costs.clone(), tolerance);
             println!("Compute area...");
             area_calculator.calculate_area(&mut dijk, false);
             println!("Finished. CH calls: {}", area_calculator.ch_counter);
@@ -653,76 +791,61 @@ fn main() -> Result<()> {
     } else if modus == 3 {
         let path_file = opts.path_file.unwrap();
         println!("loading file: {}", path_file.display());
-        let paths: Vec<Path> = serde_yaml::from_reader(BufReader::new(File::open(path_file)?))?;
+        let mut paths: Vec<Path> = serde_yaml::from_reader(BufReader::new(File::open(path_file)?))?;
+        check_loaded_paths(&graph, &mut paths, opts.repair_paths)?;
         println!("loaded {} paths", paths.len());
 
         let exact_start = Instant::now();
         let sum_ch = std::sync::atomic::AtomicUsize::new(0);
 
-        let thread_count = num_cpus::get().min(paths.len());
-        let item_per_thread = paths.len() / thread_count;
-
-        let intersections = vec![vec![]; paths.len()];
-        let mut path_plus_result: Vec<_> = paths.iter().zip(intersections.into_iter()).collect();
-
-        crossbeam::scope(|scope| {
-            let chunks = path_plus_result.chunks_mut(item_per_thread);
-            for chunk in chunks {
-                scope.spawn(|_| {
-                    let mut dijk = dijk.clone();
-                    for (p, intersection) in chunk {
-                        let s = *p.nodes.first().unwrap();
-                        let t = *p.nodes.last().unwrap();
-                        let mut area_calculator = AreaCalculator::new(
-                            s,
-                            t,
-                            p.total_dimension_costs.iter().map(|&v| v as u32).collect(),
-                        );
-                        area_calculator.calculate_area(&mut dijk, false);
-                        *intersection = area_calculator.get_intersections();
-                        sum_ch.fetch_add(
-                            area_calculator.ch_counter,
-                            std::sync::atomic::Ordering::Relaxed,
-                        );
-                        // println!("{}: {}", i, area_calculator.ch_counter);
-                    }
-                });
-            }
-        })
-        .expect("There were threading errors");
+        let pool = QueryPool::new(&dijk);
+        let exact = opts.exact;
+        let intersections = work_stealing_map(
+            &paths,
+            || pool.get(),
+            |dijk, p| {
+                let s = *p.nodes.first().unwrap();
+                let t = *p.nodes.last().unwrap();
+                let costs = p.total_dimension_costs.iter().map(|&v| v as u32).collect();
+                let (intersection, ch_counter) = if exact {
+                    preference_space::<Rational>(dijk, s, t, costs, tolerance)
+                } else {
+                    preference_space::<f64>(dijk, s, t, costs, tolerance)
+                };
+                sum_ch.fetch_add(ch_counter, std::sync::atomic::Ordering::Relaxed);
+                // println!("{}: {}", i, ch_counter);
+                intersection
+            },
+        );
         let exact_time = exact_start.elapsed();
         println!("exact spaces wall clock time: {}", exact_time.as_secs_f64());
 
-        let intersections = path_plus_result.into_iter().map(|t| t.1).collect();
-        print_intersections_to_file(output, intersections)?;
+        print_intersections_to_file(output, &graph.metric_names, tolerance, exact, intersections)?;
         println!(
             "Finished. Average CH calls: {}",
             sum_ch.into_inner() as f64 / paths.len() as f64
         );
     } else if modus == 4 {
-        let nodes_dist = Uniform::from(0..dijk.graph.nodes.len() as u32);
-        let s = nodes_dist.sample(&mut rng);
-        let t = nodes_dist.sample(&mut rng);
+        let (s, t) = sampler.sample(&mut dijk, &mut rng).context(no_pair_found)?;
         let all_areas = get_all_optimal_areas_as_string(&mut dijk, s, t);
         let mut file = File::create(output)?;
         file.write_all(all_areas.as_bytes())?;
     } else if modus == 5 {
-        let nodes_dist = Uniform::from(0..dijk.graph.nodes.len() as u32);
-        let s = nodes_dist.sample(&mut rng);
-        let t = nodes_dist.sample(&mut rng);
+        let (s, t) = sampler.sample(&mut dijk, &mut rng).context(no_pair_found)?;
         let all_paths = get_all_optimal_costs_as_string(&mut dijk, s, t);
         let mut file = File::create(output)?;
         file.write_all(all_paths.as_bytes())?;
     } else if modus == 6 {
         let path_file = opts.path_file.unwrap();
         println!("loading file: {}", path_file.display());
-        let paths: Vec<Path> = serde_yaml::from_reader(BufReader::new(File::open(path_file)?))?;
+        let mut paths: Vec<Path> = serde_yaml::from_reader(BufReader::new(File::open(path_file)?))?;
+        check_loaded_paths(&graph, &mut paths, opts.repair_paths)?;
         println!("loaded {} paths", paths.len());
 
         for i in 0..paths.len() {
             let s = paths[i].nodes.first().unwrap();
             let t = paths[i].nodes.last().unwrap();
-            let mut area_calculator = AreaCalculator::new(
+            let mut area_calculator: AreaCalculator = AreaCalculator::new(
                 *s,
                 *t,
                 paths[i]
@@ -730,6 +853,7 @@ fn main() -> Result<()> {
                     .iter()
                     .map(|&v| v as u32)
                     .collect(),
+                tolerance,
             );
             area_calculator.calculate_area(&mut dijk, false);
             area_calculator
@@ -0,0 +1,106 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use pref_polys::graph::{
+    self,
+    geojson::{write_paths_geojson, PathLabel},
+    path::{check_loaded_paths, Path},
+};
+use pref_polys::utils::MyVec;
+
+use anyhow::{ensure, Context, Result};
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the graph file
+    graph: PathBuf,
+    /// Path to the paths file
+    paths_file: PathBuf,
+    /// Path to write the GeoJSON file to
+    #[structopt(short = "o", long, default_value = "paths.geojson")]
+    output: PathBuf,
+    /// File with the cluster index of every path, one per line
+    #[structopt(short = "c", long)]
+    clusters: Option<PathBuf>,
+    /// File with the preference of every path, one per line with the values
+    /// separated by space
+    #[structopt(short = "p", long)]
+    preferences: Option<PathBuf>,
+    /// Recompute nodes and costs of loaded paths from their edges instead of
+    /// failing when they do not match the graph
+    #[structopt(long)]
+    repair_paths: bool,
+}
+
+/// Reads one value per non-empty line of `file_path`
+fn read_lines<T>(file_path: &PathBuf, parse: impl Fn(&str) -> Result<T>) -> Result<Vec<T>> {
+    fs::read_to_string(file_path)
+        .with_context(|| format!("failed to open {}", file_path.display()))?
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            parse(l.trim())
+                .with_context(|| format!("{}: invalid line {}", file_path.display(), i + 1))
+        })
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+    let graph = graph::parse_minimal_graph_file_cached(&opts.graph)?;
+
+    println!("loading file: {}", opts.paths_file.display());
+    let mut paths: Vec<Path> = serde_yaml::from_reader(BufReader::new(
+        File::open(&opts.paths_file)
+            .with_context(|| format!("failed to open {}", opts.paths_file.display()))?,
+    ))?;
+    println!("loaded {} paths", paths.len());
+    check_loaded_paths(&graph, &mut paths, opts.repair_paths)?;
+
+    let mut labels = vec![PathLabel::default(); paths.len()];
+    if let Some(clusters) = &opts.clusters {
+        let clusters = read_lines(clusters, |l| Ok(l.parse()?))?;
+        ensure!(
+            clusters.len() == paths.len(),
+            "Got {} clusters for {} paths",
+            clusters.len(),
+            paths.len()
+        );
+        for (label, cluster) in labels.iter_mut().zip(clusters) {
+            label.cluster = Some(cluster);
+        }
+    }
+    if let Some(preferences) = &opts.preferences {
+        let preferences = read_lines(preferences, |l| {
+            let pref = l
+                .split_whitespace()
+                .map(|v| v.parse())
+                .collect::<Result<Vec<f64>, _>>()?;
+            ensure!(
+                pref.len() == graph.dim as usize,
+                "expected {} values",
+                graph.dim
+            );
+            Ok(MyVec(pref))
+        })?;
+        ensure!(
+            preferences.len() == paths.len(),
+            "Got {} preferences for {} paths",
+            preferences.len(),
+            paths.len()
+        );
+        for (label, preference) in labels.iter_mut().zip(preferences) {
+            label.preference = Some(preference);
+        }
+    }
+
+    write_paths_geojson(&opts.output, &graph, &paths, &labels)?;
+    println!("wrote {}", opts.output.display());
+    Ok(())
+}
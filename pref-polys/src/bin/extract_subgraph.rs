@@ -0,0 +1,128 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use pref_polys::graph::{
+    self,
+    path::{check_loaded_paths, Path},
+    Coordinates,
+};
+use pref_polys::utils::io::metrics_header;
+
+use anyhow::{ensure, Context, Result};
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the graph file
+    graph: PathBuf,
+    /// Path to write the subgraph to (minimal format)
+    #[structopt(short = "o", long)]
+    output: PathBuf,
+    #[structopt(subcommand)]
+    selection: Selection,
+}
+
+#[derive(StructOpt)]
+enum Selection {
+    /// Nodes around the trajectories of a paths file
+    Paths {
+        /// Path to the paths file
+        paths_file: PathBuf,
+        /// Include all nodes within this many edges of the paths
+        #[structopt(short = "k", long, default_value = "0")]
+        hops: usize,
+        /// Path to write the paths with the ids of the subgraph to
+        #[structopt(short = "p", long)]
+        paths_output: Option<PathBuf>,
+        /// Recompute nodes and costs of the paths from their edges instead of
+        /// failing when they do not match the graph
+        #[structopt(long)]
+        repair_paths: bool,
+    },
+    /// Nodes within a coordinate box
+    Bbox {
+        min_lat: f64,
+        min_lon: f64,
+        max_lat: f64,
+        max_lon: f64,
+    },
+    /// Nodes with a ch level of at least `min_level`
+    Levels { min_level: u32 },
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+    let graph = graph::parse_minimal_graph_file_cached(&opts.graph)?;
+
+    let mut paths: Vec<Path> = Vec::new();
+    let nodes = match &opts.selection {
+        Selection::Paths {
+            paths_file,
+            hops,
+            repair_paths,
+            ..
+        } => {
+            println!("loading file: {}", paths_file.display());
+            paths = serde_yaml::from_reader(BufReader::new(
+                File::open(paths_file)
+                    .with_context(|| format!("failed to open {}", paths_file.display()))?,
+            ))?;
+            println!("loaded {} paths", paths.len());
+            check_loaded_paths(&graph, &mut paths, *repair_paths)?;
+            graph.nodes_around_paths(&paths, *hops)?
+        }
+        Selection::Bbox {
+            min_lat,
+            min_lon,
+            max_lat,
+            max_lon,
+        } => {
+            ensure!(graph.has_coordinates(), "The graph has no node coordinates");
+            graph.nodes_in_box(
+                Coordinates {
+                    lat: *min_lat,
+                    lon: *min_lon,
+                },
+                Coordinates {
+                    lat: *max_lat,
+                    lon: *max_lon,
+                },
+            )
+        }
+        Selection::Levels { min_level } => graph.nodes_from_level(*min_level),
+    };
+
+    let subgraph = graph.induced_subgraph(&nodes);
+    println!(
+        "Extracted subgraph with {} nodes and {} edges",
+        subgraph.graph.nodes.len(),
+        subgraph.graph.edges.len()
+    );
+    subgraph.graph.write_minimal_graph_file(&opts.output)?;
+    println!("Wrote subgraph to {}", opts.output.display());
+
+    if let Selection::Paths {
+        paths_output: Some(paths_output),
+        ..
+    } = &opts.selection
+    {
+        let mapped = paths
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                subgraph
+                    .map_path(p)
+                    .with_context(|| format!("path {} is not part of the subgraph", i))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut file = BufWriter::new(File::create(paths_output)?);
+        writeln!(file, "{}", metrics_header(&subgraph.graph.metric_names))?;
+        serde_yaml::to_writer(file, &mapped).context("Failed writing paths")?;
+        println!("Wrote paths to {}", paths_output.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use pref_polys::graph::{self, contraction::contract_graph};
+
+use anyhow::Result;
+
+#[derive(StructOpt)]
+struct Opts {
+    #[structopt(subcommand)]
+    format: Format,
+    /// Path to write the graph to (minimal format)
+    #[structopt(short = "o", long)]
+    output: PathBuf,
+    /// Build a contraction hierarchy before writing the graph
+    #[structopt(short = "c", long)]
+    contract: bool,
+}
+
+#[derive(StructOpt)]
+enum Format {
+    /// DIMACS shortest path challenge files
+    Dimacs {
+        /// One .gr file per metric, all with the same arcs in the same order
+        #[structopt(required = true)]
+        gr_files: Vec<PathBuf>,
+        /// Names of the metrics (default: the file names)
+        #[structopt(short = "n", long)]
+        names: Vec<String>,
+        /// .co file with the node coordinates
+        #[structopt(long)]
+        co_file: Option<PathBuf>,
+    },
+    /// CSV edge list with a header line
+    Csv {
+        /// Path to the CSV file
+        file: PathBuf,
+        /// Column with the source node ids
+        #[structopt(short = "s", long, default_value = "source")]
+        source: String,
+        /// Column with the target node ids
+        #[structopt(short = "t", long, default_value = "target")]
+        target: String,
+        /// Columns to use as metrics
+        #[structopt(short = "m", long, required = true)]
+        metrics: Vec<String>,
+    },
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+
+    let graph = match &opts.format {
+        Format::Dimacs {
+            gr_files,
+            names,
+            co_file,
+        } => {
+            let names = if names.is_empty() {
+                None
+            } else {
+                Some(names.clone())
+            };
+            graph::parse_dimacs_files(gr_files, names, co_file.as_deref())?
+        }
+        Format::Csv {
+            file,
+            source,
+            target,
+            metrics,
+        } => graph::parse_csv_edge_list_file(file, source, target, metrics)?,
+    };
+    println!(
+        "Imported graph with {} nodes and {} edges",
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+
+    if opts.contract {
+        let contracted = contract_graph(&graph);
+        contracted.graph.write_minimal_graph_file(&opts.output)?;
+    } else {
+        graph.write_minimal_graph_file(&opts.output)?;
+    }
+    println!("Wrote graph to {}", opts.output.display());
+    Ok(())
+}
@@ -10,10 +10,13 @@ use structopt::StructOpt;
 use pref_polys::{
     graph::{
         self,
-        dijkstra::{self, Dijkstra},
-        path::Path,
+        dijkstra::{self, Dijkstra, TieBreaking},
+        path::{check_loaded_paths, Path},
+        DistanceBand, PairSampler,
     },
     preference::{self, ApproxPoint},
+    tolerance::Tolerance,
+    utils::io::{metrics_header, space_header},
 };
 use pref_polys::{preference::SizeApproximation, utils::randomized_preference};
 use pref_polys::{
@@ -22,11 +25,8 @@ use pref_polys::{
 };
 
 use anyhow::{Context, Result};
+use rand::prelude::SliceRandom;
 use rand::rngs::StdRng;
-use rand::{
-    distributions::{Distribution, Uniform},
-    prelude::SliceRandom,
-};
 use rand::{thread_rng, RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
 
@@ -55,6 +55,30 @@ struct Opts {
     output_path: PathBuf,
     #[structopt(short = "f", long)]
     paths_file: Option<PathBuf>,
+    /// Minimum length of generated trajectories in the distance metric
+    #[structopt(long)]
+    min_distance: Option<f64>,
+    /// Maximum length of generated trajectories in the distance metric
+    #[structopt(long)]
+    max_distance: Option<f64>,
+    /// Metric for min and max distance (default: the first metric)
+    #[structopt(long)]
+    distance_metric: Option<String>,
+    /// Rule to choose between equal cost paths. Possible values are: none,
+    /// costs (lexicographically smallest cost vector)
+    #[structopt(long)]
+    tie_breaking: Option<TieBreaking>,
+    /// Recompute nodes and costs of loaded paths from their edges instead of
+    /// failing when they do not match the graph
+    #[structopt(long)]
+    #[serde(default)]
+    repair_paths: bool,
+    /// Paths within (1 + epsilon) times the optimal cost count as optimal
+    #[structopt(long)]
+    epsilon: Option<f64>,
+    /// Paths within this absolute cost above the optimum count as optimal
+    #[structopt(long)]
+    slack: Option<f64>,
     #[structopt(long)]
     config_file: Option<PathBuf>,
     /// Exit after writing config file
@@ -76,26 +100,33 @@ fn main() -> Result<()> {
         std::process::exit(1);
     }
 
-    let graph = graph::parse_minimal_graph_file(graph_file)?;
+    let tolerance = Tolerance::new(opts.epsilon.unwrap_or(0.0), opts.slack.unwrap_or(0.0))?;
+
+    let graph = graph::parse_minimal_graph_file_cached(graph_file)?;
     let mut dij = graph::dijkstra::Dijkstra::new(&graph);
+    if let Some(tie_breaking) = opts.tie_breaking {
+        dij.set_tie_breaking(tie_breaking);
+    }
 
     println!("using seed {}", seed);
     let mut rng = StdRng::seed_from_u64(seed);
 
     let path_start = Instant::now();
-    let paths = create_paths(&opts, &mut dij, &mut rng);
+    let paths = create_paths(&opts, &mut dij, &mut rng)?;
     let path_time = path_start.elapsed();
 
     println!("path finding time: {}", path_time.as_secs_f64());
     println!("{} paths found", paths.len());
 
-    let file = create_output_file(&opts, "paths.yml")?;
+    let mut file = create_output_file(&opts, "paths.yml")?;
+    writeln!(file, "{}", metrics_header(&graph.metric_names))?;
     serde_yaml::to_writer(file, &paths).context("Failed writing paths")?;
 
-    dijkstra::TimeReports::dijkstra();
+    dijkstra::TimeReports::dijkstra(&graph);
     dijkstra::TimeReports::clear_dijkstra_time();
 
     let mut set_pref = SetPreferences::new(dij, &paths)?;
+    set_pref.set_tolerance(tolerance);
 
     let approx_start = Instant::now();
     let approx = run_approximation(
@@ -111,10 +142,11 @@ fn main() -> Result<()> {
         approx_time.as_secs_f64()
     );
     preference::TimeReports::approximate_pref_spaces();
-    dijkstra::TimeReports::dijkstra();
+    dijkstra::TimeReports::dijkstra(&graph);
 
     let mut file = create_output_file(&opts, "inner.space")?;
 
+    writeln!(file, "{}", space_header(&graph.metric_names, tolerance))?;
     writeln!(file, "{}", approx.len())?;
     for a in &approx {
         let approx_points = ApproxPoint::inner_from_size_approximation(a);
@@ -128,6 +160,7 @@ fn main() -> Result<()> {
 
     let mut file = create_output_file(&opts, "outer.space")?;
 
+    writeln!(file, "{}", space_header(&graph.metric_names, tolerance))?;
     writeln!(file, "{}", approx.len())?;
     for a in approx {
         let approx_points = ApproxPoint::outer_from_size_approximation(&a);
@@ -181,7 +214,7 @@ fn create_output_file(opts: &Opts, filename: &str) -> Result<BufWriter<File>> {
     Ok(file)
 }
 
-fn create_paths(opts: &Opts, dij: &mut Dijkstra, rng: &mut StdRng) -> Vec<Path> {
+fn create_paths(opts: &Opts, dij: &mut Dijkstra, rng: &mut StdRng) -> Result<Vec<Path>> {
     let mut paths: Vec<Path> = Vec::new();
 
     if let Some(num_paths) = opts.num_paths {
@@ -195,13 +228,19 @@ fn create_paths(opts: &Opts, dij: &mut Dijkstra, rng: &mut StdRng) -> Vec<Path>
             ));
         }
 
-        let nodes_dist = Uniform::from(0..dij.graph.nodes.len() as u32);
+        let mut sampler = PairSampler::new(dij.graph);
+        if let Some(band) = DistanceBand::from_options(
+            dij.graph,
+            opts.distance_metric.as_deref(),
+            opts.min_distance,
+            opts.max_distance,
+        )? {
+            sampler = sampler.with_distance_band(band);
+        }
         while paths.len() < num_paths {
-            let s = nodes_dist.sample(rng);
-            let t = nodes_dist.sample(rng);
-            if s == t {
-                continue;
-            }
+            let (s, t) = sampler
+                .sample(dij, rng)
+                .context("Could not find a source and target within the distance band")?;
             let pref = if opts.num_paths.is_some() {
                 preferences.choose(rng).unwrap()
             } else {
@@ -221,8 +260,9 @@ fn create_paths(opts: &Opts, dij: &mut Dijkstra, rng: &mut StdRng) -> Vec<Path>
         paths = serde_yaml::from_reader(file)
             .context("Trying to read paths.")
             .unwrap();
+        check_loaded_paths(dij.graph, &mut paths, opts.repair_paths)?;
     }
-    paths
+    Ok(paths)
 }
 
 fn run_approximation(
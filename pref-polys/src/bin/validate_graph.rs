@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use pref_polys::graph;
+
+use anyhow::Result;
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the graph file
+    graph: PathBuf,
+    /// Read the graph in the legacy format with osm ids and coordinates
+    #[structopt(short = "l", long)]
+    legacy: bool,
+    /// Maximum number of issues to print per kind
+    #[structopt(short = "m", long, default_value = "10")]
+    max_issues: usize,
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+
+    let graph = if opts.legacy {
+        graph::parse_graph_file(&opts.graph)?
+    } else {
+        graph::parse_minimal_graph_file(&opts.graph)?
+    };
+
+    println!("Validating graph...");
+    let report = graph.validate();
+
+    for (kind, count) in report.summary() {
+        println!("{} ({}):", kind, count);
+        for issue in report
+            .issues
+            .iter()
+            .filter(|i| i.kind() == kind)
+            .take(opts.max_issues)
+        {
+            println!("    {}", issue);
+        }
+        if count > opts.max_issues {
+            println!("    ...");
+        }
+    }
+
+    let errors = report.errors().count();
+    let warnings = report.warnings().count();
+    println!("{} errors, {} warnings", errors, warnings);
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
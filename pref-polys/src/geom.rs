@@ -1,4 +1,7 @@
-use crate::{float_eq, utils::MyVec};
+use crate::{
+    utils::{number::Number, MyVec},
+    ACCURACY,
+};
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum Orientation {
@@ -6,12 +9,18 @@ pub enum Orientation {
     Outside,
 }
 
-pub fn orientation_test(constraint: &[f64], preference: &[f64]) -> Orientation {
+/// Whether `preference` satisfies `constraint * preference >= last`, where
+/// `last` is the last coefficient of the constraint. For floating point
+/// numbers the constraint is relaxed by `ACCURACY`.
+pub fn orientation_test<T: Number>(constraint: &[T], preference: &[T]) -> Orientation {
     assert_eq!(constraint.len(), preference.len() + 1);
 
-    let last = *constraint.last().unwrap();
-    let sum: f64 = constraint.iter().zip(preference).map(|(c, p)| c * p).sum();
-    if sum >= last || float_eq!(sum, last) {
+    let last = constraint.last().unwrap().clone();
+    let sum = constraint
+        .iter()
+        .zip(preference)
+        .fold(T::zero(), |sum, (c, p)| sum + c.clone() * p.clone());
+    if sum >= last - T::tolerance(ACCURACY) {
         Orientation::Inside
     } else {
         Orientation::Outside
@@ -47,37 +56,35 @@ pub fn angle(p: &[f64], center: &[f64]) -> f64 {
     y.atan2(x)
 }
 
-pub fn intersection(a: &[f64], b: &[f64]) -> Option<MyVec<f64>> {
+/// Intersection of the lines `a[0] * x + a[1] * y = a[2]` and `b[0] * x +
+/// b[1] * y = b[2]`, or `None` if they are parallel. For floating point
+/// numbers lines count as parallel up to `ACCURACY`.
+pub fn intersection<T: Number>(a: &[T], b: &[T]) -> Option<MyVec<T>> {
     assert_eq!(
         a.len(),
         3,
         "Intersection is only implemented for dimension 2 (len = 3)"
     );
     assert_eq!(a.len(), b.len());
+    let tolerance = T::tolerance(ACCURACY);
 
-    let y_denom = -a[0] * b[1] + b[0] * a[1];
-    let y_num = a[2] * b[0] - a[0] * b[2];
+    let y_denom = -a[0].clone() * b[1].clone() + b[0].clone() * a[1].clone();
+    let y_num = a[2].clone() * b[0].clone() - a[0].clone() * b[2].clone();
 
-    let y = if float_eq!(y_denom, 0.0) {
+    if y_denom.abs() <= tolerance {
         return None;
-    } else {
-        y_num / y_denom
-    };
-    let x_num;
-    let x_denom;
-
-    if b[0] == 0.0 {
-        x_num = a[2] - a[1] * y;
-        x_denom = a[0];
-    } else {
-        x_num = b[2] - b[1] * y;
-        x_denom = b[0];
     }
-    let x = if float_eq!(x_denom, 0.0) {
-        return None;
+    let y = y_num / y_denom;
+
+    let (x_num, x_denom) = if b[0].is_zero() {
+        (a[2].clone() - a[1].clone() * y.clone(), a[0].clone())
     } else {
-        x_num / x_denom
+        (b[2].clone() - b[1].clone() * y.clone(), b[0].clone())
     };
+    if x_denom.abs() <= tolerance {
+        return None;
+    }
+    let x = x_num / x_denom;
 
     Some(vec![x, y].into())
 }
@@ -106,7 +113,10 @@ mod tests {
 
     use rand::prelude::{SliceRandom, ThreadRng};
 
-    use crate::utils::same_array;
+    use crate::utils::{
+        number::{convert, Rational},
+        same_array,
+    };
 
     #[test]
     fn test_center_point() {
@@ -207,6 +217,26 @@ mod tests {
         assert_eq!(Orientation::Inside, orientation_test(&b, &point));
     }
 
+    #[test]
+    fn test_exact_intersection() {
+        let a: Vec<Rational> = convert(&[2.0, -3.0, 4.0]);
+        let b: Vec<Rational> = convert(&[-3.0, 7.0, 2.0]);
+        let point = intersection(&a, &b).unwrap();
+
+        let fifth = |n| Rational::from_i64(n) / Rational::from_i64(5);
+        assert_eq!(point.0, vec![fifth(34), fifth(16)]);
+        assert_eq!(Orientation::Inside, orientation_test(&a, &point));
+        assert_eq!(Orientation::Inside, orientation_test(&b, &point));
+
+        // a point outside by less than the accuracy of floating point tests
+        let mut outside = point.clone();
+        outside[0] -= Rational::from_i64(1) / Rational::from_i64(1_000_000_000);
+        assert_eq!(Orientation::Outside, orientation_test(&a, &outside));
+        let outside: Vec<f64> = outside.iter().map(Number::to_f64).collect();
+        let a: Vec<f64> = a.iter().map(Number::to_f64).collect();
+        assert_eq!(Orientation::Inside, orientation_test(&a, &outside));
+    }
+
     #[test]
     fn test_parallel_lines_do_not_intersect() {
         let a = [0.0, 1.0, 0.0];
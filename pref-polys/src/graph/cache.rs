@@ -0,0 +1,417 @@
+//! Binary cache for fully constructed graphs.
+//!
+//! Parsing the text format and sorting the edges in `Graph::new` takes a long
+//! time for large graphs. The cache stores the finished `Graph` including both
+//! CSR edge arrays in little endian byte order, so that loading it only means
+//! reading the file and copying the arrays out of it. The graph owns all its
+//! arrays, so the file is read into memory at once instead of being mapped.
+//!
+//! Layout (all integers little endian):
+//!
+//! - magic bytes `PPGRAPH\0`, format version (u32)
+//! - cost dimension (u32), node count (u64), edge count (u64)
+//! - per metric: length of the name (u32) and the UTF-8 encoded name
+//! - per node: id, ch level (u32 each), latitude and longitude (f64 each, NaN
+//!   if the node has no coordinates)
+//! - per edge: id, source, target, both replaced edges (u32 each, `u32::MAX`
+//!   if the edge is no shortcut) and `dim` costs (f64)
+//! - outgoing and then incoming CSR arrays: offsets (node count + 1 u32s),
+//!   edge ids and target ids (edge count u32s each), costs (edge count * dim
+//!   f64s)
+
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use super::{edge::GraphEdges, parse_minimal_graph_file, Edge, Graph, Node};
+use crate::utils::MyVec;
+
+const MAGIC: &[u8; 8] = b"PPGRAPH\0";
+/// Has to be increased whenever the layout of the cache changes.
+pub const CACHE_VERSION: u32 = 3;
+const NO_EDGE: u32 = u32::MAX;
+
+impl Graph {
+    /// Writes the graph into a binary cache file which can be loaded with
+    /// `Graph::from_cache`. The cache is written to a temporary file next to
+    /// `file_path` and renamed afterwards, so an interrupted run does not
+    /// leave a truncated cache behind.
+    pub fn write_cache(&self, file_path: impl AsRef<Path>) -> Result<()> {
+        let file_path = file_path.as_ref();
+        let mut tmp_name = file_path.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(format!(".tmp-{}", std::process::id()));
+        let tmp_path = file_path.with_file_name(tmp_name);
+
+        let written = File::create(&tmp_path)
+            .with_context(|| format!("creating graph cache {}", tmp_path.display()))
+            .and_then(|file| {
+                let mut w = BufWriter::new(file);
+                self.write_cache_data(&mut w)?;
+                w.into_inner()?.sync_all()?;
+                Ok(())
+            })
+            .and_then(|_| {
+                std::fs::rename(&tmp_path, file_path)
+                    .with_context(|| format!("moving graph cache to {}", file_path.display()))
+            });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        written
+    }
+
+    fn write_cache_data(&self, w: &mut impl Write) -> Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&CACHE_VERSION.to_le_bytes())?;
+        w.write_all(&self.dim.to_le_bytes())?;
+        w.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        w.write_all(&(self.edges.len() as u64).to_le_bytes())?;
+
+        for name in self.metric_names.iter() {
+            w.write_all(&(name.len() as u32).to_le_bytes())?;
+            w.write_all(name.as_bytes())?;
+        }
+
+        for n in self.nodes.iter() {
+            w.write_all(&n.id.to_le_bytes())?;
+            w.write_all(&n.ch_level.to_le_bytes())?;
+            let (lat, lon) = n
+                .coordinates
+                .map_or((f64::NAN, f64::NAN), |c| (c.lat, c.lon));
+            write_f64s(w, &[lat, lon])?;
+        }
+
+        for e in self.edges.iter() {
+            let (edge1, edge2) = e.replaced_edges.unwrap_or((NO_EDGE, NO_EDGE));
+            for v in &[e.id, e.source_id, e.target_id, edge1, edge2] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            write_f64s(w, &e.edge_costs)?;
+        }
+
+        for graph_edges in &[&self.edges_out, &self.edges_in] {
+            write_u32s(w, &graph_edges.offset)?;
+            write_u32s(w, &graph_edges.edge_id)?;
+            write_u32s(w, &graph_edges.target_id)?;
+            write_f64s(w, &graph_edges.costs)?;
+        }
+        Ok(())
+    }
+
+    /// Loads a graph from a binary cache file written by `Graph::write_cache`.
+    pub fn from_cache(file_path: impl AsRef<Path>) -> Result<Graph> {
+        let file_path = file_path.as_ref();
+        let data = std::fs::read(file_path)
+            .with_context(|| format!("reading graph cache {}", file_path.display()))?;
+        let mut r = CacheReader {
+            data: &data,
+            pos: 0,
+        };
+
+        ensure!(
+            r.bytes(MAGIC.len())? == MAGIC,
+            "{} is no graph cache",
+            file_path.display()
+        );
+        let version = r.u32()?;
+        if version != CACHE_VERSION {
+            bail!(
+                "graph cache {} has version {}, expected {}",
+                file_path.display(),
+                version,
+                CACHE_VERSION
+            );
+        }
+        let dim = r.u32()?;
+        let node_count: usize = r.u64()?.try_into()?;
+        let edge_count: usize = r.u64()?.try_into()?;
+
+        let mut metric_names = Vec::with_capacity(dim as usize);
+        for _ in 0..dim {
+            let len = r.u32()?;
+            let name = std::str::from_utf8(r.bytes(len as usize)?)
+                .context("graph cache contains invalid metric name")?;
+            metric_names.push(name.to_string());
+        }
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for index in 0..node_count {
+            let id = r.u32()?;
+            ensure!(
+                id as usize == index,
+                "graph cache contains node {} at index {}",
+                id,
+                index
+            );
+            let ch_level = r.u32()?;
+            let coordinates = r.f64s(2)?;
+            nodes.push(if coordinates.iter().any(|c| c.is_nan()) {
+                Node::new(id, ch_level)
+            } else {
+                Node::with_coordinates(id, ch_level, coordinates[0], coordinates[1])
+            });
+        }
+
+        let mut edges = Vec::with_capacity(edge_count);
+        for index in 0..edge_count {
+            let id = r.u32()?;
+            ensure!(
+                id as usize == index,
+                "graph cache contains edge {} at index {}",
+                id,
+                index
+            );
+            let source_id = r.u32()?;
+            let target_id = r.u32()?;
+            let edge1 = r.u32()?;
+            let edge2 = r.u32()?;
+            let replaced_edges = if edge1 == NO_EDGE {
+                None
+            } else {
+                Some((edge1, edge2))
+            };
+            let costs = r.f64s(dim as usize)?;
+            edges.push(Edge::new(id, source_id, target_id, costs, replaced_edges));
+        }
+
+        let edges_out = r.graph_edges(node_count, edge_count, dim as usize)?;
+        let edges_in = r.graph_edges(node_count, edge_count, dim as usize)?;
+        ensure!(
+            r.pos == r.data.len(),
+            "graph cache {} has trailing data",
+            file_path.display()
+        );
+
+        Ok(Graph {
+            nodes: MyVec(nodes),
+            edges: MyVec(edges),
+            dim,
+            metric_names: MyVec(metric_names),
+            edges_in,
+            edges_out,
+        })
+    }
+}
+
+/// Returns the path of the cache file belonging to a graph file.
+pub fn cache_path(file_path: impl AsRef<Path>) -> PathBuf {
+    let mut name = file_path.as_ref().as_os_str().to_owned();
+    name.push(".cache");
+    PathBuf::from(name)
+}
+
+/// Like `parse_minimal_graph_file`, but uses the binary cache next to the
+/// graph file if it is newer than the graph file. Otherwise the graph file is
+/// parsed and the cache is (re)written.
+pub fn parse_minimal_graph_file_cached(file_path: impl AsRef<Path>) -> Result<Graph> {
+    let file_path = file_path.as_ref();
+    let cache = cache_path(file_path);
+
+    if is_newer(&cache, file_path) {
+        let start = Instant::now();
+        match Graph::from_cache(&cache) {
+            Ok(graph) => {
+                println!(
+                    "graph loading time (cache): {}s",
+                    start.elapsed().as_secs_f64()
+                );
+                return Ok(graph);
+            }
+            Err(err) => println!("ignoring graph cache: {:#}", err),
+        }
+    }
+
+    let graph = parse_minimal_graph_file(file_path)?;
+    if let Err(err) = graph.write_cache(&cache) {
+        println!("could not write graph cache: {:#}", err);
+    } else {
+        println!("wrote graph cache {}", cache.display());
+    }
+    Ok(graph)
+}
+
+fn is_newer(a: &Path, b: &Path) -> bool {
+    let modified = |p: &Path| p.metadata().and_then(|m| m.modified());
+    match (modified(a), modified(b)) {
+        (Ok(a), Ok(b)) => a > b,
+        _ => false,
+    }
+}
+
+fn write_u32s(w: &mut impl Write, values: &[u32]) -> std::io::Result<()> {
+    values
+        .iter()
+        .try_for_each(|v| w.write_all(&v.to_le_bytes()))
+}
+
+fn write_f64s(w: &mut impl Write, values: &[f64]) -> std::io::Result<()> {
+    values
+        .iter()
+        .try_for_each(|v| w.write_all(&v.to_le_bytes()))
+}
+
+struct CacheReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CacheReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .context("graph cache is truncated")?;
+        let res = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(res)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn u32s(&mut self, len: usize) -> Result<Vec<u32>> {
+        Ok(self
+            .bytes(len.checked_mul(4).context("graph cache is corrupt")?)?
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect())
+    }
+
+    fn f64s(&mut self, len: usize) -> Result<Vec<f64>> {
+        Ok(self
+            .bytes(len.checked_mul(8).context("graph cache is corrupt")?)?
+            .chunks_exact(8)
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+            .collect())
+    }
+
+    fn graph_edges(
+        &mut self,
+        node_count: usize,
+        edge_count: usize,
+        dim: usize,
+    ) -> Result<GraphEdges> {
+        let offset = self.u32s(node_count + 1)?;
+        let edge_id = self.u32s(edge_count)?;
+        let target_id = self.u32s(edge_count)?;
+        let costs = self.f64s(edge_count * dim)?;
+
+        ensure!(
+            offset.windows(2).all(|w| w[0] <= w[1])
+                && offset.last().map(|&o| o as usize) == Some(edge_count),
+            "graph cache contains invalid edge offsets"
+        );
+        ensure!(
+            target_id.iter().all(|&t| (t as usize) < node_count)
+                && edge_id.iter().all(|&e| (e as usize) < edge_count),
+            "graph cache contains invalid edge references"
+        );
+
+        Ok(GraphEdges {
+            offset: MyVec(offset),
+            edge_id: MyVec(edge_id),
+            target_id: MyVec(target_id),
+            costs: MyVec(costs),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{edge::EdgeDirection, parse_graph_file};
+    use crate::utils::same_array;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+
+        let mut path = std::env::temp_dir();
+        path.push(format!("pref-polys-cache-test-{}", std::process::id()));
+        graph.write_cache(&path).unwrap();
+        let cached = Graph::from_cache(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // the temporary file was renamed
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(format!(".tmp-{}", std::process::id()));
+        assert!(!Path::new(&tmp_path).exists());
+
+        assert_eq!(graph.dim, cached.dim);
+        assert_eq!(graph.metric_names.0, cached.metric_names.0);
+        assert_eq!(graph.nodes.len(), cached.nodes.len());
+        for (a, b) in graph.nodes.iter().zip(cached.nodes.iter()) {
+            assert_eq!(
+                (a.id, a.ch_level, a.coordinates),
+                (b.id, b.ch_level, b.coordinates)
+            );
+        }
+        assert_eq!(graph.edges.len(), cached.edges.len());
+        for (a, b) in graph.edges.iter().zip(cached.edges.iter()) {
+            assert_eq!(
+                (a.id, a.source_id, a.target_id, a.replaced_edges),
+                (b.id, b.source_id, b.target_id, b.replaced_edges)
+            );
+            assert!(same_array(&a.edge_costs, &b.edge_costs));
+        }
+        for n in 0..graph.nodes.len() as u32 {
+            for dir in &[EdgeDirection::In, EdgeDirection::Out] {
+                let expected: Vec<_> = graph.edges_of(n, *dir).collect();
+                let found: Vec<_> = cached.edges_of(n, *dir).collect();
+                assert_eq!(expected.len(), found.len());
+                for (a, b) in expected.iter().zip(&found) {
+                    assert_eq!((a.edge_id, a.target_id), (b.edge_id, b.target_id));
+                    assert!(same_array(a.edge_costs, b.edge_costs));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_with_wrong_version_is_rejected() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "pref-polys-cache-version-test-{}",
+            std::process::id()
+        ));
+        graph.write_cache(&path).unwrap();
+        let mut data = std::fs::read(&path).unwrap();
+        data[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        assert!(Graph::from_cache(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cache_with_wrong_node_id_is_rejected() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+
+        let mut path = std::env::temp_dir();
+        path.push(format!("pref-polys-cache-id-test-{}", std::process::id()));
+        graph.write_cache(&path).unwrap();
+        let mut data = std::fs::read(&path).unwrap();
+        // the id of the first node follows the header and the metric names
+        let names: usize = graph.metric_names.iter().map(|n| 4 + n.len()).sum();
+        let first_node = MAGIC.len() + 4 + 4 + 8 + 8 + names;
+        data[first_node..first_node + 4].copy_from_slice(&1u32.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        let err = Graph::from_cache(&path).unwrap_err();
+        assert!(err.to_string().contains("node 1 at index 0"), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,425 @@
+//! Construction of contraction hierarchies for graphs with multiple costs.
+//!
+//! When a node `v` is contracted, a shortcut `u -> w` replacing `u -> v -> w`
+//! is only left out if there is a witness path from `u` to `w` avoiding `v`
+//! which is at most as expensive in every single cost dimension. Such a path
+//! is at least as good as the shortcut for every preference on the simplex,
+//! so the resulting hierarchy answers queries for all preferences correctly.
+//!
+//! Witnesses are only searched with a few fixed preferences and a limited
+//! number of settled nodes. Missing a witness only leads to a superfluous
+//! shortcut, never to a wrong hierarchy.
+
+use std::{cmp::Reverse, collections::BinaryHeap, time::Instant};
+
+use ordered_float::OrderedFloat;
+use priority_queue::PriorityQueue;
+
+use super::{
+    path::{add_edge_costs, costs_by_alpha},
+    Edge, Graph, Node,
+};
+use crate::utils::{equal_weights, MyVec, Preference};
+
+/// Number of nodes a single witness search may settle before it gives up.
+const WITNESS_SETTLE_LIMIT: usize = 500;
+
+pub struct ContractedGraph {
+    pub graph: Graph,
+    /// Id of every node of the input graph in the contracted graph
+    pub node_map: MyVec<u32>,
+}
+
+struct Shortcut {
+    source: u32,
+    target: u32,
+    replaced_edges: (u32, u32),
+    costs: Vec<f64>,
+}
+
+/// Contracts all nodes of `graph` and returns the graph including the
+/// shortcuts. The ch levels of the input are ignored and replaced by the
+/// contraction order.
+///
+/// Edge ids of the input graph stay the same, shortcuts are appended after
+/// them. Node ids change because `Graph::new` orders the nodes by level, use
+/// `node_map` to translate them.
+pub fn contract_graph(graph: &Graph) -> ContractedGraph {
+    println!("Contracting graph...");
+    let start = Instant::now();
+
+    let mut contractor = Contractor::new(graph);
+    let node_count = graph.nodes.len() as u32;
+
+    let mut queue = PriorityQueue::new();
+    for node in 0..node_count {
+        let (priority, _) = contractor.simulate(node);
+        queue.push(node, Reverse(priority));
+    }
+
+    let mut levels = MyVec(vec![0; node_count as usize]);
+    let mut level = 0;
+    while let Some((node, _)) = queue.pop() {
+        // priorities of the remaining nodes are only updated lazily
+        let (priority, shortcuts) = contractor.simulate(node);
+        if let Some((_, Reverse(next))) = queue.peek() {
+            if priority > *next {
+                queue.push(node, Reverse(priority));
+                continue;
+            }
+        }
+        contractor.contract(node, shortcuts);
+        levels[node] = level;
+        level += 1;
+    }
+
+    let shortcut_count = contractor.edges.len() - graph.edges.len();
+    println!(
+        "Contracted {} nodes and added {} shortcuts in {}ms",
+        node_count,
+        shortcut_count,
+        start.elapsed().as_millis()
+    );
+
+    let nodes = graph
+        .nodes
+        .iter()
+        .map(|n| Node {
+            id: n.id,
+            ch_level: levels[n.id],
+            coordinates: n.coordinates,
+        })
+        .collect();
+
+    ContractedGraph {
+        graph: Graph::new(nodes, contractor.edges, graph.metric_names.0.clone()),
+        // the levels are unique, so a node's level becomes its new id
+        node_map: levels,
+    }
+}
+
+struct Contractor {
+    edges: Vec<Edge>,
+    // edges between nodes which are not contracted yet
+    edges_out: MyVec<Vec<u32>>,
+    edges_in: MyVec<Vec<u32>>,
+    contracted_neighbors: MyVec<u32>,
+    witness_preferences: Vec<Preference>,
+    witness_search: WitnessSearch,
+}
+
+impl Contractor {
+    fn new(graph: &Graph) -> Self {
+        let node_count = graph.nodes.len();
+        let dim = graph.dim as usize;
+
+        let mut edges_out = MyVec(vec![Vec::new(); node_count]);
+        let mut edges_in = MyVec(vec![Vec::new(); node_count]);
+        let edges = graph
+            .edges
+            .iter()
+            .map(|e| {
+                edges_out[e.source_id].push(e.id);
+                edges_in[e.target_id].push(e.id);
+                Edge::new(
+                    e.id,
+                    e.source_id,
+                    e.target_id,
+                    e.edge_costs.clone(),
+                    e.replaced_edges,
+                )
+            })
+            .collect();
+
+        // Equal weights and one preference leaning towards each metric
+        let mut witness_preferences = vec![equal_weights(dim)];
+        for i in 0..dim {
+            let mut pref = vec![1.0 / (2 * dim) as f64; dim];
+            pref[i] += 0.5;
+            witness_preferences.push(MyVec(pref));
+        }
+
+        Contractor {
+            edges,
+            edges_out,
+            edges_in,
+            contracted_neighbors: MyVec(vec![0; node_count]),
+            witness_preferences,
+            witness_search: WitnessSearch::new(node_count),
+        }
+    }
+
+    /// Returns the priority of `node` and the shortcuts needed to contract it.
+    fn simulate(&mut self, node: u32) -> (i64, Vec<Shortcut>) {
+        let shortcuts = self.shortcuts(node);
+        let removed_edges = self.edges_in[node].len() + self.edges_out[node].len();
+        let priority =
+            shortcuts.len() as i64 - removed_edges as i64 + self.contracted_neighbors[node] as i64;
+        (priority, shortcuts)
+    }
+
+    fn shortcuts(&mut self, node: u32) -> Vec<Shortcut> {
+        let mut shortcuts: Vec<Shortcut> = Vec::new();
+
+        for &in_edge in &self.edges_in[node] {
+            let source = self.edges[in_edge as usize].source_id;
+            if source == node {
+                continue;
+            }
+
+            let mut candidates: Vec<Shortcut> = self.edges_out[node]
+                .iter()
+                .filter_map(|&out_edge| {
+                    let target = self.edges[out_edge as usize].target_id;
+                    if target == node || target == source {
+                        return None;
+                    }
+                    let mut costs = self.edges[in_edge as usize].edge_costs.clone();
+                    add_edge_costs(&mut costs, &self.edges[out_edge as usize].edge_costs);
+                    Some(Shortcut {
+                        source,
+                        target,
+                        replaced_edges: (in_edge, out_edge),
+                        costs,
+                    })
+                })
+                .collect();
+
+            for alpha in &self.witness_preferences {
+                if candidates.is_empty() {
+                    break;
+                }
+                let max_cost = candidates
+                    .iter()
+                    .map(|c| costs_by_alpha(&c.costs, alpha))
+                    .fold(0.0, f64::max);
+                self.witness_search.run(
+                    &self.edges,
+                    &self.edges_out,
+                    source,
+                    node,
+                    alpha,
+                    max_cost,
+                );
+                let search = &self.witness_search;
+                let edges = &self.edges;
+                candidates.retain(|c| match search.path_costs(edges, c.target) {
+                    Some(witness) => !dominates(&witness, &c.costs),
+                    None => true,
+                });
+            }
+
+            // parallel edges can lead to several shortcuts between the same nodes
+            for candidate in candidates {
+                let is_dominated = shortcuts.iter().any(|s| {
+                    s.source == candidate.source
+                        && s.target == candidate.target
+                        && dominates(&s.costs, &candidate.costs)
+                });
+                if !is_dominated {
+                    shortcuts.retain(|s| {
+                        s.source != candidate.source
+                            || s.target != candidate.target
+                            || !dominates(&candidate.costs, &s.costs)
+                    });
+                    shortcuts.push(candidate);
+                }
+            }
+        }
+        shortcuts
+    }
+
+    fn contract(&mut self, node: u32, shortcuts: Vec<Shortcut>) {
+        let in_edges = std::mem::take(&mut self.edges_in[node]);
+        let out_edges = std::mem::take(&mut self.edges_out[node]);
+
+        for edge_id in in_edges {
+            let source = self.edges[edge_id as usize].source_id;
+            if source != node {
+                self.edges_out[source].retain(|&e| e != edge_id);
+                self.contracted_neighbors[source] += 1;
+            }
+        }
+        for edge_id in out_edges {
+            let target = self.edges[edge_id as usize].target_id;
+            if target != node {
+                self.edges_in[target].retain(|&e| e != edge_id);
+                self.contracted_neighbors[target] += 1;
+            }
+        }
+
+        for shortcut in shortcuts {
+            let id = self.edges.len() as u32;
+            self.edges_out[shortcut.source].push(id);
+            self.edges_in[shortcut.target].push(id);
+            self.edges.push(Edge::new(
+                id,
+                shortcut.source,
+                shortcut.target,
+                shortcut.costs,
+                Some(shortcut.replaced_edges),
+            ));
+        }
+    }
+}
+
+/// Whether `a` is at most as expensive as `b` in every dimension.
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a <= b)
+}
+
+/// Single criterion Dijkstra on the not yet contracted part of the graph
+struct WitnessSearch {
+    dist: MyVec<f64>,
+    prev: MyVec<Option<u32>>,
+    heap: BinaryHeap<Reverse<(OrderedFloat<f64>, u32)>>,
+    touched: Vec<u32>,
+}
+
+impl WitnessSearch {
+    fn new(node_count: usize) -> Self {
+        WitnessSearch {
+            dist: MyVec(vec![f64::MAX; node_count]),
+            prev: MyVec(vec![None; node_count]),
+            heap: BinaryHeap::new(),
+            touched: Vec::new(),
+        }
+    }
+
+    fn run(
+        &mut self,
+        edges: &[Edge],
+        edges_out: &MyVec<Vec<u32>>,
+        source: u32,
+        avoid: u32,
+        alpha: &[f64],
+        max_cost: f64,
+    ) {
+        for &t in &self.touched {
+            self.dist[t] = f64::MAX;
+            self.prev[t] = None;
+        }
+        self.touched.clear();
+        self.heap.clear();
+
+        self.dist[source] = 0.0;
+        self.touched.push(source);
+        self.heap.push(Reverse((OrderedFloat(0.0), source)));
+
+        let mut settled = 0;
+        while let Some(Reverse((OrderedFloat(dist), node))) = self.heap.pop() {
+            if dist > self.dist[node] {
+                continue;
+            }
+            if dist > max_cost || settled == WITNESS_SETTLE_LIMIT {
+                break;
+            }
+            settled += 1;
+
+            for &edge_id in &edges_out[node] {
+                let edge = &edges[edge_id as usize];
+                if edge.target_id == avoid {
+                    continue;
+                }
+                let alt = dist + costs_by_alpha(&edge.edge_costs, alpha);
+                if alt < self.dist[edge.target_id] {
+                    if self.dist[edge.target_id] == f64::MAX {
+                        self.touched.push(edge.target_id);
+                    }
+                    self.dist[edge.target_id] = alt;
+                    self.prev[edge.target_id] = Some(edge_id);
+                    self.heap.push(Reverse((OrderedFloat(alt), edge.target_id)));
+                }
+            }
+        }
+    }
+
+    /// Costs of the best path to `target` found by the last search
+    fn path_costs(&self, edges: &[Edge], target: u32) -> Option<Vec<f64>> {
+        if self.dist[target] == f64::MAX {
+            return None;
+        }
+        let mut costs = vec![0.0; edges[0].edge_costs.len()];
+        let mut node = target;
+        while let Some(edge_id) = self.prev[node] {
+            let edge = &edges[edge_id as usize];
+            add_edge_costs(&mut costs, &edge.edge_costs);
+            node = edge.source_id;
+        }
+        Some(costs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::float_eq;
+    use crate::graph::{
+        dijkstra::{Dijkstra, NDijkstra},
+        parse_graph_file, parse_minimal_graph_file,
+        path::randomized_preference,
+    };
+
+    fn assert_same_distances(original: &Graph, contracted: &Graph, node_map: &MyVec<u32>) {
+        let mut n_dijkstra = NDijkstra::new(original);
+        let mut ch_dijkstra = Dijkstra::new(contracted);
+        let mut rng = rand::thread_rng();
+        let dim = original.dim.try_into().unwrap();
+
+        for _ in 0..10 {
+            let alpha = randomized_preference(dim, &mut rng);
+            for s in 0..(original.nodes.len() as u32) {
+                for t in 0..(original.nodes.len() as u32) {
+                    let n_costs = n_dijkstra.run(s, t, &alpha);
+                    let ch_result = ch_dijkstra.run(node_map[s], node_map[t], &alpha);
+                    assert!(float_eq!(
+                        ch_result.as_ref().map(|r| r.total_cost).unwrap_or(-1.),
+                        n_costs.unwrap_or(-1.)
+                    ));
+
+                    // the unpacked path has to consist of original edges only
+                    if let Some(result) = ch_result {
+                        let edges: Vec<u32> = result
+                            .edges
+                            .iter()
+                            .flat_map(|&e| contracted.unpack_edge(e))
+                            .collect();
+                        let mut node = node_map[s];
+                        for &e in &edges {
+                            assert!(e < original.edges.len() as u32);
+                            assert_eq!(contracted.edges[e].source_id, node);
+                            node = contracted.edges[e].target_id;
+                        }
+                        assert_eq!(node, node_map[t]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_contraction_matches_ndijkstra() {
+        let graph = parse_graph_file("./resources/testGraph").unwrap();
+        let contracted = contract_graph(&graph);
+
+        let mut levels: Vec<_> = contracted.graph.nodes.iter().map(|n| n.ch_level).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        assert_eq!(levels.len(), graph.nodes.len());
+
+        assert_same_distances(&graph, &contracted.graph, &contracted.node_map);
+
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "pref-polys-contraction-test-{}",
+            std::process::id()
+        ));
+        contracted.graph.write_minimal_graph_file(&path).unwrap();
+        let read = parse_minimal_graph_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_same_distances(&graph, &read, &contracted.node_map);
+    }
+}
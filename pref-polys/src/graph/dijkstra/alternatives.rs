@@ -0,0 +1,439 @@
+//! Near-optimal alternatives to the shortest path for a preference.
+//!
+//! Both generators modify the graph during the search by blocking or
+//! penalizing edges, which would invalidate the contraction hierarchy. They
+//! therefore run a plain Dijkstra on the original edges and ignore shortcuts.
+
+use std::collections::{BinaryHeap, HashSet};
+
+use super::state::State;
+use crate::graph::{
+    edge::EdgeDirection,
+    path::{add_edge_costs, costs_by_alpha, Path},
+    Graph,
+};
+use crate::utils::MyVec;
+
+/// Dijkstra on the original edges with blocked nodes and modified edge
+/// weights.
+struct RestrictedSearch<'a> {
+    graph: &'a Graph,
+    dist: MyVec<f64>,
+    previous: MyVec<Option<u32>>,
+    touched: Vec<u32>,
+}
+
+impl<'a> RestrictedSearch<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        RestrictedSearch {
+            graph,
+            dist: MyVec(vec![f64::MAX; graph.nodes.len()]),
+            previous: MyVec(vec![None; graph.nodes.len()]),
+            touched: Vec::new(),
+        }
+    }
+
+    /// Returns the edges of the shortest path. `weight` gives the weight of an
+    /// edge or `None` if it is blocked.
+    fn run(
+        &mut self,
+        source: u32,
+        target: u32,
+        blocked_nodes: &MyVec<bool>,
+        weight: impl Fn(u32, &[f64]) -> Option<f64>,
+    ) -> Option<Vec<u32>> {
+        for &node in &self.touched {
+            self.dist[node] = f64::MAX;
+            self.previous[node] = None;
+        }
+        self.touched.clear();
+
+        let mut heap = BinaryHeap::new();
+        self.dist[source] = 0.0;
+        self.touched.push(source);
+        heap.push(State::new(source, EdgeDirection::Out));
+
+        while let Some(State {
+            node_id,
+            total_cost,
+            ..
+        }) = heap.pop()
+        {
+            if total_cost > self.dist[node_id] {
+                continue;
+            }
+            if node_id == target {
+                let mut edges = Vec::new();
+                let mut current = target;
+                while let Some(edge) = self.previous[current] {
+                    edges.push(edge);
+                    current = self.graph.edges[edge].source_id;
+                }
+                edges.reverse();
+                return Some(edges);
+            }
+            for half_edge in self.graph.edges_of(node_id, EdgeDirection::Out) {
+                let next = half_edge.target_id;
+                if blocked_nodes[next]
+                    || self.graph.edges[half_edge.edge_id].replaced_edges.is_some()
+                {
+                    continue;
+                }
+                let edge_weight = match weight(half_edge.edge_id, half_edge.edge_costs) {
+                    Some(w) => w,
+                    None => continue,
+                };
+                let next_cost = total_cost + edge_weight;
+                if next_cost < self.dist[next] {
+                    if self.dist[next] == f64::MAX {
+                        self.touched.push(next);
+                    }
+                    self.dist[next] = next_cost;
+                    self.previous[next] = Some(half_edge.edge_id);
+                    heap.push(State {
+                        node_id: next,
+                        total_cost: next_cost,
+                        direction: EdgeDirection::Out,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+fn path_from_edges(graph: &Graph, source: u32, edges: Vec<u32>) -> Path {
+    let mut nodes = vec![source];
+    let mut total_dimension_costs = MyVec(vec![0.0; graph.dim as usize]);
+    for &e in &edges {
+        nodes.push(graph.edges[e].target_id);
+        add_edge_costs(&mut total_dimension_costs, &graph.edges[e].edge_costs);
+    }
+    Path {
+        nodes: MyVec(nodes),
+        edges: MyVec(edges),
+        total_dimension_costs,
+    }
+}
+
+fn cost_of_edges(graph: &Graph, edges: &[u32], alpha: &[f64]) -> f64 {
+    edges
+        .iter()
+        .map(|&e| costs_by_alpha(&graph.edges[e].edge_costs, alpha))
+        .sum()
+}
+
+/// Yen's algorithm for the `k` shortest simple paths from `source` to
+/// `target` for the preference `alpha`. The paths are sorted by their cost
+/// for `alpha`. Fewer than `k` paths are returned if there are no more simple
+/// paths.
+pub fn k_shortest_paths(
+    graph: &Graph,
+    source: u32,
+    target: u32,
+    alpha: &[f64],
+    k: usize,
+) -> Vec<Path> {
+    let mut search = RestrictedSearch::new(graph);
+    let mut blocked_nodes = MyVec(vec![false; graph.nodes.len()]);
+    let weight = |_, costs: &[f64]| Some(costs_by_alpha(costs, alpha));
+
+    let mut found: Vec<Path> = Vec::new();
+    if k == 0 {
+        return found;
+    }
+    match search.run(source, target, &blocked_nodes, weight) {
+        Some(edges) => found.push(path_from_edges(graph, source, edges)),
+        None => return found,
+    }
+
+    // (cost, path), the costs are only compared with each other
+    let mut candidates: Vec<(f64, Path)> = Vec::new();
+    let mut known: HashSet<Vec<u32>> = HashSet::new();
+    known.insert(found[0].edges.0.clone());
+
+    while found.len() < k {
+        let last = found.last().unwrap().clone();
+        for spur_index in 0..last.edges.len() {
+            let spur_node = last.nodes[spur_index];
+            let root = &last.edges.0[..spur_index];
+
+            let blocked_edges: HashSet<u32> = found
+                .iter()
+                .filter(|p| p.edges.len() > spur_index && &p.edges.0[..spur_index] == root)
+                .map(|p| p.edges[spur_index])
+                .collect();
+            for &n in &last.nodes.0[..spur_index] {
+                blocked_nodes[n] = true;
+            }
+
+            let spur = search.run(spur_node, target, &blocked_nodes, |e, costs| {
+                if blocked_edges.contains(&e) {
+                    None
+                } else {
+                    Some(costs_by_alpha(costs, alpha))
+                }
+            });
+
+            for &n in &last.nodes.0[..spur_index] {
+                blocked_nodes[n] = false;
+            }
+
+            if let Some(spur) = spur {
+                let mut edges = root.to_vec();
+                edges.extend(spur);
+                if known.insert(edges.clone()) {
+                    let cost = cost_of_edges(graph, &edges, alpha);
+                    candidates.push((cost, path_from_edges(graph, source, edges)));
+                }
+            }
+        }
+
+        // Take the cheapest candidate, the first one on equal costs
+        let best = candidates.iter().enumerate().fold(
+            None,
+            |best: Option<(usize, f64)>, (i, (cost, _))| match best {
+                Some((_, best_cost)) if best_cost <= *cost => best,
+                _ => Some((i, *cost)),
+            },
+        );
+        match best {
+            Some((index, _)) => found.push(candidates.remove(index).1),
+            None => break,
+        }
+    }
+    found
+}
+
+/// Parameters of the penalty method for alternative routes.
+#[derive(Debug, Clone)]
+pub struct AlternativeOptions {
+    /// Maximal number of returned paths, including the shortest path
+    pub max_paths: usize,
+    /// Factor applied to the weight of all edges of a found path
+    pub penalty: f64,
+    /// Maximal cost of an alternative relative to the shortest path
+    pub max_stretch: f64,
+    /// Maximal share of the cost of an alternative which it may have in
+    /// common with any of the previously found paths
+    pub max_overlap: f64,
+    /// Number of searches after which the method gives up
+    pub max_iterations: usize,
+}
+
+impl Default for AlternativeOptions {
+    fn default() -> Self {
+        AlternativeOptions {
+            max_paths: 3,
+            penalty: 1.4,
+            max_stretch: 1.25,
+            max_overlap: 0.8,
+            max_iterations: 20,
+        }
+    }
+}
+
+/// Finds alternative routes with the penalty method: After each search the
+/// edges of the found path are penalized, so that the next search prefers
+/// other edges. Paths are only accepted if they are not too long and differ
+/// enough from the paths found before. The first path is the shortest path
+/// for `alpha`.
+pub fn penalty_alternatives(
+    graph: &Graph,
+    source: u32,
+    target: u32,
+    alpha: &[f64],
+    options: &AlternativeOptions,
+) -> Vec<Path> {
+    let mut search = RestrictedSearch::new(graph);
+    let blocked_nodes = MyVec(vec![false; graph.nodes.len()]);
+    let mut factors = MyVec(vec![1.0; graph.edges.len()]);
+
+    let mut found: Vec<(f64, Path)> = Vec::new();
+    let mut known: HashSet<Vec<u32>> = HashSet::new();
+    for _ in 0..options.max_iterations {
+        if found.len() >= options.max_paths {
+            break;
+        }
+        let edges = match search.run(source, target, &blocked_nodes, |e, costs| {
+            Some(factors[e] * costs_by_alpha(costs, alpha))
+        }) {
+            Some(edges) => edges,
+            None => break,
+        };
+        for &e in &edges {
+            factors[e] *= options.penalty;
+        }
+        if !known.insert(edges.clone()) {
+            continue;
+        }
+
+        let cost = cost_of_edges(graph, &edges, alpha);
+        let acceptable = match found.first() {
+            None => true,
+            Some((shortest, _)) => {
+                let edge_set: HashSet<u32> = edges.iter().copied().collect();
+                cost <= options.max_stretch * shortest
+                    && found.iter().all(|(_, p)| {
+                        let shared: Vec<u32> = p
+                            .edges
+                            .iter()
+                            .copied()
+                            .filter(|e| edge_set.contains(e))
+                            .collect();
+                        let overlap = if cost > 0.0 {
+                            cost_of_edges(graph, &shared, alpha) / cost
+                        } else {
+                            // without costs the share of the edges counts
+                            shared.len() as f64 / edges.len().max(1) as f64
+                        };
+                        overlap <= options.max_overlap
+                    })
+            }
+        };
+        if acceptable {
+            found.push((cost, path_from_edges(graph, source, edges)));
+        }
+    }
+    found.into_iter().map(|(_, p)| p).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float_eq;
+    use crate::graph::{dijkstra::NDijkstra, parse_graph_file};
+    use crate::utils::{equal_weights, same_array};
+
+    /// Costs of all simple paths, found by depth first search
+    fn all_simple_path_costs(
+        graph: &Graph,
+        node: u32,
+        target: u32,
+        alpha: &[f64],
+        visited: &mut Vec<u32>,
+        cost: f64,
+        result: &mut Vec<f64>,
+    ) {
+        if node == target {
+            result.push(cost);
+            return;
+        }
+        visited.push(node);
+        for half_edge in graph.edges_of(node, EdgeDirection::Out) {
+            if graph.edges[half_edge.edge_id].replaced_edges.is_some()
+                || visited.contains(&half_edge.target_id)
+            {
+                continue;
+            }
+            let next_cost = cost + costs_by_alpha(half_edge.edge_costs, alpha);
+            all_simple_path_costs(
+                graph,
+                half_edge.target_id,
+                target,
+                alpha,
+                visited,
+                next_cost,
+                result,
+            );
+        }
+        visited.pop();
+    }
+
+    fn check_path(graph: &Graph, path: &Path, source: u32, target: u32) {
+        assert_eq!(*path.nodes.first().unwrap(), source);
+        assert_eq!(*path.nodes.last().unwrap(), target);
+        for (i, &e) in path.edges.iter().enumerate() {
+            assert_eq!(graph.edges[e].source_id, path.nodes[i]);
+            assert_eq!(graph.edges[e].target_id, path.nodes[i + 1]);
+        }
+        let mut nodes = path.nodes.0.clone();
+        nodes.sort_unstable();
+        nodes.dedup();
+        assert_eq!(nodes.len(), path.nodes.len(), "path is not simple");
+    }
+
+    #[test]
+    fn test_k_shortest_paths() {
+        let graph = parse_graph_file("./resources/testGraph").unwrap();
+        let alpha = equal_weights(graph.dim as usize);
+        let n = graph.nodes.len() as u32;
+        let k = 5;
+
+        for s in 0..n {
+            for t in 0..n {
+                let paths = k_shortest_paths(&graph, s, t, &alpha, k);
+
+                let mut expected = Vec::new();
+                all_simple_path_costs(&graph, s, t, &alpha, &mut Vec::new(), 0.0, &mut expected);
+                expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                assert_eq!(paths.len(), expected.len().min(k));
+
+                let mut edge_sequences = HashSet::new();
+                for (path, &expected_cost) in paths.iter().zip(&expected) {
+                    check_path(&graph, path, s, t);
+                    assert!(edge_sequences.insert(path.edges.0.clone()));
+                    let cost = costs_by_alpha(&path.total_dimension_costs, &alpha);
+                    assert!(float_eq!(cost, expected_cost));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_penalty_alternatives() {
+        let graph = parse_graph_file("./resources/testGraph").unwrap();
+        let alpha = equal_weights(graph.dim as usize);
+        let mut dijkstra = NDijkstra::new(&graph);
+        let options = AlternativeOptions {
+            max_paths: 4,
+            max_stretch: 1.5,
+            ..Default::default()
+        };
+        let n = graph.nodes.len() as u32;
+
+        let mut alternatives = 0;
+        for s in 0..n {
+            for t in 0..n {
+                let paths = penalty_alternatives(&graph, s, t, &alpha, &options);
+                let shortest = dijkstra.run(s, t, &alpha);
+                assert_eq!(paths.is_empty(), shortest.is_none());
+                if let Some(shortest) = shortest {
+                    assert!(paths.len() <= options.max_paths);
+                    let first_cost = costs_by_alpha(&paths[0].total_dimension_costs, &alpha);
+                    assert!(float_eq!(first_cost, shortest));
+                    for path in &paths {
+                        check_path(&graph, path, s, t);
+                        let cost = costs_by_alpha(&path.total_dimension_costs, &alpha);
+                        assert!(cost <= options.max_stretch * shortest + crate::ACCURACY);
+                    }
+                    alternatives += paths.len() - 1;
+                }
+            }
+        }
+        assert!(alternatives > 0);
+    }
+
+    #[test]
+    fn test_penalty_alternatives_with_zero_costs() {
+        use crate::graph::parse_minimal_graph;
+
+        // 0 -> 2 -> 1 and 0 -> 2 -> 3 -> 1 share the free edge 0 -> 2
+        let graph = parse_minimal_graph(
+            "1\na\n4\n4\n0 0\n1 0\n2 0\n3 0\n\
+             0 0 2 0 -1 -1\n1 2 1 1 -1 -1\n2 2 3 0.5 -1 -1\n3 3 1 0.6 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let paths = penalty_alternatives(&graph, 0, 1, &[1.0], &AlternativeOptions::default());
+        let edges: Vec<_> = paths.iter().map(|p| p.edges.0.clone()).collect();
+        assert_eq!(edges, vec![vec![0, 1], vec![0, 2, 3]]);
+
+        // a route without costs
+        let graph =
+            parse_minimal_graph("1\na\n2\n1\n0 0\n1 0\n0 0 1 0 -1 -1\n".as_bytes()).unwrap();
+        let paths = penalty_alternatives(&graph, 0, 1, &[1.0], &AlternativeOptions::default());
+        assert_eq!(paths.len(), 1);
+        assert!(same_array(&paths[0].total_dimension_costs, &[0.0]));
+    }
+}
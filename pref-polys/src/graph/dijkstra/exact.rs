@@ -0,0 +1,142 @@
+//! CH query in exact arithmetic.
+//!
+//! The floating point query rounds the preference and the costs by preference,
+//! so if several routes are almost equally expensive it may return one which
+//! is not optimal for the exact preference. This query compares the costs by
+//! preference with rationals. It is much slower and only meant to double
+//! check such ties.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{
+    graph::{edge::EdgeDirection, path::add_edge_costs, Graph},
+    utils::{
+        number::{dot, Rational},
+        MyVec,
+    },
+};
+
+/// Rational cost by preference and cost vector of the best route to or from
+/// every node reached by an upward search.
+type SearchSpace = HashMap<u32, (Rational, Vec<f64>)>;
+
+/// Returns the cost vector of a route from `source` to `target` which is
+/// optimal for `alpha` in exact arithmetic, or `None` if there is none. The
+/// cost vector is summed up in floating point, which is exact for integer
+/// costs.
+pub fn exact_shortest_costs(
+    graph: &Graph,
+    source: u32,
+    target: u32,
+    alpha: &[Rational],
+) -> Option<MyVec<f64>> {
+    let forward = upward_search(graph, source, EdgeDirection::Out, alpha, None);
+    let bound = forward.get(&target).map(|(cost, _)| cost.clone());
+    let backward = upward_search(graph, target, EdgeDirection::In, alpha, bound);
+
+    backward
+        .iter()
+        .filter_map(|(node, (cost_b, costs_b))| {
+            let (cost_f, costs_f) = forward.get(node)?;
+            Some((cost_f + cost_b, costs_f, costs_b))
+        })
+        .min_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, costs_f, costs_b)| {
+            let mut costs = costs_f.clone();
+            add_edge_costs(&mut costs, costs_b);
+            MyVec(costs)
+        })
+}
+
+/// Dijkstra over the edges to nodes of at least the same ch level. Nodes
+/// farther away than `bound` are not settled.
+fn upward_search(
+    graph: &Graph,
+    start: u32,
+    direction: EdgeDirection,
+    alpha: &[Rational],
+    bound: Option<Rational>,
+) -> SearchSpace {
+    let zero = Rational::from_integer(0.into());
+    let mut best: HashMap<u32, (Rational, Vec<f64>)> = HashMap::new();
+    best.insert(start, (zero.clone(), vec![0.0; graph.dim as usize]));
+    let mut settled = SearchSpace::new();
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse((zero, start)));
+
+    while let Some(Reverse((cost, node))) = heap.pop() {
+        if settled.contains_key(&node) || bound.as_ref().is_some_and(|b| cost > *b) {
+            continue;
+        }
+        let costs = best[&node].1.clone();
+        for half_edge in graph.edges_of(node, direction) {
+            if graph.nodes[node].ch_level > graph.nodes[half_edge.target_id].ch_level {
+                break;
+            }
+            let next_cost = &cost + dot(half_edge.edge_costs, alpha);
+            let improves = best
+                .get(&half_edge.target_id)
+                .is_none_or(|(c, _)| next_cost < *c);
+            if improves && !settled.contains_key(&half_edge.target_id) {
+                let mut next_costs = costs.clone();
+                add_edge_costs(&mut next_costs, half_edge.edge_costs);
+                best.insert(half_edge.target_id, (next_cost.clone(), next_costs));
+                heap.push(Reverse((next_cost, half_edge.target_id)));
+            }
+        }
+        settled.insert(node, (cost, costs));
+    }
+    settled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::path::costs_by_alpha;
+    use crate::graph::{dijkstra::Dijkstra, parse_graph_file, parse_minimal_graph};
+    use crate::utils::{
+        number::{convert, Number},
+        same_array,
+    };
+
+    #[test]
+    fn test_same_costs_as_dijkstra() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let alpha = [0.125, 0.5, 0.25, 0.125];
+        let exact_alpha = convert::<Rational>(&alpha);
+        for (s, t) in itertools::iproduct!(0..12, 0..12) {
+            let expected = dijkstra.run(s, t, &alpha).map(|r| r.total_cost);
+            let costs = exact_shortest_costs(&graph, s, t, &exact_alpha);
+            match (expected, costs) {
+                (Some(expected), Some(costs)) => {
+                    assert!(crate::float_eq!(costs_by_alpha(&costs, &alpha), expected))
+                }
+                (None, None) => (),
+                other => panic!("results differ: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_in_floating_point() {
+        // two routes from 0 to 1 with costs (1, 0) and (0, 1)
+        let graph = parse_minimal_graph(
+            "2\na b\n3\n3\n0 0\n1 0\n2 0\n0 0 1 1 0 -1 -1\n1 0 2 0 1 -1 -1\n2 2 1 0 0 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        // both round to 0.5, but the second metric is slightly cheaper
+        let tiny = Rational::from_i64(1) / Rational::from_i64(1 << 62);
+        let half = Rational::from_i64(1) / Rational::from_i64(2);
+        assert_eq!((&half + &tiny).to_f64(), 0.5);
+
+        let costs = exact_shortest_costs(&graph, 0, 1, &[&half + &tiny, &half - &tiny]).unwrap();
+        assert!(same_array(&costs, &[0.0, 1.0]));
+        let costs = exact_shortest_costs(&graph, 0, 1, &[&half - &tiny, &half + &tiny]).unwrap();
+        assert!(same_array(&costs, &[1.0, 0.0]));
+    }
+}
@@ -0,0 +1,229 @@
+//! Landmark lower bounds for goal-directed search (ALT).
+//!
+//! For every landmark `l` and every metric the distances from `l` to all nodes
+//! and from all nodes to `l` are stored. By the triangle inequality
+//! `d(v, t) >= d(l, t) - d(l, v)` and `d(v, t) >= d(v, l) - d(t, l)` hold in
+//! every metric. The bounds are computed per metric and combined with the
+//! preference at query time: since the cost of a path for `alpha` is the
+//! combination of its costs in the single metrics, `sum_i alpha_i * lb_i` is a
+//! lower bound as well. Maxima and non-negative combinations of consistent
+//! potentials are consistent, so A* with this potential settles every node with
+//! its exact distance.
+
+use std::collections::BinaryHeap;
+
+use super::state::State;
+use crate::graph::{edge::EdgeDirection, Graph};
+use crate::utils::MyVec;
+
+pub struct Landmarks {
+    landmarks: Vec<u32>,
+    node_count: usize,
+    dim: usize,
+    /// Distance from the landmark to the node, indexed by
+    /// `(landmark * node_count + node) * dim + metric`
+    from_landmark: Vec<f64>,
+    /// Distance from the node to the landmark, same layout
+    to_landmark: Vec<f64>,
+}
+
+/// Distances from or to `start` in a single metric, `f64::INFINITY` for
+/// unreachable nodes.
+fn metric_distances(
+    graph: &Graph,
+    start: u32,
+    metric: usize,
+    direction: EdgeDirection,
+) -> MyVec<f64> {
+    let mut dist = MyVec(vec![f64::INFINITY; graph.nodes.len()]);
+    let mut heap = BinaryHeap::new();
+    dist[start] = 0.0;
+    heap.push(State::new(start, direction));
+
+    while let Some(State {
+        node_id,
+        total_cost,
+        ..
+    }) = heap.pop()
+    {
+        if total_cost > dist[node_id] {
+            continue;
+        }
+        for half_edge in graph.edges_of(node_id, direction) {
+            let next_cost = total_cost + half_edge.edge_costs[metric];
+            if next_cost < dist[half_edge.target_id] {
+                dist[half_edge.target_id] = next_cost;
+                heap.push(State {
+                    node_id: half_edge.target_id,
+                    total_cost: next_cost,
+                    direction,
+                });
+            }
+        }
+    }
+    dist
+}
+
+impl Landmarks {
+    /// Precomputes the distances for the given landmarks.
+    pub fn new(graph: &Graph, landmarks: Vec<u32>) -> Landmarks {
+        let node_count = graph.nodes.len();
+        let dim = graph.dim as usize;
+        let size = landmarks.len() * node_count * dim;
+        let mut me = Landmarks {
+            landmarks,
+            node_count,
+            dim,
+            from_landmark: vec![0.0; size],
+            to_landmark: vec![0.0; size],
+        };
+
+        for (index, &landmark) in me.landmarks.iter().enumerate() {
+            for metric in 0..dim {
+                let from = metric_distances(graph, landmark, metric, EdgeDirection::Out);
+                let to = metric_distances(graph, landmark, metric, EdgeDirection::In);
+                for node in 0..node_count {
+                    let i = (index * node_count + node) * dim + metric;
+                    me.from_landmark[i] = from[node];
+                    me.to_landmark[i] = to[node];
+                }
+            }
+        }
+        me
+    }
+
+    /// Selects `count` landmarks by farthest selection: Every new landmark is
+    /// the node with the largest distance to the closest landmark so far, in
+    /// the sum of all metrics.
+    pub fn select(graph: &Graph, count: usize) -> Landmarks {
+        let count = count.min(graph.nodes.len());
+        let mut landmarks = Vec::with_capacity(count);
+        let mut closest = MyVec(vec![f64::INFINITY; graph.nodes.len()]);
+
+        let mut next = 0;
+        while landmarks.len() < count {
+            landmarks.push(next);
+            let mut dist = MyVec(vec![0.0; graph.nodes.len()]);
+            for metric in 0..graph.dim as usize {
+                let metric_dist = metric_distances(graph, next, metric, EdgeDirection::Out);
+                dist.iter_mut()
+                    .zip(metric_dist.iter())
+                    .for_each(|(d, m)| *d += m);
+            }
+            closest
+                .iter_mut()
+                .zip(dist.iter())
+                .for_each(|(c, &d)| *c = c.min(d));
+
+            next = (0..graph.nodes.len() as u32)
+                .filter(|n| !landmarks.contains(n))
+                .fold(None, |best: Option<u32>, n| match best {
+                    Some(b) if closest[b] >= closest[n] => Some(b),
+                    _ => Some(n),
+                })
+                .unwrap_or(0);
+        }
+        Landmarks::new(graph, landmarks)
+    }
+
+    pub fn landmarks(&self) -> &[u32] {
+        &self.landmarks
+    }
+
+    pub(super) fn fits(&self, graph: &Graph) -> bool {
+        self.node_count == graph.nodes.len() && self.dim == graph.dim as usize
+    }
+
+    /// Lower bound on the distance from `node` to `target` in `metric`,
+    /// `f64::INFINITY` if `target` can not be reached.
+    fn metric_bound(&self, node: u32, target: u32, metric: usize) -> f64 {
+        let mut bound: f64 = 0.0;
+        for index in 0..self.landmarks.len() {
+            let node_index = (index * self.node_count + node as usize) * self.dim + metric;
+            let target_index = (index * self.node_count + target as usize) * self.dim + metric;
+
+            let from_node = self.from_landmark[node_index];
+            let from_target = self.from_landmark[target_index];
+            if from_node.is_finite() && from_target.is_finite() {
+                bound = bound.max(from_target - from_node);
+            }
+
+            let to_node = self.to_landmark[node_index];
+            let to_target = self.to_landmark[target_index];
+            if to_target.is_finite() {
+                // If the landmark is reachable from the target but not from
+                // the node, the target is not reachable either
+                bound = bound.max(to_node - to_target);
+            }
+        }
+        bound
+    }
+
+    /// Lower bound on the distance from `node` to `target` in every metric.
+    pub fn lower_bounds(&self, node: u32, target: u32) -> Vec<f64> {
+        (0..self.dim)
+            .map(|metric| self.metric_bound(node, target, metric))
+            .collect()
+    }
+
+    /// Lower bound on the cost from `node` to `target` for `alpha`,
+    /// `f64::INFINITY` if `target` can not be reached.
+    pub fn potential(&self, node: u32, target: u32, alpha: &[f64]) -> f64 {
+        let mut potential = 0.0;
+        for (metric, a) in alpha.iter().enumerate() {
+            let bound = self.metric_bound(node, target, metric);
+            if bound.is_infinite() {
+                return f64::INFINITY;
+            }
+            potential += a * bound;
+        }
+        potential
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::float_eq;
+    use crate::graph::{dijkstra::NDijkstra, parse_graph_file};
+    use crate::utils::randomized_preference;
+
+    #[test]
+    fn test_alt_matches_plain_search() {
+        for file in &["./resources/testGraph", "./resources/concTestGraph"] {
+            let graph = parse_graph_file(file).unwrap();
+            let landmarks = Landmarks::select(&graph, 3);
+            assert_eq!(landmarks.landmarks().len(), 3);
+
+            let mut plain = NDijkstra::new(&graph);
+            let mut alt = NDijkstra::new(&graph);
+            alt.set_landmarks(&landmarks);
+            let mut rng = StdRng::seed_from_u64(11);
+            let n = graph.nodes.len() as u32;
+
+            for s in 0..n {
+                let alpha = randomized_preference(&mut rng, graph.dim as usize);
+                for t in 0..n {
+                    let cost = plain.run(s, t, &alpha);
+                    let alt_cost = alt.run(s, t, &alpha);
+                    assert!(float_eq!(cost.unwrap_or(-1.), alt_cost.unwrap_or(-1.)));
+
+                    let potential = landmarks.potential(s, t, &alpha);
+                    match cost {
+                        Some(cost) => assert!(potential <= cost + crate::ACCURACY),
+                        None => assert!(potential >= 0.0),
+                    }
+
+                    if let Some(path) = alt.path(t) {
+                        assert!(float_eq!(
+                            crate::graph::path::costs_by_alpha(&path.total_dimension_costs, &alpha),
+                            cost.unwrap()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
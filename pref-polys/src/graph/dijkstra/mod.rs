@@ -1,4 +1,4 @@
-use std::collections::binary_heap::BinaryHeap;
+use std::{cmp::Ordering, collections::binary_heap::BinaryHeap};
 
 use state::State;
 
@@ -6,10 +6,30 @@ use crate::graph::{path::costs_by_alpha, Graph};
 use crate::utils::metrics::YesNoTime;
 use crate::utils::MyVec;
 
+mod alternatives;
+mod exact;
+mod landmarks;
 mod ndijkstra;
+mod pareto;
+mod phast;
+mod pool;
 mod state;
+mod stats;
+mod tie_breaking;
 
+pub use alternatives::{k_shortest_paths, penalty_alternatives, AlternativeOptions};
+pub use exact::exact_shortest_costs;
+pub use landmarks::Landmarks;
 pub use ndijkstra::NDijkstra;
+pub use pareto::{linearly_optimal_paths, pareto_optimal_paths};
+pub use phast::Phast;
+pub use pool::{PooledDijkstra, QueryPool};
+pub use stats::{AggregateQueryStats, QueryStats};
+
+use stats::AtomicQueryStats;
+pub use tie_breaking::TieBreaking;
+
+use tie_breaking::{compare_costs, unpacked_edges};
 
 use lazy_static::lazy_static;
 use metered::metered;
@@ -31,6 +51,7 @@ pub struct DijkstraResult {
     pub edges: MyVec<u32>,
     pub costs: MyVec<f64>,
     pub total_cost: f64,
+    pub stats: QueryStats,
 }
 
 #[derive(Clone)]
@@ -51,6 +72,16 @@ pub struct Dijkstra<'a> {
 
     // (node_id, cost array, total_cost)
     best_node: Option<(u32, f64)>,
+
+    stall_on_demand: bool,
+    stats: QueryStats,
+
+    // Cost vectors to/from node and of the best path, only used for
+    // `TieBreaking::CostVector`
+    tie_breaking: TieBreaking,
+    dim_costs_f: Vec<f64>,
+    dim_costs_b: Vec<f64>,
+    best_costs: Vec<f64>,
 }
 
 #[metered(registry = DijkstraMetrics, registry_expr = DIJKSTRA_METRICS )]
@@ -68,9 +99,38 @@ impl<'a> Dijkstra<'a> {
             previous_f: MyVec(vec![None; num_of_nodes]),
             previous_b: MyVec(vec![None; num_of_nodes]),
             best_node: None,
+            stall_on_demand: true,
+            stats: QueryStats::default(),
+            tie_breaking: TieBreaking::Unspecified,
+            dim_costs_f: Vec::new(),
+            dim_costs_b: Vec::new(),
+            best_costs: Vec::new(),
+        }
+    }
+
+    pub fn set_tie_breaking(&mut self, tie_breaking: TieBreaking) {
+        self.tie_breaking = tie_breaking;
+        if tie_breaking == TieBreaking::CostVector && self.dim_costs_f.is_empty() {
+            let size = self.graph.nodes.len() * self.graph.dim as usize;
+            self.dim_costs_f = vec![0.0; size];
+            self.dim_costs_b = vec![0.0; size];
         }
     }
 
+    pub fn tie_breaking(&self) -> TieBreaking {
+        self.tie_breaking
+    }
+
+    /// Enables or disables stall-on-demand (enabled by default).
+    pub fn set_stall_on_demand(&mut self, enabled: bool) {
+        self.stall_on_demand = enabled;
+    }
+
+    /// Statistics of the last query.
+    pub fn stats(&self) -> QueryStats {
+        self.stats
+    }
+
     fn prepare(&mut self, source: u32, target: u32) {
         // Candidates
         self.candidates = BinaryHeap::new();
@@ -97,6 +157,16 @@ impl<'a> Dijkstra<'a> {
 
         // Best node
         self.best_node = None;
+
+        self.stats = QueryStats::default();
+        self.stats.pushes = 2;
+
+        if self.tie_breaking == TieBreaking::CostVector {
+            let dim = self.graph.dim as usize;
+            node_costs_mut(&mut self.dim_costs_f, source, dim).fill(0.0);
+            node_costs_mut(&mut self.dim_costs_b, target, dim).fill(0.0);
+            self.best_costs.clear();
+        }
     }
 
     #[measure(YesNoTime)]
@@ -112,6 +182,7 @@ impl<'a> Dijkstra<'a> {
             }
             self.process_state(candidate, alpha);
         }
+        QUERY_STATS.add_query(self.stats);
 
         match self.best_node {
             None => None,
@@ -133,6 +204,7 @@ impl<'a> Dijkstra<'a> {
                     edges,
                     costs,
                     total_cost,
+                    stats: self.stats,
                 })
             }
         }
@@ -149,17 +221,29 @@ impl<'a> Dijkstra<'a> {
         let other_costs;
         let found_best;
         let previous;
+        let other_previous;
+        let my_dim_costs;
+        let other_dim_costs;
         if direction == EdgeDirection::Out {
             my_costs = &mut self.cost_f;
             other_costs = &self.cost_b;
             found_best = &mut self.found_best_f;
             previous = &mut self.previous_f;
+            other_previous = &self.previous_b;
+            my_dim_costs = &mut self.dim_costs_f;
+            other_dim_costs = &self.dim_costs_b;
         } else {
             my_costs = &mut self.cost_b;
             other_costs = &self.cost_f;
             found_best = &mut self.found_best_b;
             previous = &mut self.previous_b;
+            other_previous = &self.previous_f;
+            my_dim_costs = &mut self.dim_costs_b;
+            other_dim_costs = &self.dim_costs_f;
         };
+        let graph = self.graph;
+        let dim = graph.dim as usize;
+        let tie_breaking = self.tie_breaking == TieBreaking::CostVector;
 
         if total_cost > my_costs[node_id] {
             return;
@@ -170,24 +254,104 @@ impl<'a> Dijkstra<'a> {
             *found_best = true;
             return;
         }
+        self.stats.settled += 1;
         if other_costs[node_id] != std::f64::MAX {
-            let merged_cost = total_cost + other_costs[node_id];
-            if merged_cost < best_node_cost {
-                // let merged_cost_vector = add_edge_costs(costs, other_costs[node_id].0);
-                self.best_node = Some((node_id, merged_cost));
+            if tie_breaking {
+                let mut merged = node_costs(my_dim_costs, node_id, dim).to_vec();
+                add_edge_costs(&mut merged, node_costs(other_dim_costs, node_id, dim));
+                let merged_cost = costs_by_alpha(&merged, alpha);
+                let improves =
+                    match compare_costs(merged_cost, &merged, best_node_cost, &self.best_costs) {
+                        Ordering::Less => true,
+                        Ordering::Equal => {
+                            let (previous_f, previous_b) = if direction == EdgeDirection::Out {
+                                (&*previous, other_previous)
+                            } else {
+                                (other_previous, &*previous)
+                            };
+                            let path = |connector| {
+                                unpacked_edges(
+                                    graph,
+                                    path_edges(graph, previous_f, previous_b, connector),
+                                )
+                            };
+                            let best_node = self.best_node.expect("equal costs of the best path");
+                            path(node_id) < path(best_node.0)
+                        }
+                        Ordering::Greater => false,
+                    };
+                if improves {
+                    self.best_node = Some((node_id, merged_cost));
+                    self.best_costs = merged;
+                }
+            } else {
+                let merged_cost = total_cost + other_costs[node_id];
+                if merged_cost < best_node_cost {
+                    // let merged_cost_vector = add_edge_costs(costs, other_costs[node_id].0);
+                    self.best_node = Some((node_id, merged_cost));
+                }
             }
         }
 
+        let dim_costs = if tie_breaking {
+            Some(&**my_dim_costs)
+        } else {
+            None
+        };
+        if self.stall_on_demand
+            && is_stalled(
+                self.graph, my_costs, dim_costs, node_id, total_cost, direction, alpha,
+            )
+        {
+            self.stats.stalled += 1;
+            return;
+        }
+        let mut next_dim_costs = vec![0.0; if tie_breaking { dim } else { 0 }];
+
         let edges = self.graph.edges_of(node_id, direction);
         for half_edge in edges {
             if self.graph.nodes[node_id].ch_level > self.graph.nodes[half_edge.target_id].ch_level {
                 break;
             }
+            self.stats.relaxed += 1;
 
             let next_node = half_edge.target_id;
-            let next_total_cost = total_cost + costs_by_alpha(half_edge.edge_costs, alpha);
+            let next_total_cost;
+            let improves;
+            if tie_breaking {
+                // The cost is computed from the cost vector, so that equal
+                // vectors always lead to equal costs
+                next_dim_costs.copy_from_slice(node_costs(my_dim_costs, node_id, dim));
+                add_edge_costs(&mut next_dim_costs, half_edge.edge_costs);
+                next_total_cost = costs_by_alpha(&next_dim_costs, alpha);
+                improves = my_costs[next_node] == f64::MAX
+                    || match compare_costs(
+                        next_total_cost,
+                        &next_dim_costs,
+                        my_costs[next_node],
+                        node_costs(my_dim_costs, next_node, dim),
+                    ) {
+                        Ordering::Less => true,
+                        Ordering::Equal => {
+                            let half_path = |edge| {
+                                unpacked_edges(
+                                    graph,
+                                    half_path_edges(graph, previous, edge, direction),
+                                )
+                            };
+                            half_path(Some(half_edge.edge_id)) < half_path(previous[next_node])
+                        }
+                        Ordering::Greater => false,
+                    };
+                if improves {
+                    node_costs_mut(my_dim_costs, next_node, dim).copy_from_slice(&next_dim_costs);
+                }
+            } else {
+                next_total_cost = total_cost + costs_by_alpha(half_edge.edge_costs, alpha);
+                improves = next_total_cost < my_costs[next_node];
+            }
 
-            if next_total_cost < my_costs[next_node] {
+            if improves {
                 my_costs[next_node] = next_total_cost;
                 previous[next_node] = Some(half_edge.edge_id);
                 self.touched_nodes.push(next_node);
@@ -196,33 +360,110 @@ impl<'a> Dijkstra<'a> {
                     total_cost: next_total_cost,
                     direction,
                 });
+                self.stats.pushes += 1;
             }
         }
     }
 
     fn make_edge_path(&self, connector: u32) -> (MyVec<u32>, MyVec<f64>) {
-        let mut edges = MyVec(Vec::new());
-        let mut previous_edge = self.previous_f[connector];
-        let mut successive_edge = self.previous_b[connector];
-
+        let edges = path_edges(self.graph, &self.previous_f, &self.previous_b, connector);
         let mut costs: MyVec<_> = vec![0.0; self.graph.dim as usize].into();
-
-        // backwards
-        while let Some(edge_id) = previous_edge {
-            edges.push(edge_id);
+        for &edge_id in &edges {
             add_edge_costs(&mut costs, &self.graph.edges[edge_id].edge_costs);
-            previous_edge = self.previous_f[self.graph.edges[edge_id].source_id];
         }
+        (MyVec(edges), costs)
+    }
+}
+
+/// Edges of the path over `connector` in the order of the path.
+fn path_edges(
+    graph: &Graph,
+    previous_f: &MyVec<Option<u32>>,
+    previous_b: &MyVec<Option<u32>>,
+    connector: u32,
+) -> Vec<u32> {
+    let mut edges = half_path_edges(graph, previous_f, previous_f[connector], EdgeDirection::Out);
+    edges.extend(half_path_edges(
+        graph,
+        previous_b,
+        previous_b[connector],
+        EdgeDirection::In,
+    ));
+    edges
+}
+
+/// Edges from the start of the search in `direction` up to `edge`, in the
+/// order of the path.
+fn half_path_edges(
+    graph: &Graph,
+    previous: &MyVec<Option<u32>>,
+    edge: Option<u32>,
+    direction: EdgeDirection,
+) -> Vec<u32> {
+    let mut edges = Vec::new();
+    let mut edge = edge;
+    while let Some(edge_id) = edge {
+        edges.push(edge_id);
+        edge = match direction {
+            EdgeDirection::Out => previous[graph.edges[edge_id].source_id],
+            EdgeDirection::In => previous[graph.edges[edge_id].target_id],
+        };
+    }
+    if direction == EdgeDirection::Out {
         edges.reverse();
+    }
+    edges
+}
 
-        // forwards
-        while let Some(edge_id) = successive_edge {
-            edges.push(edge_id);
-            add_edge_costs(&mut costs, &self.graph.edges[edge_id].edge_costs);
-            successive_edge = self.previous_b[self.graph.edges[edge_id].target_id];
+fn node_costs(dim_costs: &[f64], node_id: u32, dim: usize) -> &[f64] {
+    let start = node_id as usize * dim;
+    &dim_costs[start..start + dim]
+}
+
+fn node_costs_mut(dim_costs: &mut [f64], node_id: u32, dim: usize) -> &mut [f64] {
+    let start = node_id as usize * dim;
+    &mut dim_costs[start..start + dim]
+}
+
+/// A node can be stalled if it is reached cheaper through an edge coming from a
+/// higher node, which the search does not follow. Its tentative cost is then
+/// not the real distance, so it can not be part of the shortest up-down path
+/// and does not need to be expanded. With `dim_costs` the costs are computed
+/// from the cost vectors, as the search does for tie breaking.
+fn is_stalled(
+    graph: &Graph,
+    costs: &MyVec<f64>,
+    dim_costs: Option<&[f64]>,
+    node_id: u32,
+    total_cost: f64,
+    direction: EdgeDirection,
+    alpha: &[f64],
+) -> bool {
+    let opposite = match direction {
+        EdgeDirection::Out => EdgeDirection::In,
+        EdgeDirection::In => EdgeDirection::Out,
+    };
+    for half_edge in graph.edges_of(node_id, opposite) {
+        if graph.nodes[half_edge.target_id].ch_level < graph.nodes[node_id].ch_level {
+            break;
+        }
+        let other_cost = costs[half_edge.target_id];
+        if other_cost == f64::MAX {
+            continue;
+        }
+        let cost = match dim_costs {
+            Some(dim_costs) => {
+                let mut via = node_costs(dim_costs, half_edge.target_id, alpha.len()).to_vec();
+                add_edge_costs(&mut via, half_edge.edge_costs);
+                costs_by_alpha(&via, alpha)
+            }
+            None => other_cost + costs_by_alpha(half_edge.edge_costs, alpha),
+        };
+        if cost < total_cost {
+            return true;
         }
-        (edges, costs)
     }
+    false
 }
 
 pub fn find_path(dijkstra: &mut Dijkstra, include: &[u32], alpha: &[f64]) -> Option<HalfPath> {
@@ -330,23 +571,139 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_tie_breaking_by_cost_vector() {
+        use crate::utils::{equal_weights, same_array};
+
+        let conc_graph = get_conc_graph();
+        let dim = conc_graph.dim as usize;
+        let mut dijkstra = NDijkstra::new(&conc_graph);
+        let mut dijkstra_conc = Dijkstra::new(&conc_graph);
+        dijkstra.set_tie_breaking(TieBreaking::CostVector);
+        dijkstra_conc.set_tie_breaking(TieBreaking::CostVector);
+
+        // Preferences ignoring some metrics produce many ties
+        let mut alphas = vec![equal_weights(dim)];
+        for i in 0..dim {
+            let mut alpha = vec![0.0; dim];
+            alpha[i] = 1.0;
+            alphas.push(alpha.into());
+        }
+
+        for alpha in &alphas {
+            for s in 0..(conc_graph.nodes.len() as u32) {
+                for t in 0..(conc_graph.nodes.len() as u32) {
+                    let ch_path = dijkstra_conc.run(s, t, alpha);
+                    dijkstra.run(s, t, alpha);
+                    let n_path = dijkstra.path(t);
+                    assert_eq!(ch_path.is_some(), n_path.is_some());
+                    if let (Some(ch_path), Some(n_path)) = (ch_path, n_path) {
+                        assert!(same_array(&ch_path.costs, &n_path.total_dimension_costs));
+                        let ch_edges = unpacked_edges(&conc_graph, ch_path.edges.0);
+                        assert_eq!(ch_edges, n_path.edges.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_breaking_by_edge_ids() {
+        use crate::graph::parse_minimal_graph;
+
+        // Routes 0 -> 1 -> 3 and 0 -> 2 -> 3 with identical cost vectors
+        for (edges, expected) in [
+            (
+                "0 0 1 1 1 -1 -1\n1 1 3 1 1 -1 -1\n2 0 2 1 1 -1 -1\n3 2 3 1 1 -1 -1\n",
+                [0, 1],
+            ),
+            (
+                "0 0 2 1 1 -1 -1\n1 2 3 1 1 -1 -1\n2 0 1 1 1 -1 -1\n3 1 3 1 1 -1 -1\n",
+                [0, 1],
+            ),
+            (
+                "0 1 3 1 1 -1 -1\n1 0 2 1 1 -1 -1\n2 2 3 1 1 -1 -1\n3 0 1 1 1 -1 -1\n",
+                [1, 2],
+            ),
+        ] {
+            let graph = parse_minimal_graph(
+                format!("2\na b\n4\n4\n0 0\n1 0\n2 0\n3 0\n{}", edges).as_bytes(),
+            )
+            .unwrap();
+            let mut dijkstra = NDijkstra::new(&graph);
+            let mut dijkstra_conc = Dijkstra::new(&graph);
+            dijkstra.set_tie_breaking(TieBreaking::CostVector);
+            dijkstra_conc.set_tie_breaking(TieBreaking::CostVector);
+
+            let ch_path = dijkstra_conc.run(0, 3, &[0.5, 0.5]).unwrap();
+            assert_eq!(ch_path.edges.0, expected);
+            dijkstra.run(0, 3, &vec![0.5, 0.5].into());
+            assert_eq!(dijkstra.path(3).unwrap().edges.0, expected);
+        }
+    }
+
+    #[test]
+    fn test_stall_on_demand() {
+        use crate::float_eq;
+
+        let conc_graph = get_conc_graph();
+        let mut stalling = Dijkstra::new(&conc_graph);
+        let mut plain = Dijkstra::new(&conc_graph);
+        plain.set_stall_on_demand(false);
+
+        let mut stalled = 0;
+        let mut rng = rand::thread_rng();
+        for s in 0..(conc_graph.nodes.len() as u32) {
+            for t in 0..(conc_graph.nodes.len() as u32) {
+                let alpha = randomized_preference(conc_graph.dim.try_into().unwrap(), &mut rng);
+                let result = stalling.run(s, t, &alpha);
+                let plain_result = plain.run(s, t, &alpha);
+                assert!(float_eq!(
+                    result.as_ref().map(|r| r.total_cost).unwrap_or(-1.),
+                    plain_result.as_ref().map(|r| r.total_cost).unwrap_or(-1.)
+                ));
+
+                let stats = stalling.stats();
+                if let Some(result) = result {
+                    assert_eq!(result.stats, stats);
+                }
+                assert!(stats.settled >= 1);
+                assert!(stats.stalled <= stats.settled);
+                assert_eq!(plain.stats().stalled, 0);
+                stalled += stats.stalled;
+            }
+        }
+        assert!(stalled > 0);
+    }
 }
 
 lazy_static! {
     static ref DIJKSTRA_METRICS: DijkstraMetrics = Default::default();
+    static ref QUERY_STATS: AtomicQueryStats = Default::default();
 }
 
 pub struct TimeReports;
 
 impl TimeReports {
-    pub fn dijkstra() {
-        println!("Dijkstra report:",);
+    pub fn dijkstra(graph: &Graph) {
+        println!(
+            "Dijkstra report (metrics: {}):",
+            graph.metric_names.join(", ")
+        );
         println!("{}", DIJKSTRA_METRICS.run.yes_no_time);
+        println!("{}", QUERY_STATS.load());
         println!("----------");
     }
 
+    /// Statistics summed over all queries since the last clear.
+    pub fn query_stats() -> AggregateQueryStats {
+        QUERY_STATS.load()
+    }
+
     pub fn clear_dijkstra_time() {
         use metered::clear::Clear;
         DIJKSTRA_METRICS.run.yes_no_time.clear();
+        QUERY_STATS.clear();
     }
 }
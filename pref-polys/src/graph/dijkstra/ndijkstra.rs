@@ -7,8 +7,14 @@ use crate::{
     utils::{equal_weights, same_array, MyVec, Preference},
 };
 
+use super::{
+    node_costs, node_costs_mut,
+    tie_breaking::{compare_costs, unpacked_edges},
+    Landmarks, TieBreaking,
+};
+
 use ordered_float::OrderedFloat;
-use std::{collections::BinaryHeap, convert::TryInto};
+use std::{cmp::Ordering, collections::BinaryHeap, convert::TryInto};
 
 pub struct NDijkstra<'a> {
     g: &'a Graph,
@@ -17,13 +23,22 @@ pub struct NDijkstra<'a> {
     heap: BinaryHeap<HeapElement>,
     touched: Vec<u32>,
     last_from: u32,
+    last_to: u32,
     last_pref: Preference,
+    tie_breaking: TieBreaking,
+    // Edge to and cost vector of the best path found so far to nodes which
+    // are not settled yet, only used for `TieBreaking::CostVector`
+    pending: MyVec<Option<u32>>,
+    dim_costs: Vec<f64>,
+    next_costs: Vec<f64>,
+    landmarks: Option<&'a Landmarks>,
 }
 
 impl<'a> NDijkstra<'a> {
     pub fn new(g: &'a Graph) -> Self {
         let dist = vec![f64::MAX; g.nodes.len()].into();
         let prev = vec![None; g.nodes.len()].into();
+        let pending = vec![None; g.nodes.len()].into();
         let heap = BinaryHeap::new();
         let touched = Vec::new();
         let dim = g.dim.try_into().unwrap();
@@ -34,7 +49,34 @@ impl<'a> NDijkstra<'a> {
             heap,
             touched,
             last_from: u32::MAX,
+            last_to: u32::MAX,
             last_pref: equal_weights(dim),
+            tie_breaking: TieBreaking::Unspecified,
+            pending,
+            dim_costs: Vec::new(),
+            next_costs: Vec::new(),
+            landmarks: None,
+        }
+    }
+
+    /// Turns the search into an A* search guided by the landmarks.
+    pub fn set_landmarks(&mut self, landmarks: &'a Landmarks) {
+        assert!(
+            landmarks.fits(self.g),
+            "Landmarks were computed for a different graph"
+        );
+        self.landmarks = Some(landmarks);
+        self.last_from = u32::MAX;
+    }
+
+    pub fn set_tie_breaking(&mut self, tie_breaking: TieBreaking) {
+        if tie_breaking != self.tie_breaking {
+            // Cached results might follow the other rule
+            self.last_from = u32::MAX;
+        }
+        self.tie_breaking = tie_breaking;
+        if tie_breaking == TieBreaking::CostVector && self.dim_costs.is_empty() {
+            self.dim_costs = vec![0.0; self.g.nodes.len() * self.g.dim as usize];
         }
     }
 
@@ -42,6 +84,7 @@ impl<'a> NDijkstra<'a> {
         for &t in &self.touched {
             self.dist[t] = f64::MAX;
             self.prev[t] = None;
+            self.pending[t] = None;
         }
         self.heap.clear();
         self.touched.clear();
@@ -49,27 +92,49 @@ impl<'a> NDijkstra<'a> {
 
     pub fn run(&mut self, from: u32, to: u32, alpha: &Preference) -> Option<f64> {
         // If the query starts from the same node as before we can reuse it
-        if self.last_from == from && same_array(&self.last_pref, alpha) {
-            if self.dist[to] < f64::MAX {
-                return Some(self.dist[to]);
-            }
-        } else {
+        let reuse = self.last_from == from && same_array(&self.last_pref, alpha);
+        if reuse && self.dist[to] < f64::MAX {
+            return Some(self.dist[to]);
+        }
+        // The A* potential depends on the target, so the heap can only be
+        // reused for the same target
+        if !reuse || (self.landmarks.is_some() && self.last_to != to) {
             // If not we initialize it normally
             self.last_from = from;
             self.last_pref = alpha.clone();
             self.reset_state();
 
-            self.heap.push(HeapElement {
-                dist: 0.0,
-                node: from,
-                prev_edge: from,
-            });
+            let key = self.potential(from, to, alpha);
+            if key < f64::INFINITY {
+                let costs = match self.tie_breaking {
+                    TieBreaking::Unspecified => Vec::new(),
+                    TieBreaking::CostVector => {
+                        let dim = self.g.dim as usize;
+                        node_costs_mut(&mut self.dim_costs, from, dim).fill(0.0);
+                        self.pending[from] = Some(from);
+                        self.touched.push(from);
+                        vec![0.0; dim]
+                    }
+                };
+                self.heap.push(HeapElement {
+                    key,
+                    dist: 0.0,
+                    costs,
+                    node: from,
+                    prev_edge: from,
+                });
+            }
         }
+        self.last_to = to;
+        let g = self.g;
+        let dim = g.dim as usize;
+        let tie_breaking = self.tie_breaking == TieBreaking::CostVector;
 
         while let Some(HeapElement {
             dist: u_dist,
             node: u,
             prev_edge,
+            ..
         }) = self.heap.pop()
         {
             // If your heap does not support a decrease key operation, you can
@@ -79,20 +144,71 @@ impl<'a> NDijkstra<'a> {
             if u_dist >= self.dist[u] {
                 continue;
             }
+            // With tie breaking a path with equal cost might have replaced it
+            if tie_breaking && self.pending[u] != Some(prev_edge) {
+                continue;
+            }
 
             self.dist[u] = u_dist;
             self.prev[u] = Some(prev_edge);
             self.touched.push(u);
 
-            for edge in self.g.edges_of(u, EdgeDirection::Out) {
-                let alt = u_dist + costs_by_alpha(edge.edge_costs, alpha);
-                if alt < self.dist[edge.target_id] {
-                    self.heap.push(HeapElement {
-                        dist: alt,
-                        node: edge.target_id,
-                        prev_edge: edge.edge_id,
-                    });
+            for edge in g.edges_of(u, EdgeDirection::Out) {
+                let v = edge.target_id;
+                let alt = if tie_breaking {
+                    // Same as in the CH Dijkstra, the cost is computed from
+                    // the cost vector for tie breaking
+                    self.next_costs.clear();
+                    self.next_costs
+                        .extend_from_slice(node_costs(&self.dim_costs, u, dim));
+                    add_edge_costs(&mut self.next_costs, edge.edge_costs);
+                    costs_by_alpha(&self.next_costs, alpha)
+                } else {
+                    u_dist + costs_by_alpha(edge.edge_costs, alpha)
+                };
+                if alt >= self.dist[v] {
+                    continue;
                 }
+                let key = alt + self.potential(v, to, alpha);
+                if key == f64::INFINITY {
+                    continue;
+                }
+                let costs = if tie_breaking {
+                    let improves = match self.pending[v] {
+                        None => true,
+                        Some(pending) => {
+                            let v_costs = node_costs(&self.dim_costs, v, dim);
+                            match compare_costs(
+                                alt,
+                                &self.next_costs,
+                                costs_by_alpha(v_costs, alpha),
+                                v_costs,
+                            ) {
+                                Ordering::Less => true,
+                                Ordering::Equal => {
+                                    self.unpacked_path(edge.edge_id) < self.unpacked_path(pending)
+                                }
+                                Ordering::Greater => false,
+                            }
+                        }
+                    };
+                    if !improves {
+                        continue;
+                    }
+                    node_costs_mut(&mut self.dim_costs, v, dim).copy_from_slice(&self.next_costs);
+                    self.pending[v] = Some(edge.edge_id);
+                    self.touched.push(v);
+                    self.next_costs.clone()
+                } else {
+                    Vec::new()
+                };
+                self.heap.push(HeapElement {
+                    key,
+                    dist: alt,
+                    costs,
+                    node: v,
+                    prev_edge: edge.edge_id,
+                });
             }
             // We moved this down here to have the heap in a consistent state
             // (all outgoing neighbors of `to` are in the heap)
@@ -104,37 +220,54 @@ impl<'a> NDijkstra<'a> {
         None
     }
 
-    pub fn path(&mut self, to: u32) -> Option<Path> {
-        if self.prev[to] == None {
-            let alpha = self.last_pref.clone();
-            self.run(self.last_from, to, &alpha);
+    fn potential(&self, node: u32, to: u32, alpha: &[f64]) -> f64 {
+        match self.landmarks {
+            Some(landmarks) => landmarks.potential(node, to, alpha),
+            None => 0.0,
         }
-        // early return if `to` is unreachable
-        self.prev[to]?;
+    }
 
-        let mut edges = MyVec::new();
-        let mut nodes = MyVec::new();
-        let mut total_dimension_costs = MyVec(vec![0.0; self.g.dim as usize]);
+    /// Edges from the start of the last query to the settled node `to`.
+    fn edges_to(&self, to: u32) -> Vec<u32> {
+        let mut edges = Vec::new();
         let mut cur_node = to;
-
         while cur_node != self.last_from {
             let edge = self.prev[cur_node].expect("Previous Edge must exist");
             edges.push(edge);
-            nodes.push(cur_node);
             cur_node = self.g.edges[edge].source_id;
-            add_edge_costs(&mut total_dimension_costs, &self.g.edges[edge].edge_costs);
         }
+        edges.reverse();
+        edges
+    }
 
-        nodes.push(cur_node);
+    /// Original edges of the path over the settled source of `edge` and then
+    /// `edge`.
+    fn unpacked_path(&self, edge: u32) -> Vec<u32> {
+        let mut edges = self.edges_to(self.g.edges[edge].source_id);
+        edges.push(edge);
+        unpacked_edges(self.g, edges)
+    }
 
-        edges.reverse();
-        nodes.reverse();
-        let edges = edges
-            .0
-            .into_iter()
-            .flat_map(|e| self.g.unpack_edge(e))
+    pub fn path(&mut self, to: u32) -> Option<Path> {
+        if self.prev[to] == None {
+            let alpha = self.last_pref.clone();
+            self.run(self.last_from, to, &alpha);
+        }
+        // early return if `to` is unreachable
+        self.prev[to]?;
+
+        let edges = self.edges_to(to);
+        let mut nodes: MyVec<u32> = edges
+            .iter()
+            .map(|&edge| self.g.edges[edge].source_id)
             .collect::<Vec<_>>()
             .into();
+        nodes.push(to);
+        let mut total_dimension_costs = MyVec(vec![0.0; self.g.dim as usize]);
+        for &edge in &edges {
+            add_edge_costs(&mut total_dimension_costs, &self.g.edges[edge].edge_costs);
+        }
+        let edges = unpacked_edges(self.g, edges).into();
 
         Some(Path {
             nodes,
@@ -146,7 +279,11 @@ impl<'a> NDijkstra<'a> {
 
 #[derive(Debug, PartialEq)]
 struct HeapElement {
+    /// Distance plus the A* potential
+    key: f64,
     dist: f64,
+    /// Cost vector, empty without tie breaking
+    costs: Vec<f64>,
     node: u32,
     prev_edge: u32,
 }
@@ -157,7 +294,15 @@ impl Eq for HeapElement {}
 // custom ordering which reverses the sorting.
 impl Ord for HeapElement {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        OrderedFloat(other.dist).cmp(&OrderedFloat(self.dist))
+        OrderedFloat(other.key)
+            .cmp(&OrderedFloat(self.key))
+            .then_with(|| {
+                other
+                    .costs
+                    .iter()
+                    .map(|&c| OrderedFloat(c))
+                    .cmp(self.costs.iter().map(|&c| OrderedFloat(c)))
+            })
     }
 }
 
@@ -0,0 +1,319 @@
+//! Multi-criteria label-setting search for all Pareto-optimal paths between
+//! two nodes.
+//!
+//! Labels are settled in order of the sum of their costs (ties broken
+//! lexicographically), so a settled label can never be dominated by one that
+//! is settled later. Only original edges are followed, shortcuts are ignored.
+//! The number of Pareto-optimal paths can grow exponentially, so this is
+//! meant for small graphs or as ground truth in tests.
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use anyhow::Result;
+use ordered_float::OrderedFloat;
+
+use crate::{
+    graph::{
+        edge::EdgeDirection,
+        path::{add_edge_costs, Path},
+        Graph,
+    },
+    lp::LpBackend,
+    preference::PrefFinder,
+    utils::{Costs, MyVec, Preference},
+};
+
+struct Label {
+    node: u32,
+    costs: Costs,
+    /// Label and edge this label was created from
+    prev: Option<(usize, u32)>,
+}
+
+/// `a` is at least as good as `b` in every metric
+fn weakly_dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a <= b)
+}
+
+/// `a` is at least as good as `b` in every metric and better in one
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    weakly_dominates(a, b) && a != b
+}
+
+/// Whether the path of `label` passes through `node`
+fn visits(labels: &[Label], mut label: usize, node: u32) -> bool {
+    loop {
+        if labels[label].node == node {
+            return true;
+        }
+        match labels[label].prev {
+            Some((prev, _)) => label = prev,
+            None => return false,
+        }
+    }
+}
+
+struct HeapElement {
+    sum: f64,
+    costs: Costs,
+    label: usize,
+}
+
+impl PartialEq for HeapElement {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapElement {}
+
+// Reversed, as BinaryHeap is a max-heap
+impl Ord for HeapElement {
+    fn cmp(&self, other: &Self) -> Ordering {
+        OrderedFloat(other.sum)
+            .cmp(&OrderedFloat(self.sum))
+            .then_with(|| {
+                other
+                    .costs
+                    .iter()
+                    .map(|&c| OrderedFloat(c))
+                    .cmp(self.costs.iter().map(|&c| OrderedFloat(c)))
+            })
+            .then_with(|| other.label.cmp(&self.label))
+    }
+}
+
+impl PartialOrd for HeapElement {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Finds all Pareto-optimal paths from `source` to `target`. Distinct paths
+/// with identical cost vectors are all reported, but no path visits a node
+/// twice. The result is sorted by the sum of the costs. Edge costs must not
+/// be negative.
+pub fn pareto_optimal_paths(graph: &Graph, source: u32, target: u32) -> Vec<Path> {
+    let mut labels = vec![Label {
+        node: source,
+        costs: vec![0.0; graph.dim as usize].into(),
+        prev: None,
+    }];
+    let mut settled: MyVec<Vec<usize>> = MyVec(vec![Vec::new(); graph.nodes.len()]);
+    let mut heap = BinaryHeap::new();
+    heap.push(HeapElement {
+        sum: 0.0,
+        costs: labels[0].costs.clone(),
+        label: 0,
+    });
+
+    let is_dominated = |labels: &[Label], settled: &MyVec<Vec<usize>>, node: u32, costs: &[f64]| {
+        settled[node]
+            .iter()
+            .chain(&settled[target])
+            .any(|&l| dominates(&labels[l].costs, costs))
+    };
+
+    while let Some(HeapElement { label, .. }) = heap.pop() {
+        let node = labels[label].node;
+        if is_dominated(&labels, &settled, node, &labels[label].costs) {
+            continue;
+        }
+        settled[node].push(label);
+        // Any extension of a path to the target is dominated by the path
+        if node == target {
+            continue;
+        }
+
+        for edge in graph.edges_of(node, EdgeDirection::Out) {
+            if graph.edges[edge.edge_id].replaced_edges.is_some() {
+                continue;
+            }
+            let mut costs = labels[label].costs.clone();
+            add_edge_costs(&mut costs, edge.edge_costs);
+            if is_dominated(&labels, &settled, edge.target_id, &costs) {
+                continue;
+            }
+            // An equally expensive detour over a cycle of zero costs
+            let ties = settled[edge.target_id]
+                .iter()
+                .any(|&l| labels[l].costs.0 == costs.0);
+            if ties && visits(&labels, label, edge.target_id) {
+                continue;
+            }
+            heap.push(HeapElement {
+                sum: costs.iter().sum(),
+                costs: costs.clone(),
+                label: labels.len(),
+            });
+            labels.push(Label {
+                node: edge.target_id,
+                costs,
+                prev: Some((label, edge.edge_id)),
+            });
+        }
+    }
+
+    settled[target]
+        .iter()
+        .map(|&l| {
+            let mut edges = Vec::new();
+            let mut current = l;
+            while let Some((prev, edge)) = labels[current].prev {
+                edges.push(edge);
+                current = prev;
+            }
+            edges.reverse();
+            let mut nodes: Vec<u32> = edges.iter().map(|&e| graph.edges[e].source_id).collect();
+            nodes.push(target);
+            Path {
+                nodes: MyVec(nodes),
+                edges: MyVec(edges),
+                total_dimension_costs: labels[l].costs.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Keeps the paths which are optimal among `paths` for some preference, i.e.
+/// whose costs lie on the lower convex hull of all costs. Every kept path is
+/// returned together with such a preference.
+pub fn linearly_optimal_paths(
+    lp: &mut impl LpBackend,
+    paths: &[Path],
+) -> Result<Vec<(Path, Preference)>> {
+    let dim = match paths.first() {
+        Some(p) => p.total_dimension_costs.len(),
+        None => return Ok(Vec::new()),
+    };
+    let mut finder = PrefFinder::new(lp, dim);
+
+    let mut result = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let constraints: Vec<Costs> = paths
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| {
+                other
+                    .total_dimension_costs
+                    .iter()
+                    .zip(path.total_dimension_costs.iter())
+                    .map(|(o, p)| o - p)
+                    .collect::<Vec<_>>()
+                    .into()
+            })
+            .collect();
+        if let Some(pref) = finder.constrained_multi_path_preference_lp_only(constraints.iter())? {
+            result.push((path.clone(), pref));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::float_eq;
+    use crate::graph::{
+        dijkstra::NDijkstra, parse_graph_file, parse_minimal_graph, path::costs_by_alpha,
+    };
+    use crate::lp::PreferenceLp;
+    use crate::utils::randomized_preference;
+
+    #[test]
+    fn test_pareto_paths_contain_all_optimal_paths() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut dijkstra = NDijkstra::new(&graph);
+        let mut rng = StdRng::seed_from_u64(7);
+        let n = graph.nodes.len() as u32;
+
+        for s in 0..n {
+            for t in 0..n {
+                let paths = pareto_optimal_paths(&graph, s, t);
+                for (i, p) in paths.iter().enumerate() {
+                    assert_eq!(*p.nodes.first().unwrap(), s);
+                    assert_eq!(*p.nodes.last().unwrap(), t);
+                    let mut costs = vec![0.0; graph.dim as usize];
+                    for &e in p.edges.iter() {
+                        add_edge_costs(&mut costs, &graph.edges[e].edge_costs);
+                    }
+                    assert!(crate::utils::same_array(&costs, &p.total_dimension_costs));
+                    for q in &paths[i + 1..] {
+                        assert!(!dominates(
+                            &q.total_dimension_costs,
+                            &p.total_dimension_costs
+                        ));
+                        assert!(!dominates(
+                            &p.total_dimension_costs,
+                            &q.total_dimension_costs
+                        ));
+                        assert_ne!(p.edges.0, q.edges.0);
+                    }
+                }
+
+                for _ in 0..5 {
+                    let alpha = randomized_preference(&mut rng, graph.dim as usize);
+                    let best = paths
+                        .iter()
+                        .map(|p| costs_by_alpha(&p.total_dimension_costs, &alpha))
+                        .fold(None, |acc: Option<f64>, c| {
+                            Some(acc.map_or(c, |a| a.min(c)))
+                        });
+                    let cost = dijkstra.run(s, t, &alpha);
+                    assert_eq!(best.is_some(), cost.is_some());
+                    if let (Some(best), Some(cost)) = (best, cost) {
+                        assert!(float_eq!(best, cost));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_paths_with_equal_costs_are_kept() {
+        // two routes from 0 to 3 with costs (1, 1), and a cycle of zero costs
+        // between 1 and 4
+        let graph = parse_minimal_graph(
+            "2\na b\n5\n6\n0 0\n1 0\n2 0\n3 0\n4 0\n\
+             0 0 1 1 0 -1 -1\n1 1 3 0 1 -1 -1\n2 0 2 0 1 -1 -1\n\
+             3 2 3 1 0 -1 -1\n4 1 4 0 0 -1 -1\n5 4 1 0 0 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let paths = pareto_optimal_paths(&graph, 0, 3);
+        let mut edges: Vec<_> = paths.iter().map(|p| p.edges.0.clone()).collect();
+        edges.sort();
+        assert_eq!(edges, vec![vec![0, 1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_linearly_optimal_subset() {
+        let path = |costs: Vec<f64>| Path {
+            nodes: MyVec(vec![0, 1]),
+            edges: MyVec(vec![0]),
+            total_dimension_costs: costs.into(),
+        };
+        let paths = vec![
+            path(vec![0.0, 4.0]),
+            path(vec![1.5, 1.5]),
+            path(vec![3.0, 1.2]),
+            path(vec![4.0, 0.0]),
+        ];
+        let mut lp = PreferenceLp::new(2).unwrap();
+        let optimal = linearly_optimal_paths(&mut lp, &paths).unwrap();
+        let costs: Vec<_> = optimal
+            .iter()
+            .map(|(p, _)| p.total_dimension_costs.0.clone())
+            .collect();
+        assert_eq!(costs, vec![vec![0.0, 4.0], vec![1.5, 1.5], vec![4.0, 0.0]]);
+        for (p, pref) in &optimal {
+            let own = costs_by_alpha(&p.total_dimension_costs, pref);
+            for q in &paths {
+                assert!(own <= costs_by_alpha(&q.total_dimension_costs, pref) + crate::ACCURACY);
+            }
+        }
+    }
+}
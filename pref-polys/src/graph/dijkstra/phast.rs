@@ -0,0 +1,306 @@
+//! One-to-many and one-to-all queries on the contraction hierarchy.
+//!
+//! Both run an upward search from the source and then sweep over the nodes in
+//! descending order of their ch level, pulling distances down along the
+//! incoming edges from higher levels (PHAST). The one-to-many query restricts
+//! the sweep to the nodes from which one of the targets can be reached
+//! downwards (RPHAST). Edges between nodes of the same level, which occur in
+//! graphs that are not fully contracted, are handled by a small Dijkstra
+//! within each level.
+
+use std::collections::BinaryHeap;
+
+use super::state::State;
+use crate::graph::{
+    edge::EdgeDirection,
+    path::{add_edge_costs, costs_by_alpha, Path},
+    Graph,
+};
+use crate::utils::MyVec;
+
+pub struct Phast<'a> {
+    pub graph: &'a Graph,
+    dist: MyVec<f64>,
+    previous: MyVec<Option<u32>>,
+    touched: Vec<u32>,
+    candidates: BinaryHeap<State>,
+    source: Option<u32>,
+}
+
+impl<'a> Phast<'a> {
+    pub fn new(graph: &'a Graph) -> Self {
+        let num_of_nodes = graph.nodes.len();
+        Phast {
+            graph,
+            dist: MyVec(vec![f64::MAX; num_of_nodes]),
+            previous: MyVec(vec![None; num_of_nodes]),
+            touched: Vec::new(),
+            candidates: BinaryHeap::new(),
+            source: None,
+        }
+    }
+
+    fn prepare(&mut self, source: u32) {
+        for &node in &self.touched {
+            self.dist[node] = f64::MAX;
+            self.previous[node] = None;
+        }
+        self.touched.clear();
+        self.candidates.clear();
+
+        self.dist[source] = 0.0;
+        self.touched.push(source);
+        self.source = Some(source);
+    }
+
+    /// Computes the distances from `source` to all nodes.
+    pub fn run(&mut self, source: u32, alpha: &[f64]) {
+        self.prepare(source);
+        self.upward_search(source, alpha);
+        let order: Vec<u32> = (0..self.graph.nodes.len() as u32).rev().collect();
+        self.sweep(&order, alpha);
+    }
+
+    /// Computes the distances from `source` to all `targets`. Distances to
+    /// other nodes are not necessarily correct afterwards.
+    pub fn run_one_to_many(
+        &mut self,
+        source: u32,
+        targets: &[u32],
+        alpha: &[f64],
+    ) -> Vec<Option<f64>> {
+        self.prepare(source);
+        self.upward_search(source, alpha);
+        let order = self.sweep_nodes(targets);
+        self.sweep(&order, alpha);
+        targets.iter().map(|&t| self.distance(t)).collect()
+    }
+
+    /// Distance to `target` in the last run, `None` if it is unreachable.
+    pub fn distance(&self, target: u32) -> Option<f64> {
+        if self.dist[target] < f64::MAX {
+            Some(self.dist[target])
+        } else {
+            None
+        }
+    }
+
+    /// Unpacked path to `target` in the last run.
+    pub fn path(&self, target: u32) -> Option<Path> {
+        let source = self.source?;
+        self.distance(target)?;
+
+        let mut edges = Vec::new();
+        let mut total_dimension_costs = MyVec(vec![0.0; self.graph.dim as usize]);
+        let mut current = target;
+        while current != source {
+            let edge = self.previous[current].expect("Previous edge must exist");
+            edges.push(edge);
+            add_edge_costs(
+                &mut total_dimension_costs,
+                &self.graph.edges[edge].edge_costs,
+            );
+            current = self.graph.edges[edge].source_id;
+        }
+        edges.reverse();
+
+        let edges: Vec<u32> = edges
+            .into_iter()
+            .flat_map(|e| self.graph.unpack_edge(e))
+            .collect();
+        let mut nodes: Vec<u32> = edges
+            .iter()
+            .map(|&e| self.graph.edges[e].source_id)
+            .collect();
+        nodes.push(target);
+
+        Some(Path {
+            nodes: MyVec(nodes),
+            edges: MyVec(edges),
+            total_dimension_costs,
+        })
+    }
+
+    fn relax(&mut self, target: u32, edge_id: u32, cost: f64) -> bool {
+        if cost < self.dist[target] {
+            if self.dist[target] == f64::MAX {
+                self.touched.push(target);
+            }
+            self.dist[target] = cost;
+            self.previous[target] = Some(edge_id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Dijkstra from the nodes in the queue along edges to nodes of at least
+    /// the same level, or exactly the same level if `same_level` is set.
+    fn search(&mut self, alpha: &[f64], same_level: bool) {
+        let graph = self.graph;
+        while let Some(State {
+            node_id,
+            total_cost,
+            ..
+        }) = self.candidates.pop()
+        {
+            if total_cost > self.dist[node_id] {
+                continue;
+            }
+            let level = graph.nodes[node_id].ch_level;
+            for half_edge in graph.edges_of(node_id, EdgeDirection::Out) {
+                let target_level = graph.nodes[half_edge.target_id].ch_level;
+                if target_level < level {
+                    break;
+                }
+                if same_level && target_level > level {
+                    continue;
+                }
+                let cost = total_cost + costs_by_alpha(half_edge.edge_costs, alpha);
+                if self.relax(half_edge.target_id, half_edge.edge_id, cost) {
+                    self.candidates.push(State {
+                        node_id: half_edge.target_id,
+                        total_cost: cost,
+                        direction: EdgeDirection::Out,
+                    });
+                }
+            }
+        }
+    }
+
+    fn upward_search(&mut self, source: u32, alpha: &[f64]) {
+        self.candidates.push(State::new(source, EdgeDirection::Out));
+        self.search(alpha, false);
+    }
+
+    /// Nodes from which one of the targets is reachable by only going down in
+    /// the hierarchy, in descending order of their level.
+    fn sweep_nodes(&self, targets: &[u32]) -> Vec<u32> {
+        let mut selected = MyVec(vec![false; self.graph.nodes.len()]);
+        let mut stack = Vec::new();
+        for &t in targets {
+            if !selected[t] {
+                selected[t] = true;
+                stack.push(t);
+            }
+        }
+        while let Some(node) = stack.pop() {
+            let level = self.graph.nodes[node].ch_level;
+            for half_edge in self.graph.edges_of(node, EdgeDirection::In) {
+                if self.graph.nodes[half_edge.target_id].ch_level < level {
+                    break;
+                }
+                if !selected[half_edge.target_id] {
+                    selected[half_edge.target_id] = true;
+                    stack.push(half_edge.target_id);
+                }
+            }
+        }
+        (0..self.graph.nodes.len() as u32)
+            .rev()
+            .filter(|&n| selected[n])
+            .collect()
+    }
+
+    /// `order` has to be sorted by descending level.
+    fn sweep(&mut self, order: &[u32], alpha: &[f64]) {
+        let graph = self.graph;
+        let mut start = 0;
+        while start < order.len() {
+            let level = graph.nodes[order[start]].ch_level;
+            let end = order[start..]
+                .iter()
+                .position(|&n| graph.nodes[n].ch_level != level)
+                .map_or(order.len(), |p| start + p);
+
+            for &node in &order[start..end] {
+                for half_edge in graph.edges_of(node, EdgeDirection::In) {
+                    if graph.nodes[half_edge.target_id].ch_level <= level {
+                        break;
+                    }
+                    let source_dist = self.dist[half_edge.target_id];
+                    if source_dist == f64::MAX {
+                        continue;
+                    }
+                    let cost = source_dist + costs_by_alpha(half_edge.edge_costs, alpha);
+                    self.relax(node, half_edge.edge_id, cost);
+                }
+            }
+
+            for &node in &order[start..end] {
+                if self.dist[node] < f64::MAX {
+                    self.candidates.push(State {
+                        node_id: node,
+                        total_cost: self.dist[node],
+                        direction: EdgeDirection::Out,
+                    });
+                }
+            }
+            self.search(alpha, true);
+
+            start = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::float_eq;
+    use crate::graph::{dijkstra::Dijkstra, parse_graph_file};
+    use crate::utils::{randomized_preference, same_array};
+
+    fn check_against_dijkstra(graph: &Graph) {
+        let mut dijkstra = Dijkstra::new(graph);
+        let mut phast = Phast::new(graph);
+        let mut rng = StdRng::seed_from_u64(3);
+        let n = graph.nodes.len() as u32;
+        let targets: Vec<u32> = (0..n).step_by(3).collect();
+
+        for s in 0..n {
+            let alpha = randomized_preference(&mut rng, graph.dim as usize);
+            let expected: Vec<_> = (0..n)
+                .map(|t| dijkstra.run(s, t, &alpha).map(|r| r.total_cost))
+                .collect();
+
+            phast.run(s, &alpha);
+            for t in 0..n {
+                assert_eq!(phast.distance(t).is_some(), expected[t as usize].is_some());
+                if let Some(cost) = expected[t as usize] {
+                    assert!(float_eq!(phast.distance(t).unwrap(), cost));
+                    let path = phast.path(t).unwrap();
+                    assert_eq!(*path.nodes.first().unwrap(), s);
+                    assert_eq!(*path.nodes.last().unwrap(), t);
+                    let mut costs = vec![0.0; graph.dim as usize];
+                    for (i, &e) in path.edges.iter().enumerate() {
+                        assert_eq!(graph.edges[e].source_id, path.nodes[i]);
+                        assert!(graph.edges[e].replaced_edges.is_none());
+                        add_edge_costs(&mut costs, &graph.edges[e].edge_costs);
+                    }
+                    assert!(same_array(&costs, &path.total_dimension_costs));
+                    assert!(float_eq!(costs_by_alpha(&costs, &alpha), cost));
+                }
+            }
+
+            let distances = phast.run_one_to_many(s, &targets, &alpha);
+            for (&t, d) in targets.iter().zip(distances) {
+                assert!(float_eq!(
+                    d.unwrap_or(-1.),
+                    expected[t as usize].unwrap_or(-1.)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_contracted_graph() {
+        check_against_dijkstra(&parse_graph_file("./resources/concTestGraph").unwrap());
+    }
+
+    #[test]
+    fn test_uncontracted_graph() {
+        check_against_dijkstra(&parse_graph_file("./resources/testGraph").unwrap());
+    }
+}
@@ -0,0 +1,114 @@
+use std::ops::{Deref, DerefMut};
+
+use parking_lot::Mutex;
+
+use super::{Dijkstra, TieBreaking};
+use crate::graph::Graph;
+
+/// Hands out `Dijkstra`s for a shared graph, so that threads do not have to
+/// clone and allocate a complete search state for every parallel run. An
+/// engine goes back into the pool when its `PooledDijkstra` is dropped and is
+/// reused by the next thread asking for one.
+pub struct QueryPool<'a> {
+    graph: &'a Graph,
+    stall_on_demand: bool,
+    tie_breaking: TieBreaking,
+    idle: Mutex<Vec<Dijkstra<'a>>>,
+}
+
+impl<'a> QueryPool<'a> {
+    /// Creates an empty pool. New engines use the graph and the settings of
+    /// `template`.
+    pub fn new(template: &Dijkstra<'a>) -> Self {
+        QueryPool {
+            graph: template.graph,
+            stall_on_demand: template.stall_on_demand,
+            tie_breaking: template.tie_breaking,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self) -> PooledDijkstra<'_, 'a> {
+        let dijkstra = self.idle.lock().pop().unwrap_or_else(|| {
+            let mut dijkstra = Dijkstra::new(self.graph);
+            dijkstra.set_stall_on_demand(self.stall_on_demand);
+            dijkstra.set_tie_breaking(self.tie_breaking);
+            dijkstra
+        });
+        PooledDijkstra {
+            pool: self,
+            dijkstra: Some(dijkstra),
+        }
+    }
+
+    /// Number of engines currently waiting in the pool.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().len()
+    }
+}
+
+pub struct PooledDijkstra<'p, 'a> {
+    pool: &'p QueryPool<'a>,
+    dijkstra: Option<Dijkstra<'a>>,
+}
+
+impl<'p, 'a> Deref for PooledDijkstra<'p, 'a> {
+    type Target = Dijkstra<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.dijkstra.as_ref().unwrap()
+    }
+}
+
+impl<'p, 'a> DerefMut for PooledDijkstra<'p, 'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dijkstra.as_mut().unwrap()
+    }
+}
+
+impl<'p, 'a> Drop for PooledDijkstra<'p, 'a> {
+    fn drop(&mut self) {
+        if let Some(dijkstra) = self.dijkstra.take() {
+            self.pool.idle.lock().push(dijkstra);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::parse_graph_file;
+    use crate::utils::{equal_weights, parallel::work_stealing_map};
+
+    #[test]
+    fn test_engines_are_reused() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut template = Dijkstra::new(&graph);
+        template.set_tie_breaking(TieBreaking::CostVector);
+        let pool = QueryPool::new(&template);
+        let alpha = equal_weights(graph.dim as usize);
+
+        {
+            let first = pool.get();
+            let second = pool.get();
+            assert_eq!(first.tie_breaking(), TieBreaking::CostVector);
+            assert_eq!(second.tie_breaking(), TieBreaking::CostVector);
+            assert_eq!(pool.idle_count(), 0);
+        }
+        assert_eq!(pool.idle_count(), 2);
+
+        let n = graph.nodes.len() as u32;
+        let pairs: Vec<(u32, u32)> = (0..n).flat_map(|s| (0..n).map(move |t| (s, t))).collect();
+        let costs = work_stealing_map(
+            &pairs,
+            || pool.get(),
+            |dijkstra, &(s, t)| dijkstra.run(s, t, &alpha).map(|r| r.total_cost),
+        );
+        let expected: Vec<_> = pairs
+            .iter()
+            .map(|&(s, t)| template.run(s, t, &alpha).map(|r| r.total_cost))
+            .collect();
+        assert_eq!(costs, expected);
+        assert!(pool.idle_count() >= 2);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fmt::{self, Display},
+    ops::AddAssign,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::Serialize;
+
+/// Work done by a single CH query.
+#[derive(Debug, Serialize, Default, Clone, Copy, PartialEq)]
+pub struct QueryStats {
+    /// Nodes taken from the queue with their final distance
+    pub settled: usize,
+    /// Settled nodes whose edges were not relaxed due to stall-on-demand
+    pub stalled: usize,
+    /// Edges looked at while relaxing settled nodes
+    pub relaxed: usize,
+    /// Elements pushed into the queue
+    pub pushes: usize,
+}
+
+impl AddAssign for QueryStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.settled += rhs.settled;
+        self.stalled += rhs.stalled;
+        self.relaxed += rhs.relaxed;
+        self.pushes += rhs.pushes;
+    }
+}
+
+/// Sum of the statistics of several queries.
+#[derive(Debug, Serialize, Default, Clone, Copy)]
+pub struct AggregateQueryStats {
+    pub queries: usize,
+    pub total: QueryStats,
+}
+
+impl AggregateQueryStats {
+    pub fn add_query(&mut self, stats: QueryStats) {
+        self.queries += 1;
+        self.total += stats;
+    }
+}
+
+/// Lock-free counterpart of `AggregateQueryStats`, shared by all queries.
+#[derive(Debug, Default)]
+pub struct AtomicQueryStats {
+    queries: AtomicUsize,
+    settled: AtomicUsize,
+    stalled: AtomicUsize,
+    relaxed: AtomicUsize,
+    pushes: AtomicUsize,
+}
+
+impl AtomicQueryStats {
+    pub fn add_query(&self, stats: QueryStats) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.settled.fetch_add(stats.settled, Ordering::Relaxed);
+        self.stalled.fetch_add(stats.stalled, Ordering::Relaxed);
+        self.relaxed.fetch_add(stats.relaxed, Ordering::Relaxed);
+        self.pushes.fetch_add(stats.pushes, Ordering::Relaxed);
+    }
+
+    /// Merges the counters into an `AggregateQueryStats`.
+    pub fn load(&self) -> AggregateQueryStats {
+        AggregateQueryStats {
+            queries: self.queries.load(Ordering::Relaxed),
+            total: QueryStats {
+                settled: self.settled.load(Ordering::Relaxed),
+                stalled: self.stalled.load(Ordering::Relaxed),
+                relaxed: self.relaxed.load(Ordering::Relaxed),
+                pushes: self.pushes.load(Ordering::Relaxed),
+            },
+        }
+    }
+
+    pub fn clear(&self) {
+        for counter in [
+            &self.queries,
+            &self.settled,
+            &self.stalled,
+            &self.relaxed,
+            &self.pushes,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Display for AggregateQueryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let per_query = |v: usize| v as f64 / self.queries.max(1) as f64;
+        writeln!(f, "  - Queries: {}", self.queries)?;
+        writeln!(
+            f,
+            "  - Settled: {} ({:.1} per query)",
+            self.total.settled,
+            per_query(self.total.settled)
+        )?;
+        writeln!(
+            f,
+            "  - Stalled: {} ({:.1} per query)",
+            self.total.stalled,
+            per_query(self.total.stalled)
+        )?;
+        writeln!(
+            f,
+            "  - Relaxed: {} ({:.1} per query)",
+            self.total.relaxed,
+            per_query(self.total.relaxed)
+        )?;
+        write!(
+            f,
+            "  - Pushes:  {} ({:.1} per query)",
+            self.total.pushes,
+            per_query(self.total.pushes)
+        )
+    }
+}
@@ -0,0 +1,65 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::Graph;
+
+/// Rule to choose between paths with equal cost for a preference.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum TieBreaking {
+    /// Whichever path the search finds first. This is the fastest, but the
+    /// CH and the plain Dijkstra may return different paths.
+    #[default]
+    Unspecified,
+    /// The path with the lexicographically smallest cost vector. The cost for
+    /// the preference is then computed from the summed up cost vector, so
+    /// ties are detected reliably as long as the costs add up exactly, e.g.
+    /// for integer costs. Paths with identical cost vectors are ordered by
+    /// their lexicographically smallest sequence of original edge ids.
+    CostVector,
+}
+
+impl std::str::FromStr for TieBreaking {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(TieBreaking::Unspecified),
+            "costs" => Ok(TieBreaking::CostVector),
+            _ => Err(format!(
+                "Unknown tie breaking rule '{}', possible values are: none, costs",
+                s
+            )),
+        }
+    }
+}
+
+/// Compares `(cost, costs)` with `(other_cost, other_costs)`. On equal cost
+/// the cost vectors are compared lexicographically. `Equal` means identical
+/// cost vectors, the caller then has to compare the edge sequences.
+pub(super) fn compare_costs(
+    cost: f64,
+    costs: &[f64],
+    other_cost: f64,
+    other_costs: &[f64],
+) -> Ordering {
+    match cost.partial_cmp(&other_cost) {
+        Some(Ordering::Equal) => costs
+            .iter()
+            .zip(other_costs)
+            .find(|(a, b)| a != b)
+            .and_then(|(a, b)| a.partial_cmp(b))
+            .unwrap_or(Ordering::Equal),
+        Some(ordering) => ordering,
+        None => Ordering::Greater,
+    }
+}
+
+/// Original edges of a path given by possibly shortcut `edges`, which are
+/// compared for the final tie break.
+pub(super) fn unpacked_edges(graph: &Graph, edges: impl IntoIterator<Item = u32>) -> Vec<u32> {
+    edges
+        .into_iter()
+        .flat_map(|e| graph.unpack_edge(e))
+        .collect()
+}
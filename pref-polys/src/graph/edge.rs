@@ -9,10 +9,6 @@ pub struct Edge {
     pub replaced_edges: Option<(u32, u32)>,
 }
 
-pub fn parse_costs(tokens: &[&str]) -> Vec<f64> {
-    tokens.iter().map(|c| c.parse().unwrap()).collect()
-}
-
 impl Edge {
     pub fn new(
         id: u32,
@@ -50,10 +46,10 @@ impl<'a> HalfEdge<'a> {
 
 #[derive(Debug)]
 pub struct GraphEdges {
-    offset: MyVec<u32>,
-    edge_id: MyVec<u32>,
-    target_id: MyVec<u32>,
-    costs: MyVec<f64>,
+    pub(super) offset: MyVec<u32>,
+    pub(super) edge_id: MyVec<u32>,
+    pub(super) target_id: MyVec<u32>,
+    pub(super) costs: MyVec<f64>,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -0,0 +1,171 @@
+//! Export of trajectories as GeoJSON, e.g. to look at them on a map.
+//!
+//! Every path becomes a `LineString` feature through the coordinates of its
+//! nodes. The properties contain the index of the path, its costs per metric
+//! and, if known, the cluster and preference it was assigned to.
+
+use std::{fs::File, io::BufWriter, path::Path as FilePath};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde_json::{json, Map, Value};
+
+use super::{path::Path, Graph};
+use crate::utils::Preference;
+
+/// Optional information about a path which is added to its properties
+#[derive(Debug, Clone, Default)]
+pub struct PathLabel {
+    pub cluster: Option<usize>,
+    pub preference: Option<Preference>,
+}
+
+fn by_metric(graph: &Graph, values: &[f64]) -> Value {
+    graph
+        .metric_names
+        .iter()
+        .zip(values)
+        .map(|(name, &v)| (name.clone(), json!(v)))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+/// Builds a GeoJSON `FeatureCollection` of the paths. `labels` is either
+/// empty or contains one label per path. Fails if a path does not match the
+/// graph.
+pub fn paths_to_geojson(graph: &Graph, paths: &[Path], labels: &[PathLabel]) -> Result<Value> {
+    ensure!(
+        labels.is_empty() || labels.len() == paths.len(),
+        "Got {} labels for {} paths",
+        labels.len(),
+        paths.len()
+    );
+    for (index, path) in paths.iter().enumerate() {
+        if let Some(issue) = path.validate(graph).first() {
+            bail!("Path {} does not match the graph: {}", index, issue);
+        }
+    }
+
+    let mut features = Vec::with_capacity(paths.len());
+    for (index, path) in paths.iter().enumerate() {
+        let coordinates = path
+            .nodes
+            .iter()
+            .map(|&n| {
+                graph
+                    .coordinates(n)
+                    .map(|c| json!([c.lon, c.lat]))
+                    .with_context(|| format!("Node {} of path {} has no coordinates", n, index))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut properties = Map::new();
+        properties.insert("index".to_string(), json!(index));
+        properties.insert(
+            "costs".to_string(),
+            by_metric(graph, &path.total_dimension_costs),
+        );
+        if let Some(label) = labels.get(index) {
+            if let Some(cluster) = label.cluster {
+                properties.insert("cluster".to_string(), json!(cluster));
+            }
+            if let Some(preference) = &label.preference {
+                properties.insert("preference".to_string(), by_metric(graph, preference));
+            }
+        }
+
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": coordinates,
+            },
+            "properties": properties,
+        }));
+    }
+
+    Ok(json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+}
+
+pub fn write_paths_geojson(
+    file_path: impl AsRef<FilePath>,
+    graph: &Graph,
+    paths: &[Path],
+    labels: &[PathLabel],
+) -> Result<()> {
+    let geojson = paths_to_geojson(graph, paths, labels)?;
+    let file = BufWriter::new(File::create(file_path)?);
+    serde_json::to_writer(file, &geojson)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{
+        dijkstra::{find_shortest_path, Dijkstra},
+        parse_minimal_graph,
+    };
+    use crate::utils::MyVec;
+
+    #[test]
+    fn test_paths_to_geojson() {
+        let graph = parse_minimal_graph(
+            "2\nlength time\n3\n2\n0 0 48.7 9.1\n1 0 48.8 9.2\n2 0 48.9 9.3\n0 0 1 1 2 -1 -1\n1 1 2 3 4 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let path = find_shortest_path(&mut dijkstra, &[0, 2], &[0.5, 0.5]).unwrap();
+        let labels = vec![PathLabel {
+            cluster: Some(3),
+            preference: Some(MyVec(vec![0.25, 0.75])),
+        }];
+
+        let geojson = paths_to_geojson(&graph, &[path.clone(), path.clone()], &[]).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[1]["properties"]["index"], 1);
+        assert!(features[1]["properties"].get("cluster").is_none());
+
+        let geojson = paths_to_geojson(&graph, &[path], &labels).unwrap();
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            json!([[9.1, 48.7], [9.2, 48.8], [9.3, 48.9]])
+        );
+        let properties = &feature["properties"];
+        assert_eq!(properties["costs"], json!({"length": 4.0, "time": 6.0}));
+        assert_eq!(properties["cluster"], 3);
+        assert_eq!(
+            properties["preference"],
+            json!({"length": 0.25, "time": 0.75})
+        );
+    }
+
+    #[test]
+    fn test_missing_coordinates_are_an_error() {
+        let graph =
+            parse_minimal_graph("1\na\n2\n1\n0 0\n1 0\n0 0 1 1 -1 -1\n".as_bytes()).unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let path = find_shortest_path(&mut dijkstra, &[0, 1], &[1.0]).unwrap();
+        assert!(paths_to_geojson(&graph, &[path], &[]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_nodes_are_an_error() {
+        let graph = parse_minimal_graph(
+            "1\na\n2\n1\n0 0 48.7 9.1\n1 0 48.8 9.2\n0 0 1 1 -1 -1\n".as_bytes(),
+        )
+        .unwrap();
+        let path = Path {
+            nodes: MyVec(vec![0, 5]),
+            edges: MyVec(vec![0]),
+            total_dimension_costs: MyVec(vec![1.0]),
+        };
+        assert!(paths_to_geojson(&graph, &[path], &[]).is_err());
+    }
+}
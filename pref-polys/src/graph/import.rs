@@ -0,0 +1,566 @@
+//! Importers for graph formats of other tools.
+//!
+//! - DIMACS shortest path challenge files: one `.gr` file per metric, which
+//!   all list the same arcs in the same order, and an optional `.co` file with
+//!   node coordinates.
+//! - CSV edge lists with a header line naming the columns.
+//!
+//! Imported graphs are not contracted. All nodes get ch level 0.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{ensure, Context};
+
+use super::{Coordinates, Edge, Graph, GraphParseError, Node};
+
+/// Iterates over the lines of a file together with their 1-based number.
+fn numbered_lines(
+    reader: impl BufRead,
+) -> impl Iterator<Item = Result<(usize, String), GraphParseError>> {
+    reader.lines().enumerate().map(|(index, line)| {
+        line.map(|l| (index + 1, l))
+            .map_err(|source| GraphParseError::Io {
+                line: index + 1,
+                source,
+            })
+    })
+}
+
+fn parse_token<T: FromStr>(line: usize, token: &str, expected: &str) -> Result<T, GraphParseError> {
+    token.parse().map_err(|_| GraphParseError::InvalidToken {
+        line,
+        token: token.to_string(),
+        expected: expected.to_string(),
+    })
+}
+
+fn check_token_count(
+    line: usize,
+    tokens: &[&str],
+    expected: usize,
+    what: &str,
+) -> Result<(), GraphParseError> {
+    if tokens.len() != expected {
+        return Err(GraphParseError::WrongTokenCount {
+            line,
+            found: tokens.len(),
+            expected: format!("{} tokens for {}", expected, what),
+        });
+    }
+    Ok(())
+}
+
+/// A graph assembled from DIMACS files. Every call of `read_metric` adds one
+/// cost dimension.
+#[derive(Default)]
+pub struct DimacsGraph {
+    node_count: Option<usize>,
+    arcs: Vec<(u32, u32)>,
+    costs: Vec<Vec<f64>>,
+    metric_names: Vec<String>,
+    coordinates: Option<Vec<Coordinates>>,
+}
+
+impl DimacsGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the problem line of a file, which is "p", the problem `kind`, the
+    /// node count and `extra` further values. Checks the node count against
+    /// the previously read files and stores it in `file_node_count`, which
+    /// must not be set by an earlier problem line of the same file.
+    fn problem_line(
+        &self,
+        file_node_count: &mut Option<usize>,
+        line: usize,
+        tokens: &[&str],
+        kind: &[&str],
+        extra: usize,
+    ) -> Result<usize, GraphParseError> {
+        if file_node_count.is_some() {
+            return Err(GraphParseError::InvalidToken {
+                line,
+                token: "p".to_string(),
+                expected: "a single problem line".to_string(),
+            });
+        }
+        check_token_count(line, tokens, kind.len() + 2 + extra, "the problem line")?;
+        if tokens[1..=kind.len()] != *kind {
+            return Err(GraphParseError::InvalidToken {
+                line,
+                token: tokens[1..=kind.len()].join(" "),
+                expected: format!("problem type '{}'", kind.join(" ")),
+            });
+        }
+        let node_count: usize = parse_token(line, tokens[kind.len() + 1], "number of nodes")?;
+        if let Some(expected) = self.node_count {
+            if node_count != expected {
+                return Err(GraphParseError::InvalidToken {
+                    line,
+                    token: node_count.to_string(),
+                    expected: format!("{} nodes like in the previous files", expected),
+                });
+            }
+        }
+        *file_node_count = Some(node_count);
+        Ok(node_count)
+    }
+
+    /// DIMACS node ids start at 1
+    fn node_id(line: usize, token: &str, node_count: usize) -> Result<u32, GraphParseError> {
+        let id: u32 = parse_token(line, token, "node id")?;
+        if id == 0 || id as usize > node_count {
+            return Err(GraphParseError::UnknownNode { line, node_id: id });
+        }
+        Ok(id - 1)
+    }
+
+    /// Reads a `.gr` file and adds its arc weights as metric `name`.
+    pub fn read_metric(
+        &mut self,
+        name: impl Into<String>,
+        reader: impl BufRead,
+    ) -> Result<(), GraphParseError> {
+        let first_metric = self.metric_names.is_empty();
+        let mut node_count = None;
+        let mut arc_count = None;
+        let mut arc_index = 0;
+        let mut last_line = 0;
+        // only added to the graph once the whole file was read
+        let mut arcs = Vec::new();
+        let mut costs = Vec::new();
+
+        for line in numbered_lines(reader) {
+            let (line, content) = line?;
+            last_line = line;
+            let tokens: Vec<&str> = content.split_whitespace().collect();
+            match tokens.first() {
+                None | Some(&"c") => continue,
+                Some(&"p") => {
+                    self.problem_line(&mut node_count, line, &tokens, &["sp"], 1)?;
+                    arc_count = Some(parse_token(line, tokens[3], "number of arcs")?);
+                }
+                Some(&"a") => {
+                    let arc_count = arc_count.ok_or(GraphParseError::InvalidToken {
+                        line,
+                        token: "a".to_string(),
+                        expected: "problem line before the first arc".to_string(),
+                    })?;
+                    if arc_index >= arc_count {
+                        return Err(GraphParseError::TrailingData { line });
+                    }
+                    check_token_count(line, &tokens, 4, "an arc")?;
+                    let node_count = node_count.unwrap_or(0);
+                    let arc = (
+                        Self::node_id(line, tokens[1], node_count)?,
+                        Self::node_id(line, tokens[2], node_count)?,
+                    );
+                    let cost = parse_token(line, tokens[3], "arc weight")?;
+
+                    if first_metric {
+                        arcs.push(arc);
+                    } else {
+                        match self.arcs.get(arc_index) {
+                            Some(&expected) if expected == arc => (),
+                            Some(&expected) => {
+                                return Err(GraphParseError::MismatchingArc {
+                                    line,
+                                    expected,
+                                    found: arc,
+                                })
+                            }
+                            None => return Err(GraphParseError::TrailingData { line }),
+                        }
+                    }
+                    costs.push(cost);
+                    arc_index += 1;
+                }
+                Some(token) => {
+                    return Err(GraphParseError::InvalidToken {
+                        line,
+                        token: token.to_string(),
+                        expected: "line type c, p or a".to_string(),
+                    })
+                }
+            }
+        }
+
+        match arc_count {
+            Some(count) if arc_index >= count && arc_index >= self.arcs.len() => (),
+            _ => {
+                return Err(GraphParseError::UnexpectedEof {
+                    line: last_line + 1,
+                    expected: format!(
+                        "{} arcs, found {}",
+                        arc_count.unwrap_or(self.arcs.len()),
+                        arc_index
+                    ),
+                })
+            }
+        }
+        if first_metric {
+            self.arcs = arcs;
+            self.costs = costs.into_iter().map(|c| vec![c]).collect();
+        } else {
+            self.costs
+                .iter_mut()
+                .zip(costs)
+                .for_each(|(arc_costs, c)| arc_costs.push(c));
+        }
+        self.node_count = node_count;
+        self.metric_names.push(name.into());
+        Ok(())
+    }
+
+    /// Reads a `.co` file. Coordinates are given in millionths of degrees
+    /// with the longitude first.
+    pub fn read_coordinates(&mut self, reader: impl BufRead) -> Result<(), GraphParseError> {
+        let mut coordinates: Option<Vec<Option<Coordinates>>> = None;
+        let mut node_count = None;
+        let mut last_line = 0;
+
+        for line in numbered_lines(reader) {
+            let (line, content) = line?;
+            last_line = line;
+            let tokens: Vec<&str> = content.split_whitespace().collect();
+            match tokens.first() {
+                None | Some(&"c") => continue,
+                Some(&"p") => {
+                    let count =
+                        self.problem_line(&mut node_count, line, &tokens, &["aux", "sp", "co"], 0)?;
+                    coordinates = Some(vec![None; count]);
+                }
+                Some(&"v") => {
+                    let coordinates =
+                        coordinates.as_mut().ok_or(GraphParseError::InvalidToken {
+                            line,
+                            token: "v".to_string(),
+                            expected: "problem line before the first node".to_string(),
+                        })?;
+                    check_token_count(line, &tokens, 4, "a node")?;
+                    let id = Self::node_id(line, tokens[1], coordinates.len())?;
+                    let lon: f64 = parse_token(line, tokens[2], "longitude")?;
+                    let lat: f64 = parse_token(line, tokens[3], "latitude")?;
+                    if coordinates[id as usize].is_some() {
+                        return Err(GraphParseError::DuplicateNode {
+                            line,
+                            node_id: id + 1,
+                        });
+                    }
+                    coordinates[id as usize] = Some(Coordinates {
+                        lat: lat / 1_000_000.0,
+                        lon: lon / 1_000_000.0,
+                    });
+                }
+                Some(token) => {
+                    return Err(GraphParseError::InvalidToken {
+                        line,
+                        token: token.to_string(),
+                        expected: "line type c, p or v".to_string(),
+                    })
+                }
+            }
+        }
+
+        let coordinates = coordinates.unwrap_or_default();
+        let found = coordinates.iter().filter(|c| c.is_some()).count();
+        if node_count.is_none() || found < coordinates.len() {
+            return Err(GraphParseError::UnexpectedEof {
+                line: last_line + 1,
+                expected: format!(
+                    "coordinates for {} nodes, found {}",
+                    node_count.unwrap_or(0),
+                    found
+                ),
+            });
+        }
+        self.node_count = node_count;
+        self.coordinates = Some(coordinates.into_iter().flatten().collect());
+        Ok(())
+    }
+
+    pub fn into_graph(self) -> Graph {
+        assert!(
+            !self.metric_names.is_empty(),
+            "A DIMACS graph needs at least one metric"
+        );
+        let node_count = self.node_count.unwrap_or(0);
+        let nodes = (0..node_count as u32)
+            .map(|id| match &self.coordinates {
+                Some(c) => Node::with_coordinates(id, 0, c[id as usize].lat, c[id as usize].lon),
+                None => Node::new(id, 0),
+            })
+            .collect();
+        let edges = self
+            .arcs
+            .into_iter()
+            .zip(self.costs)
+            .enumerate()
+            .map(|(id, ((source, target), costs))| {
+                Edge::new(id as u32, source, target, costs, None)
+            })
+            .collect();
+        Graph::new(nodes, edges, self.metric_names)
+    }
+}
+
+/// Builds a graph from DIMACS `.gr` files, one per metric, and an optional
+/// `.co` file with the node coordinates. The metrics are named like the
+/// files without their extension unless `metric_names` is given.
+pub fn parse_dimacs_files(
+    metric_files: &[PathBuf],
+    metric_names: Option<Vec<String>>,
+    coordinate_file: Option<&Path>,
+) -> anyhow::Result<Graph> {
+    println!("Parsing DIMACS graph...");
+    ensure!(!metric_files.is_empty(), "At least one .gr file is needed");
+    let metric_names = metric_names.unwrap_or_else(|| {
+        metric_files
+            .iter()
+            .map(|f| f.file_stem().unwrap_or_default().to_string_lossy().into())
+            .collect()
+    });
+    ensure!(
+        metric_names.len() == metric_files.len(),
+        "Got {} metric names for {} .gr files",
+        metric_names.len(),
+        metric_files.len()
+    );
+
+    let mut graph = DimacsGraph::new();
+    for (file_path, name) in metric_files.iter().zip(metric_names) {
+        let file = File::open(file_path)
+            .with_context(|| format!("Could not open {}", file_path.display()))?;
+        graph
+            .read_metric(name, BufReader::new(file))
+            .with_context(|| format!("Could not parse {}", file_path.display()))?;
+    }
+    if let Some(file_path) = coordinate_file {
+        let file = File::open(file_path)
+            .with_context(|| format!("Could not open {}", file_path.display()))?;
+        graph
+            .read_coordinates(BufReader::new(file))
+            .with_context(|| format!("Could not parse {}", file_path.display()))?;
+    }
+    Ok(graph.into_graph())
+}
+
+/// Splits a CSV line at commas and removes surrounding quotes. Quoted values
+/// containing commas are not supported.
+fn csv_fields(line: &str) -> Vec<&str> {
+    line.split(',')
+        .map(|f| f.trim().trim_matches('"').trim())
+        .collect()
+}
+
+/// Builds a graph from a CSV edge list. The first line has to name the
+/// columns. Node ids may be arbitrary strings, the nodes are numbered in the
+/// order of their first appearance. The metrics are named like their columns.
+pub fn parse_csv_edge_list(
+    reader: impl BufRead,
+    source_column: &str,
+    target_column: &str,
+    metric_columns: &[String],
+) -> Result<Graph, GraphParseError> {
+    let mut lines = numbered_lines(reader).filter(|l| match l {
+        Ok((_, content)) => !content.trim().is_empty(),
+        Err(_) => true,
+    });
+
+    let (header_line, header) = lines.next().ok_or(GraphParseError::UnexpectedEof {
+        line: 1,
+        expected: "header line".to_string(),
+    })??;
+    let header = csv_fields(&header);
+    let column_index = |column: &str| {
+        header
+            .iter()
+            .position(|h| *h == column)
+            .ok_or_else(|| GraphParseError::MissingColumn {
+                line: header_line,
+                column: column.to_string(),
+            })
+    };
+    let source_index = column_index(source_column)?;
+    let target_index = column_index(target_column)?;
+    let metric_indices = metric_columns
+        .iter()
+        .map(|c| column_index(c))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut node_ids: HashMap<String, u32> = HashMap::new();
+    let mut node_id = |name: &str| {
+        let next_id = node_ids.len() as u32;
+        *node_ids.entry(name.to_string()).or_insert(next_id)
+    };
+
+    let mut edges = Vec::new();
+    for line in lines {
+        let (line, content) = line?;
+        let fields = csv_fields(&content);
+        check_token_count(line, &fields, header.len(), "an edge")?;
+        let costs = metric_indices
+            .iter()
+            .map(|&i| parse_token(line, fields[i], "edge cost"))
+            .collect::<Result<Vec<f64>, _>>()?;
+        let source = node_id(fields[source_index]);
+        let target = node_id(fields[target_index]);
+        edges.push(Edge::new(edges.len() as u32, source, target, costs, None));
+    }
+
+    let nodes = (0..node_ids.len() as u32)
+        .map(|id| Node::new(id, 0))
+        .collect();
+    Ok(Graph::new(nodes, edges, metric_columns.to_vec()))
+}
+
+pub fn parse_csv_edge_list_file(
+    file_path: impl AsRef<Path>,
+    source_column: &str,
+    target_column: &str,
+    metric_columns: &[String],
+) -> anyhow::Result<Graph> {
+    println!("Parsing CSV edge list...");
+    let file = File::open(&file_path)
+        .with_context(|| format!("Could not open {}", file_path.as_ref().display()))?;
+    Ok(parse_csv_edge_list(
+        BufReader::new(file),
+        source_column,
+        target_column,
+        metric_columns,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISTANCES: &str = "c distances\np sp 3 3\na 1 2 10\na 2 3 20\na 3 1 30\n";
+    const TIMES: &str = "c times\n\np sp 3 3\na 1 2 1\na 2 3 2\na 3 1 3\n";
+
+    #[test]
+    fn test_dimacs_metrics_are_merged() {
+        let mut dimacs = DimacsGraph::new();
+        dimacs
+            .read_metric("distance", DISTANCES.as_bytes())
+            .unwrap();
+        dimacs.read_metric("time", TIMES.as_bytes()).unwrap();
+        dimacs
+            .read_coordinates(
+                "p aux sp co 3\nv 1 9100000 48700000\nv 2 9200000 48800000\nv 3 9300000 48900000\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        let graph = dimacs.into_graph();
+
+        assert_eq!(graph.metric_names.0, vec!["distance", "time"]);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges.len(), 3);
+        let edge = &graph.edges[2u32];
+        assert_eq!((edge.source_id, edge.target_id), (2, 0));
+        assert_eq!(edge.edge_costs, vec![30.0, 3.0]);
+        let c = graph.coordinates(1).unwrap();
+        assert!(crate::float_eq!(c.lat, 48.8) && crate::float_eq!(c.lon, 9.2));
+        assert!(graph.validate().is_valid());
+    }
+
+    #[test]
+    fn test_dimacs_errors() {
+        let mut dimacs = DimacsGraph::new();
+        dimacs
+            .read_metric("distance", DISTANCES.as_bytes())
+            .unwrap();
+        let err = dimacs
+            .read_metric("time", "p sp 3 3\na 1 2 1\na 3 2 2\na 3 1 3\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::MismatchingArc {
+                line: 3,
+                expected: (1, 2),
+                found: (2, 1)
+            }
+        ));
+
+        let err = dimacs
+            .read_metric("time", "p sp 3 3\na 1 2 1\na 2 3 2\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::UnexpectedEof { line: 4, .. }
+        ));
+
+        let err = dimacs
+            .read_coordinates("v 1 9100000 48700000\np aux sp co 3\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(err, GraphParseError::InvalidToken { line: 1, .. }));
+
+        let err = dimacs
+            .read_metric("time", "p sp 3 3\np sp 3 3\na 1 2 1\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(err, GraphParseError::InvalidToken { line: 2, .. }));
+
+        // failed files leave the graph unchanged
+        let graph = dimacs.into_graph();
+        assert_eq!(graph.dim, 1);
+        assert_eq!(graph.edges[0u32].edge_costs, vec![10.0]);
+
+        // the node count of a failed file is not kept either
+        let mut dimacs = DimacsGraph::new();
+        assert!(dimacs
+            .read_metric("distance", "p sp 5 1\n".as_bytes())
+            .is_err());
+        dimacs
+            .read_metric("distance", DISTANCES.as_bytes())
+            .unwrap();
+        assert_eq!(dimacs.into_graph().nodes.len(), 3);
+
+        let err = DimacsGraph::new()
+            .read_metric("distance", "p sp 3 1\na 1 4 1\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::UnknownNode {
+                line: 2,
+                node_id: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn test_csv_edge_list() {
+        let csv = "\"from\",\"to\",length,time,lanes\na,b,10,1.5,2\nb,c,20,2.5,1\n\nc,a,30,3.5,1\n";
+        let metrics = vec!["time".to_string(), "length".to_string()];
+        let graph = parse_csv_edge_list(csv.as_bytes(), "from", "to", &metrics).unwrap();
+
+        assert_eq!(graph.metric_names.0, metrics);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges.len(), 3);
+        let edge = &graph.edges[1u32];
+        assert_eq!((edge.source_id, edge.target_id), (1, 2));
+        assert_eq!(edge.edge_costs, vec![2.5, 20.0]);
+
+        let err =
+            parse_csv_edge_list(csv.as_bytes(), "from", "to", &["speed".to_string()]).unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::MissingColumn { line: 1, ref column } if column == "speed"
+        ));
+
+        let err = parse_csv_edge_list(
+            "from,to,length\na,b,x\n".as_bytes(),
+            "from",
+            "to",
+            &["length".to_string()],
+        )
+        .unwrap_err();
+        assert!(matches!(err, GraphParseError::InvalidToken { line: 2, .. }));
+    }
+}
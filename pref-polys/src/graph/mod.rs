@@ -1,31 +1,63 @@
 pub use edge::Edge;
-pub use node::Node;
+pub use node::{Coordinates, Node};
 
 use edge::{EdgeDirection, GraphEdges, HalfEdgeIter};
 
+pub use cache::{cache_path, parse_minimal_graph_file_cached, CACHE_VERSION};
+pub use import::{parse_csv_edge_list, parse_csv_edge_list_file, parse_dimacs_files, DimacsGraph};
+pub use parse::{
+    parse_graph, parse_graph_file, parse_minimal_graph, parse_minimal_graph_file, GraphParseError,
+};
+pub use sampling::{DistanceBand, PairSampler};
+pub use subgraph::Subgraph;
+pub use validate::{GraphIssue, ValidationReport};
+
 use crate::utils::MyVec;
-use std::{collections::HashMap, time::Instant};
+use std::collections::HashMap;
 
+mod cache;
+pub mod contraction;
 pub mod dijkstra;
 mod edge;
+pub mod geojson;
+mod import;
 mod node;
+mod parse;
 pub mod path;
+mod sampling;
+mod scc;
+mod subgraph;
+mod validate;
+mod write;
 
 #[derive(Debug)]
 pub struct Graph {
     pub nodes: MyVec<Node>,
     pub edges: MyVec<Edge>,
     pub dim: u32,
+    /// Names of the cost dimensions, e.g. distance or travel time
+    pub metric_names: MyVec<String>,
     edges_in: GraphEdges,
     edges_out: GraphEdges,
 }
 
+/// Placeholder names for graphs whose file format does not name the metrics.
+pub fn default_metric_names(dim: usize) -> Vec<String> {
+    (0..dim).map(|i| format!("metric_{}", i)).collect()
+}
+
 impl Graph {
-    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>) -> Graph {
+    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>, metric_names: Vec<String>) -> Graph {
         println!("Constructing graph...");
         let mut nodes = MyVec(nodes);
         let mut edges = MyVec(edges);
-        let dim = edges.first().map(|e| e.edge_costs.len()).unwrap_or(0) as u32;
+        let dim = metric_names.len() as u32;
+        assert!(
+            edges
+                .iter()
+                .all(|e| e.edge_costs.len() == metric_names.len()),
+            "Edge costs do not match the number of metrics"
+        );
 
         nodes.sort_by_key(|n| n.ch_level);
         let mut id_map = HashMap::new();
@@ -73,6 +105,7 @@ impl Graph {
             nodes,
             edges,
             dim,
+            metric_names: MyVec(metric_names),
             edges_in,
             edges_out,
         }
@@ -87,6 +120,33 @@ impl Graph {
         }
     }
 
+    /// Returns the coordinates of a node, if the graph file provided them.
+    pub fn coordinates(&self, node_id: u32) -> Option<Coordinates> {
+        self.nodes[node_id].coordinates
+    }
+
+    /// Whether all nodes of the graph have coordinates.
+    pub fn has_coordinates(&self) -> bool {
+        !self.nodes.is_empty() && self.nodes.iter().all(|n| n.coordinates.is_some())
+    }
+
+    /// Returns the index of the metric with the given name.
+    pub fn metric_index(&self, name: &str) -> Option<usize> {
+        self.metric_names.iter().position(|m| m == name)
+    }
+
+    /// Describes a preference with the metric names, e.g. "40.0% distance,
+    /// 60.0% time".
+    pub fn describe_preference(&self, alpha: &[f64]) -> String {
+        assert_eq!(alpha.len(), self.metric_names.len());
+        self.metric_names
+            .iter()
+            .zip(alpha)
+            .map(|(name, a)| format!("{:.1}% {}", a * 100.0, name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     pub fn unpack_edge(&self, edge: u32) -> Vec<u32> {
         if let Some((edge1, edge2)) = self.edges[edge].replaced_edges {
             if self.edges[edge1].source_id != self.edges[edge].source_id {
@@ -112,178 +172,25 @@ impl Graph {
     }
 }
 
-pub fn parse_graph_file(file_path: &str) -> Result<Graph, Box<dyn std::error::Error>> {
-    // use crate::EDGE_COST_DIMENSION;
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-
-    println!("Parsing graph...");
-    let mut nodes: Vec<Node> = Vec::new();
-    let mut edges: Vec<Edge> = Vec::new();
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
-    for _i in 0..4 {
-        // comments and blanks
-        lines.next();
-    }
-    let cost_dim: usize = lines.next().expect("No edge cost dim given")?.parse()?;
-    // assert_eq!(EDGE_COST_DIMENSION, cost_dim);
-    let num_of_nodes = lines
-        .next()
-        .expect("Number of nodes not present in file")?
-        .parse()?;
-    let num_of_edges = lines
-        .next()
-        .expect("Number of edges not present in file")?
-        .parse()?;
-
-    let mut parsed_nodes: usize = 0;
-    let mut parsed_edges: u32 = 0;
-    while let Some(Ok(line)) = lines.next() {
-        let tokens: Vec<&str> = line.split_whitespace().collect();
-        if tokens[0] == "#" || tokens[0] == "\n" {
-            continue;
-        }
-        if parsed_nodes < num_of_nodes {
-            nodes.push(Node::new(
-                tokens[0].parse()?,
-                // tokens[2].parse()?,
-                // tokens[3].parse()?,
-                // tokens[4].parse()?,
-                tokens[5].parse()?,
-            ));
-            parsed_nodes += 1;
-        } else if parsed_edges < num_of_edges {
-            let replaced_edges = if tokens[tokens.len() - 2] == "-1" {
-                None
-            } else {
-                Some((
-                    tokens[tokens.len() - 2].parse()?,
-                    tokens[tokens.len() - 1].parse()?,
-                ))
-            };
-
-            let costs = edge::parse_costs(&tokens[2..tokens.len() - 2]);
-            assert_eq!(
-                costs.len(),
-                cost_dim,
-                "Edge with wrong amount of costs parsed"
-            );
-            edges.push(Edge::new(
-                parsed_edges,
-                tokens[0].parse()?,
-                tokens[1].parse()?,
-                costs,
-                replaced_edges,
-            ));
-            parsed_edges += 1;
-        } else {
-            panic!("Something doesn't add up with the amount of nodes and edges in graph file");
-        }
-    }
-    Ok(Graph::new(nodes, edges))
-}
-
-pub fn parse_minimal_graph_file(file_path: impl AsRef<std::path::Path>) -> anyhow::Result<Graph> {
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-    let start = Instant::now();
-
-    println!("Parsing graph...");
-    let mut nodes: Vec<Node> = Vec::new();
-    let mut edges: Vec<Edge> = Vec::new();
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
-
-    loop {
-        if let Some(Ok(line)) = lines.next() {
-            if !line.starts_with('#') {
-                break;
-            }
-        }
-    }
-
-    let cost_dim: usize = lines.next().expect("No edge cost dim given")?.parse()?;
-
-    let metric_name_line = lines.next().expect("No metric names given")?;
-    let metric_names = metric_name_line.split(' ');
-    assert_eq!(
-        metric_names.count(),
-        cost_dim,
-        "Wrong number of metric names in graph file"
-    );
-
-    let num_of_nodes = lines
-        .next()
-        .expect("Number of nodes not present in file")?
-        .parse()?;
-    let num_of_edges = lines
-        .next()
-        .expect("Number of edges not present in file")?
-        .parse()?;
-
-    let mut parsed_nodes: usize = 0;
-    let mut parsed_edges = 0;
-    while let Some(Ok(line)) = lines.next() {
-        let tokens: Vec<&str> = line.split_whitespace().collect();
-        if tokens[0] == "#" || tokens[0] == "\n" {
-            continue;
-        }
-        if parsed_nodes < num_of_nodes {
-            if tokens.len() != 2 {
-                panic!("Not right amount of information for a node");
-            }
-            nodes.push(Node::new(
-                tokens[0].parse()?,
-                tokens[1].parse().unwrap_or(0),
-            ));
-            parsed_nodes += 1;
-        } else if parsed_edges < num_of_edges {
-            if tokens.len() != 5 + cost_dim {
-                panic!(
-                    "Not right amount of information for an edge, {} instead of {}",
-                    tokens.len(),
-                    5 + cost_dim
-                );
-            }
-            let replaced_edges = if tokens[tokens.len() - 2] == "-1" {
-                None
-            } else {
-                Some((
-                    tokens[tokens.len() - 2].parse()?,
-                    tokens[tokens.len() - 1].parse()?,
-                ))
-            };
-            edges.push(Edge::new(
-                parsed_edges,
-                tokens[1].parse()?,
-                tokens[2].parse()?,
-                edge::parse_costs(&tokens[3..tokens.len() - 2]),
-                replaced_edges,
-            ));
-            parsed_edges += 1;
-        } else {
-            panic!("Something doesn't add up with the amount of nodes and edges in graph file");
-        }
-
-        if nodes.len() != parsed_nodes || edges.len() != parsed_edges as usize {
-            panic!("Not enough nodes or edges parsed");
-        }
-    }
-    let graph = Graph::new(nodes, edges);
-    let time = start.elapsed();
-    println!("graph loading time: {}s", time.as_secs_f64());
-    Ok(graph)
-}
-
 #[cfg(test)]
 mod tests {
     use crate::utils::same_array;
 
     use super::*;
 
+    #[test]
+    fn test_describe_preference() {
+        let graph = parse_minimal_graph_file("./resources/lp_only_test_graph").unwrap();
+        assert_eq!(graph.metric_names.0, vec!["a", "b", "c"]);
+        assert_eq!(
+            graph.describe_preference(&[0.4, 0.6, 0.0]),
+            "40.0% a, 60.0% b, 0.0% c"
+        );
+
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        assert_eq!(graph.metric_names.0, default_metric_names(4));
+    }
+
     #[test]
     fn test_graph_structure() {
         let graph = parse_graph_file("./resources/concTestGraph").unwrap();
@@ -2,10 +2,30 @@
 pub struct Node {
     pub id: u32,
     pub ch_level: u32,
+    pub coordinates: Option<Coordinates>,
+}
+
+/// Geographic position of a node in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
 }
 
 impl Node {
     pub fn new(id: u32, ch_level: u32) -> Node {
-        Node { id, ch_level }
+        Node {
+            id,
+            ch_level,
+            coordinates: None,
+        }
+    }
+
+    pub fn with_coordinates(id: u32, ch_level: u32, lat: f64, lon: f64) -> Node {
+        Node {
+            id,
+            ch_level,
+            coordinates: Some(Coordinates { lat, lon }),
+        }
     }
 }
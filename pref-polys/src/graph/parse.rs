@@ -0,0 +1,542 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{self, BufRead, BufReader, Lines},
+    str::FromStr,
+    time::Instant,
+};
+
+use super::{default_metric_names, Edge, Graph, Node};
+
+/// Everything that can go wrong while reading a graph file. All variants carry
+/// the (1-based) line of the file the problem was detected in.
+#[derive(Debug)]
+pub enum GraphParseError {
+    /// The line could not be read from the underlying reader
+    Io { line: usize, source: io::Error },
+    /// The file ended while `expected` was still missing
+    UnexpectedEof { line: usize, expected: String },
+    /// `token` could not be parsed as `expected`
+    InvalidToken {
+        line: usize,
+        token: String,
+        expected: String,
+    },
+    /// The line consists of `found` tokens instead of `expected`
+    WrongTokenCount {
+        line: usize,
+        found: usize,
+        expected: String,
+    },
+    /// An edge starts or ends at a node id which was not declared
+    UnknownNode { line: usize, node_id: u32 },
+    /// A node id was declared twice
+    DuplicateNode { line: usize, node_id: u32 },
+    /// A shortcut replaces an edge id which does not exist
+    UnknownShortcutEdge {
+        line: usize,
+        edge_id: u32,
+        edge_count: usize,
+    },
+    /// There is more data after all declared nodes and edges were parsed
+    TrailingData { line: usize },
+    /// A column required for the import is not part of the CSV header
+    MissingColumn { line: usize, column: String },
+    /// An arc of a DIMACS metric file differs from the arc in the same
+    /// position of the previously read metric files
+    MismatchingArc {
+        line: usize,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+}
+
+impl GraphParseError {
+    pub fn line(&self) -> usize {
+        match self {
+            GraphParseError::Io { line, .. }
+            | GraphParseError::UnexpectedEof { line, .. }
+            | GraphParseError::InvalidToken { line, .. }
+            | GraphParseError::WrongTokenCount { line, .. }
+            | GraphParseError::UnknownNode { line, .. }
+            | GraphParseError::DuplicateNode { line, .. }
+            | GraphParseError::UnknownShortcutEdge { line, .. }
+            | GraphParseError::TrailingData { line }
+            | GraphParseError::MissingColumn { line, .. }
+            | GraphParseError::MismatchingArc { line, .. } => *line,
+        }
+    }
+}
+
+impl Display for GraphParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line())?;
+        match self {
+            GraphParseError::Io { source, .. } => write!(f, "could not read line: {}", source),
+            GraphParseError::UnexpectedEof { expected, .. } => {
+                write!(f, "unexpected end of file, expected {}", expected)
+            }
+            GraphParseError::InvalidToken {
+                token, expected, ..
+            } => write!(f, "expected {}, found '{}'", expected, token),
+            GraphParseError::WrongTokenCount {
+                found, expected, ..
+            } => write!(f, "expected {}, found {} tokens", expected, found),
+            GraphParseError::UnknownNode { node_id, .. } => {
+                write!(f, "edge references unknown node {}", node_id)
+            }
+            GraphParseError::DuplicateNode { node_id, .. } => {
+                write!(f, "node {} is declared twice", node_id)
+            }
+            GraphParseError::UnknownShortcutEdge {
+                edge_id,
+                edge_count,
+                ..
+            } => write!(
+                f,
+                "shortcut replaces edge {} but the graph only has {} edges",
+                edge_id, edge_count
+            ),
+            GraphParseError::TrailingData { .. } => {
+                write!(f, "found data after all declared nodes and edges")
+            }
+            GraphParseError::MissingColumn { column, .. } => {
+                write!(f, "header has no column '{}'", column)
+            }
+            GraphParseError::MismatchingArc {
+                expected, found, ..
+            } => write!(
+                f,
+                "expected arc {} -> {} like in the previous files, found {} -> {}",
+                expected.0, expected.1, found.0, found.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphParseError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a graph file line by line, skipping comments and blank lines, while
+/// keeping track of the current line number for error reporting.
+struct GraphLines<R> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> GraphLines<R> {
+    fn new(reader: R) -> Self {
+        GraphLines {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+
+    fn next_data_line(&mut self) -> Result<Option<String>, GraphParseError> {
+        loop {
+            let line = match self.lines.next() {
+                Some(line) => line,
+                None => return Ok(None),
+            };
+            self.line += 1;
+            let line = line.map_err(|source| GraphParseError::Io {
+                line: self.line,
+                source,
+            })?;
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                return Ok(Some(line));
+            }
+        }
+    }
+
+    fn expect_data_line(&mut self, expected: &str) -> Result<String, GraphParseError> {
+        self.next_data_line()?
+            .ok_or_else(|| GraphParseError::UnexpectedEof {
+                line: self.line + 1,
+                expected: expected.to_string(),
+            })
+    }
+
+    fn expect_value<T: FromStr>(&mut self, expected: &str) -> Result<T, GraphParseError> {
+        let line = self.expect_data_line(expected)?;
+        self.parse_token(line.trim(), expected)
+    }
+
+    fn parse_token<T: FromStr>(&self, token: &str, expected: &str) -> Result<T, GraphParseError> {
+        token.parse().map_err(|_| GraphParseError::InvalidToken {
+            line: self.line,
+            token: token.to_string(),
+            expected: expected.to_string(),
+        })
+    }
+
+    fn check_token_count(
+        &self,
+        tokens: &[&str],
+        expected: usize,
+        what: &str,
+    ) -> Result<(), GraphParseError> {
+        if tokens.len() != expected {
+            return Err(GraphParseError::WrongTokenCount {
+                line: self.line,
+                found: tokens.len(),
+                expected: format!("{} tokens for {}", expected, what),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Collects nodes and edges and checks the references between them while
+/// parsing.
+struct GraphBuilder {
+    num_of_nodes: usize,
+    num_of_edges: usize,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    node_ids: HashSet<u32>,
+}
+
+impl GraphBuilder {
+    fn new(num_of_nodes: usize, num_of_edges: usize) -> Self {
+        GraphBuilder {
+            num_of_nodes,
+            num_of_edges,
+            nodes: Vec::with_capacity(num_of_nodes),
+            edges: Vec::with_capacity(num_of_edges),
+            node_ids: HashSet::with_capacity(num_of_nodes),
+        }
+    }
+
+    fn nodes_complete(&self) -> bool {
+        self.nodes.len() >= self.num_of_nodes
+    }
+
+    fn edges_complete(&self) -> bool {
+        self.edges.len() >= self.num_of_edges
+    }
+
+    fn add_node(&mut self, line: usize, node: Node) -> Result<(), GraphParseError> {
+        if !self.node_ids.insert(node.id) {
+            return Err(GraphParseError::DuplicateNode {
+                line,
+                node_id: node.id,
+            });
+        }
+        self.nodes.push(node);
+        Ok(())
+    }
+
+    fn add_edge(&mut self, line: usize, edge: Edge) -> Result<(), GraphParseError> {
+        for &node_id in &[edge.source_id, edge.target_id] {
+            if !self.node_ids.contains(&node_id) {
+                return Err(GraphParseError::UnknownNode { line, node_id });
+            }
+        }
+        if let Some((edge1, edge2)) = edge.replaced_edges {
+            for &edge_id in &[edge1, edge2] {
+                if edge_id as usize >= self.num_of_edges {
+                    return Err(GraphParseError::UnknownShortcutEdge {
+                        line,
+                        edge_id,
+                        edge_count: self.num_of_edges,
+                    });
+                }
+            }
+        }
+        self.edges.push(edge);
+        Ok(())
+    }
+
+    fn finish(self, line: usize, metric_names: Vec<String>) -> Result<Graph, GraphParseError> {
+        if !self.nodes_complete() || !self.edges_complete() {
+            return Err(GraphParseError::UnexpectedEof {
+                line,
+                expected: format!(
+                    "{} nodes and {} edges, found {} nodes and {} edges",
+                    self.num_of_nodes,
+                    self.num_of_edges,
+                    self.nodes.len(),
+                    self.edges.len()
+                ),
+            });
+        }
+        Ok(Graph::new(self.nodes, self.edges, metric_names))
+    }
+}
+
+fn parse_replaced_edges<R: BufRead>(
+    lines: &GraphLines<R>,
+    first: &str,
+    second: &str,
+) -> Result<Option<(u32, u32)>, GraphParseError> {
+    if first == "-1" {
+        return Ok(None);
+    }
+    Ok(Some((
+        lines.parse_token(first, "replaced edge id")?,
+        lines.parse_token(second, "replaced edge id")?,
+    )))
+}
+
+fn parse_costs<R: BufRead>(
+    lines: &GraphLines<R>,
+    tokens: &[&str],
+) -> Result<Vec<f64>, GraphParseError> {
+    tokens
+        .iter()
+        .map(|t| lines.parse_token(t, "edge cost"))
+        .collect()
+}
+
+pub fn parse_graph_file(file_path: impl AsRef<std::path::Path>) -> anyhow::Result<Graph> {
+    println!("Parsing graph...");
+    let file = File::open(file_path)?;
+    Ok(parse_graph(BufReader::new(file))?)
+}
+
+/// Parses a graph in the legacy format, in which nodes carry additional
+/// information and edges have no explicit id.
+pub fn parse_graph(reader: impl BufRead) -> Result<Graph, GraphParseError> {
+    let mut lines = GraphLines::new(reader);
+
+    let cost_dim: usize = lines.expect_value("edge cost dimension")?;
+    let num_of_nodes = lines.expect_value("number of nodes")?;
+    let num_of_edges = lines.expect_value("number of edges")?;
+
+    let mut builder = GraphBuilder::new(num_of_nodes, num_of_edges);
+    while let Some(line) = lines.next_data_line()? {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if !builder.nodes_complete() {
+            lines.check_token_count(&tokens, 6, "a node")?;
+            // tokens[4] is the height, which we do not use
+            let node = Node::with_coordinates(
+                lines.parse_token(tokens[0], "node id")?,
+                lines.parse_token(tokens[5], "ch level")?,
+                lines.parse_token(tokens[2], "latitude")?,
+                lines.parse_token(tokens[3], "longitude")?,
+            );
+            builder.add_node(lines.line, node)?;
+        } else if !builder.edges_complete() {
+            lines.check_token_count(&tokens, 4 + cost_dim, "an edge")?;
+            let replaced_edges =
+                parse_replaced_edges(&lines, tokens[tokens.len() - 2], tokens[tokens.len() - 1])?;
+            let edge = Edge::new(
+                builder.edges.len() as u32,
+                lines.parse_token(tokens[0], "source node id")?,
+                lines.parse_token(tokens[1], "target node id")?,
+                parse_costs(&lines, &tokens[2..tokens.len() - 2])?,
+                replaced_edges,
+            );
+            builder.add_edge(lines.line, edge)?;
+        } else {
+            return Err(GraphParseError::TrailingData { line: lines.line });
+        }
+    }
+    builder.finish(lines.line + 1, default_metric_names(cost_dim))
+}
+
+pub fn parse_minimal_graph_file(file_path: impl AsRef<std::path::Path>) -> anyhow::Result<Graph> {
+    let start = Instant::now();
+
+    println!("Parsing graph...");
+    let file = File::open(file_path)?;
+    let graph = parse_minimal_graph(BufReader::new(file))?;
+    let time = start.elapsed();
+    println!("graph loading time: {}s", time.as_secs_f64());
+    Ok(graph)
+}
+
+/// Parses a graph in the minimal format described in the README.
+pub fn parse_minimal_graph(reader: impl BufRead) -> Result<Graph, GraphParseError> {
+    let mut lines = GraphLines::new(reader);
+
+    let cost_dim: usize = lines.expect_value("edge cost dimension")?;
+
+    let metric_name_line = lines.expect_data_line("metric names")?;
+    let metric_names: Vec<&str> = metric_name_line.split_whitespace().collect();
+    lines.check_token_count(&metric_names, cost_dim, "the metric names")?;
+    let metric_names = metric_names.into_iter().map(String::from).collect();
+
+    let num_of_nodes = lines.expect_value("number of nodes")?;
+    let num_of_edges = lines.expect_value("number of edges")?;
+
+    let mut builder = GraphBuilder::new(num_of_nodes, num_of_edges);
+    while let Some(line) = lines.next_data_line()? {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if !builder.nodes_complete() {
+            if tokens.len() != 2 && tokens.len() != 4 {
+                return Err(GraphParseError::WrongTokenCount {
+                    line: lines.line,
+                    found: tokens.len(),
+                    expected: "2 or 4 tokens for a node".to_string(),
+                });
+            }
+            let id = lines.parse_token(tokens[0], "node id")?;
+            let ch_level = tokens[1].parse().unwrap_or(0);
+            let node = if tokens.len() == 4 {
+                Node::with_coordinates(
+                    id,
+                    ch_level,
+                    lines.parse_token(tokens[2], "latitude")?,
+                    lines.parse_token(tokens[3], "longitude")?,
+                )
+            } else {
+                Node::new(id, ch_level)
+            };
+            builder.add_node(lines.line, node)?;
+        } else if !builder.edges_complete() {
+            lines.check_token_count(&tokens, 5 + cost_dim, "an edge")?;
+            let replaced_edges =
+                parse_replaced_edges(&lines, tokens[tokens.len() - 2], tokens[tokens.len() - 1])?;
+            let edge = Edge::new(
+                builder.edges.len() as u32,
+                lines.parse_token(tokens[1], "source node id")?,
+                lines.parse_token(tokens[2], "target node id")?,
+                parse_costs(&lines, &tokens[3..tokens.len() - 2])?,
+                replaced_edges,
+            );
+            builder.add_edge(lines.line, edge)?;
+        } else {
+            return Err(GraphParseError::TrailingData { line: lines.line });
+        }
+    }
+    builder.finish(lines.line + 1, metric_names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Coordinates;
+
+    fn minimal(content: &str) -> Result<Graph, GraphParseError> {
+        parse_minimal_graph(content.as_bytes())
+    }
+
+    #[test]
+    fn test_minimal_graph_without_blank_line() {
+        let graph = minimal("# comment\n2\na b\n2\n1\n0 0\n1 1\n0 0 1 3 4 -1 -1\n").unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.dim, 2);
+    }
+
+    #[test]
+    fn test_parse_errors_carry_line() {
+        let err = minimal("# comment\n\n2\na b\n2\n1\n0 0\n1 1\n0 0 1 3 4 -1\n").unwrap_err();
+        match err {
+            GraphParseError::WrongTokenCount { line, found, .. } => {
+                assert_eq!(line, 9);
+                assert_eq!(found, 6);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let err = minimal("2\na b\n2\n1\n0 0\n1 1\n0 0 1 3 y -1 -1\n").unwrap_err();
+        match err {
+            GraphParseError::InvalidToken { line, token, .. } => {
+                assert_eq!(line, 7);
+                assert_eq!(token, "y");
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let err = minimal("2\na b c\n2\n1\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::WrongTokenCount { line: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_inconsistent_graphs_are_rejected() {
+        let err = minimal("2\na b\n2\n1\n0 0\n1 1\n0 0 2 3 4 -1 -1\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::UnknownNode { node_id: 2, .. }
+        ));
+
+        let err = minimal("2\na b\n2\n1\n0 0\n1 1\n0 0 1 3 4 0 1\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::UnknownShortcutEdge { edge_id: 1, .. }
+        ));
+
+        let err = minimal("2\na b\n2\n2\n0 0\n1 1\n0 0 1 3 4 -1 -1\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::UnexpectedEof { line: 8, .. }
+        ));
+
+        let err =
+            minimal("2\na b\n2\n1\n0 0\n1 1\n0 0 1 3 4 -1 -1\n1 1 0 3 4 -1 -1\n").unwrap_err();
+        assert!(matches!(err, GraphParseError::TrailingData { line: 8 }));
+
+        let err = minimal("2\na b\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::UnexpectedEof { line: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn test_node_coordinates() {
+        let graph = minimal("2\na b\n2\n1\n0 0 48.7 9.1\n1 1 48.8 9.2\n0 0 1 3 4 -1 -1\n").unwrap();
+        assert!(graph.has_coordinates());
+        let c = graph.coordinates(1).unwrap();
+        assert!(crate::float_eq!(c.lat, 48.8) && crate::float_eq!(c.lon, 9.2));
+
+        let graph = minimal("2\na b\n2\n1\n0 0\n1 1\n0 0 1 3 4 -1 -1\n").unwrap();
+        assert!(!graph.has_coordinates());
+        assert_eq!(graph.coordinates(0), None);
+
+        let err = minimal("2\na b\n2\n1\n0 0 48.7\n1 1\n0 0 1 3 4 -1 -1\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::WrongTokenCount {
+                line: 5,
+                found: 3,
+                ..
+            }
+        ));
+
+        let err = minimal("2\na b\n2\n1\n0\n1 1\n0 0 1 3 4 -1 -1\n").unwrap_err();
+        assert!(matches!(
+            err,
+            GraphParseError::WrongTokenCount {
+                line: 5,
+                found: 1,
+                ..
+            }
+        ));
+
+        // an unparsable ch level defaults to 0
+        let graph = minimal("2\na b\n2\n1\n0 0\n1 x\n0 0 1 3 4 -1 -1\n").unwrap();
+        assert_eq!(graph.nodes[1u32].ch_level, 0);
+    }
+
+    #[test]
+    fn test_legacy_header_with_arbitrary_comments() {
+        let graph = parse_graph(
+            "# one\n# two\n2\n2\n1\n0 0 48.7 9.1 0 1\n1 0 48.8 9.2 0 2\n0 1 3 4 -1 -1\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(
+            graph.coordinates(1),
+            Some(Coordinates {
+                lat: 48.8,
+                lon: 9.2
+            })
+        );
+    }
+}
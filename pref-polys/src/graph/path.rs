@@ -1,4 +1,9 @@
-use std::convert::TryInto;
+use std::{
+    convert::TryInto,
+    fmt::{self, Display, Formatter},
+};
+
+use anyhow::{bail, Result};
 
 use rand::{
     distributions::Uniform,
@@ -16,6 +21,89 @@ pub struct Path {
     pub total_dimension_costs: MyVec<f64>,
 }
 
+/// A way in which a path disagrees with the graph it is supposed to run on.
+#[derive(Debug, PartialEq)]
+pub enum PathIssue {
+    /// The edge at `position` does not exist in the graph
+    UnknownEdge { position: usize, edge_id: u32 },
+    /// The edge at `position` does not start where the previous one ends
+    DisconnectedEdges { position: usize },
+    /// `nodes` differs from the end points of the edges first at `position`.
+    /// `None` means that one of the lists is shorter.
+    NodeMismatch {
+        position: usize,
+        expected: Option<u32>,
+        found: Option<u32>,
+    },
+    /// `total_dimension_costs` has the wrong number of metrics
+    DimensionMismatch { expected: usize, found: usize },
+    /// `total_dimension_costs` is not the sum of the edge costs
+    CostMismatch {
+        dimension: usize,
+        expected: f64,
+        found: f64,
+    },
+}
+
+impl PathIssue {
+    /// Whether recomputing nodes and costs from the edges fixes the issue
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self,
+            PathIssue::UnknownEdge { .. } | PathIssue::DisconnectedEdges { .. }
+        )
+    }
+}
+
+impl Display for PathIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PathIssue::UnknownEdge { position, edge_id } => {
+                write!(
+                    f,
+                    "edge {} at position {} does not exist",
+                    edge_id, position
+                )
+            }
+            PathIssue::DisconnectedEdges { position } => write!(
+                f,
+                "edges at positions {} and {} are not connected",
+                position - 1,
+                position
+            ),
+            PathIssue::NodeMismatch {
+                position,
+                expected,
+                found,
+            } => {
+                let show =
+                    |n: &Option<u32>| n.map_or_else(|| "nothing".to_string(), |n| n.to_string());
+                write!(
+                    f,
+                    "node at position {} should be {}, but is {}",
+                    position,
+                    show(expected),
+                    show(found)
+                )
+            }
+            PathIssue::DimensionMismatch { expected, found } => write!(
+                f,
+                "path has costs in {} dimensions, but the graph has {}",
+                found, expected
+            ),
+            PathIssue::CostMismatch {
+                dimension,
+                expected,
+                found,
+            } => write!(
+                f,
+                "path has cost {} in dimension {}, but its edges sum up to {}",
+                found, dimension, expected
+            ),
+        }
+    }
+}
+
 pub fn add_edge_costs(a: &mut [f64], b: &[f64]) {
     a.iter_mut().zip(b).for_each(|(a, b)| *a += b)
 }
@@ -77,6 +165,180 @@ impl Path {
             total_dimension_costs,
         }
     }
+
+    /// Checks that the edges exist and are connected, and that `nodes` and
+    /// `total_dimension_costs` match the edges.
+    pub fn validate(&self, graph: &Graph) -> Vec<PathIssue> {
+        let mut issues = Vec::new();
+        for (position, &edge_id) in self.edges.iter().enumerate() {
+            if edge_id as usize >= graph.edges.len() {
+                issues.push(PathIssue::UnknownEdge { position, edge_id });
+            } else if position > 0 {
+                let previous = self.edges[position - 1];
+                if (previous as usize) < graph.edges.len()
+                    && graph.edges[previous].target_id != graph.edges[edge_id].source_id
+                {
+                    issues.push(PathIssue::DisconnectedEdges { position });
+                }
+            }
+        }
+        if !issues.is_empty() {
+            // nodes and costs can not be checked against broken edges
+            return issues;
+        }
+
+        let expected = self.nodes_from_edges(graph);
+        let mismatch = (0..expected.len().max(self.nodes.len()))
+            .find(|&i| expected.get(i) != self.nodes.get(i));
+        if let Some(position) = mismatch {
+            issues.push(PathIssue::NodeMismatch {
+                position,
+                expected: expected.get(position).copied(),
+                found: self.nodes.get(position).copied(),
+            });
+        }
+
+        let dim = graph.dim as usize;
+        if self.total_dimension_costs.len() != dim {
+            issues.push(PathIssue::DimensionMismatch {
+                expected: dim,
+                found: self.total_dimension_costs.len(),
+            });
+            return issues;
+        }
+        let costs = self.get_subpath_costs(graph, 0, self.edges.len() as u32);
+        for (dimension, (&expected, &found)) in costs
+            .iter()
+            .zip(self.total_dimension_costs.iter())
+            .enumerate()
+        {
+            // long paths sum up rounding errors, so the tolerance grows with
+            // the costs
+            if !approx::relative_eq!(
+                expected,
+                found,
+                epsilon = crate::ACCURACY,
+                max_relative = crate::ACCURACY
+            ) {
+                issues.push(PathIssue::CostMismatch {
+                    dimension,
+                    expected,
+                    found,
+                });
+            }
+        }
+        issues
+    }
+
+    /// Validates the path and recomputes `nodes` and `total_dimension_costs`
+    /// from the edges if they do not match. Returns the issues that were
+    /// fixed, or an error if the edges themselves are broken.
+    pub fn repair(&mut self, graph: &Graph) -> Result<Vec<PathIssue>> {
+        let issues = self.validate(graph);
+        if let Some(issue) = issues.iter().find(|i| !i.is_repairable()) {
+            bail!("path can not be repaired: {}", issue);
+        }
+        if !issues.is_empty() {
+            self.nodes = self.nodes_from_edges(graph);
+            self.total_dimension_costs = self.get_subpath_costs(graph, 0, self.edges.len() as u32);
+        }
+        Ok(issues)
+    }
+
+    /// The nodes along the edges. A path without edges keeps its first node.
+    fn nodes_from_edges(&self, graph: &Graph) -> MyVec<u32> {
+        match self.edges.first() {
+            Some(&first) => std::iter::once(graph.edges[first].source_id)
+                .chain(self.edges.iter().map(|&e| graph.edges[e].target_id))
+                .collect::<Vec<_>>()
+                .into(),
+            None => MyVec(self.nodes.iter().copied().take(1).collect()),
+        }
+    }
+}
+
+/// Validates paths that were loaded from a file. With `repair` broken nodes
+/// and costs are recomputed from the edges, otherwise every issue is an
+/// error. Fails if a path can not be used with the graph.
+pub fn check_loaded_paths(graph: &Graph, paths: &mut [Path], repair: bool) -> Result<()> {
+    let mut repaired = 0;
+    for (i, path) in paths.iter_mut().enumerate() {
+        if repair {
+            match path.repair(graph) {
+                Ok(issues) if !issues.is_empty() => {
+                    println!("repaired path {}: {}", i, issues[0]);
+                    repaired += 1;
+                }
+                Ok(_) => (),
+                Err(e) => bail!("path {}: {}", i, e),
+            }
+        } else if let Some(issue) = path.validate(graph).first() {
+            bail!(
+                "path {} does not match the graph: {} (nodes and costs can be recomputed from the edges with --repair-paths)",
+                i,
+                issue
+            );
+        }
+    }
+    if repaired > 0 {
+        println!("repaired {} of {} paths", repaired, paths.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{dijkstra::Dijkstra, parse_graph_file};
+    use crate::utils::equal_weights;
+
+    #[test]
+    fn test_validate_and_repair() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let alpha = equal_weights(graph.dim as usize);
+        let n = graph.nodes.len() as u32;
+        let path = (0..n)
+            .flat_map(|s| (0..n).map(move |t| (s, t)))
+            .filter_map(|(s, t)| {
+                crate::graph::dijkstra::find_shortest_path(&mut dijkstra, &[s, t], &alpha)
+            })
+            .find(|p| p.edges.len() > 2)
+            .expect("there should be a path with several edges");
+        assert!(path.edges.len() > 1);
+        assert!(path.validate(&graph).is_empty());
+
+        let mut broken = path.clone();
+        broken.total_dimension_costs[0u32] += 1.0;
+        broken.nodes.0.pop();
+        let issues = broken.validate(&graph);
+        assert_eq!(issues.len(), 2);
+        assert!(matches!(
+            issues[0],
+            PathIssue::NodeMismatch { found: None, .. }
+        ));
+        assert!(matches!(
+            issues[1],
+            PathIssue::CostMismatch { dimension: 0, .. }
+        ));
+        assert_eq!(broken.repair(&graph).unwrap(), issues);
+        assert_eq!(broken.nodes.0, path.nodes.0);
+        assert!(broken.validate(&graph).is_empty());
+
+        let mut broken = path.clone();
+        broken.edges.0.swap(0, 1);
+        assert_eq!(
+            broken.validate(&graph)[0],
+            PathIssue::DisconnectedEdges { position: 1 }
+        );
+        assert!(broken.repair(&graph).is_err());
+
+        let mut broken = path;
+        broken.edges.push(1000);
+        let mut paths = vec![broken];
+        assert!(check_loaded_paths(&graph, &mut paths, true).is_err());
+        assert!(check_loaded_paths(&graph, &mut paths, false).is_err());
+    }
 }
 
 #[test]
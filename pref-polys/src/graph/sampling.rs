@@ -0,0 +1,149 @@
+use rand::{
+    distributions::{Distribution, Uniform},
+    RngCore,
+};
+
+use anyhow::{bail, Context, Result};
+
+use super::{dijkstra::Dijkstra, Graph};
+
+/// Number of pairs drawn before `PairSampler::sample` gives up on finding one
+/// within the distance band.
+const MAX_TRIES: usize = 10_000;
+
+/// Restricts sampled pairs to those whose shortest path in `metric` has a
+/// length between `min` and `max`.
+#[derive(Debug, Clone, Copy)]
+pub struct DistanceBand {
+    pub metric: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DistanceBand {
+    /// Builds a band from optional command line values. The metric defaults
+    /// to the first one. Returns `None` if neither bound is given.
+    pub fn from_options(
+        graph: &Graph,
+        metric: Option<&str>,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<Option<DistanceBand>> {
+        if min.is_none() && max.is_none() {
+            return Ok(None);
+        }
+        let metric = match metric {
+            Some(name) => graph
+                .metric_index(name)
+                .with_context(|| format!("The graph has no metric '{}'", name))?,
+            None => 0,
+        };
+        let band = DistanceBand {
+            metric,
+            min: min.unwrap_or(0.0),
+            max: max.unwrap_or(f64::INFINITY),
+        };
+        if band.min > band.max {
+            bail!("Empty distance band [{}, {}]", band.min, band.max);
+        }
+        Ok(Some(band))
+    }
+}
+
+/// Draws source/target pairs from the largest strongly connected component,
+/// so that a path between them always exists.
+pub struct PairSampler {
+    nodes: Vec<u32>,
+    band: Option<DistanceBand>,
+}
+
+impl PairSampler {
+    pub fn new(graph: &Graph) -> Self {
+        let nodes = graph.largest_strongly_connected_component();
+        println!(
+            "Sampling from largest strongly connected component with {} of {} nodes",
+            nodes.len(),
+            graph.nodes.len()
+        );
+        PairSampler { nodes, band: None }
+    }
+
+    pub fn with_distance_band(mut self, band: DistanceBand) -> Self {
+        self.band = Some(band);
+        self
+    }
+
+    /// Returns two different nodes of the component, or `None` if no pair
+    /// within the distance band was found.
+    pub fn sample(&self, dijkstra: &mut Dijkstra, rng: &mut dyn RngCore) -> Option<(u32, u32)> {
+        if self.nodes.len() < 2 {
+            return None;
+        }
+        let nodes_dist = Uniform::from(0..self.nodes.len());
+        for _ in 0..MAX_TRIES {
+            let s = self.nodes[nodes_dist.sample(rng)];
+            let t = self.nodes[nodes_dist.sample(rng)];
+            if s == t {
+                continue;
+            }
+            let band = match self.band {
+                Some(band) => band,
+                None => return Some((s, t)),
+            };
+            let mut alpha = vec![0.0; dijkstra.graph.dim as usize];
+            alpha[band.metric] = 1.0;
+            if let Some(result) = dijkstra.run(s, t, &alpha) {
+                if band.min <= result.total_cost && result.total_cost <= band.max {
+                    return Some((s, t));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::graph::parse_graph_file;
+
+    #[test]
+    fn test_pairs_are_connected_and_within_band() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let components = graph.strongly_connected_components();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let sampler = PairSampler::new(&graph);
+        for _ in 0..20 {
+            let (s, t) = sampler.sample(&mut dijkstra, &mut rng).unwrap();
+            assert_ne!(s, t);
+            assert_eq!(components[s], components[t]);
+        }
+
+        let band = DistanceBand {
+            metric: 0,
+            min: 3.0,
+            max: 5.0,
+        };
+        let sampler = PairSampler::new(&graph).with_distance_band(band);
+        for _ in 0..20 {
+            let (s, t) = sampler.sample(&mut dijkstra, &mut rng).unwrap();
+            let cost = dijkstra
+                .run(s, t, &[1.0, 0.0, 0.0, 0.0])
+                .unwrap()
+                .total_cost;
+            assert!((3.0..=5.0).contains(&cost));
+        }
+
+        let band = DistanceBand {
+            metric: 0,
+            min: 1000.0,
+            max: f64::INFINITY,
+        };
+        let sampler = PairSampler::new(&graph).with_distance_band(band);
+        assert_eq!(sampler.sample(&mut dijkstra, &mut rng), None);
+    }
+}
@@ -0,0 +1,116 @@
+use super::{edge::EdgeDirection, Graph};
+use crate::utils::MyVec;
+
+const UNVISITED: u32 = u32::MAX;
+
+impl Graph {
+    /// Computes the strongly connected components with Tarjan's algorithm.
+    /// Returns the component id of every node, components are numbered in
+    /// reverse topological order.
+    pub fn strongly_connected_components(&self) -> MyVec<u32> {
+        let node_count = self.nodes.len();
+        let mut index = MyVec(vec![UNVISITED; node_count]);
+        let mut low_link = MyVec(vec![UNVISITED; node_count]);
+        let mut on_stack = MyVec(vec![false; node_count]);
+        let mut stack = Vec::new();
+        let mut components = MyVec(vec![UNVISITED; node_count]);
+        let mut next_index = 0;
+        let mut next_component = 0;
+
+        // explicit call stack, as the recursion gets too deep on road networks
+        let mut call_stack = Vec::new();
+        for root in 0..node_count as u32 {
+            if index[root] != UNVISITED {
+                continue;
+            }
+            index[root] = next_index;
+            low_link[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            call_stack.push((root, self.edges_of(root, EdgeDirection::Out)));
+
+            while let Some((node, edges)) = call_stack.last_mut() {
+                let node = *node;
+                match edges.next().map(|e| e.target_id) {
+                    Some(next) if index[next] == UNVISITED => {
+                        index[next] = next_index;
+                        low_link[next] = next_index;
+                        next_index += 1;
+                        stack.push(next);
+                        on_stack[next] = true;
+                        call_stack.push((next, self.edges_of(next, EdgeDirection::Out)));
+                    }
+                    Some(next) => {
+                        if on_stack[next] {
+                            low_link[node] = low_link[node].min(index[next]);
+                        }
+                    }
+                    None => {
+                        call_stack.pop();
+                        if let Some((parent, _)) = call_stack.last() {
+                            low_link[*parent] = low_link[*parent].min(low_link[node]);
+                        }
+                        if low_link[node] == index[node] {
+                            while let Some(member) = stack.pop() {
+                                on_stack[member] = false;
+                                components[member] = next_component;
+                                if member == node {
+                                    break;
+                                }
+                            }
+                            next_component += 1;
+                        }
+                    }
+                }
+            }
+        }
+        components
+    }
+
+    /// Returns the nodes of the largest strongly connected component in
+    /// increasing order.
+    pub fn largest_strongly_connected_component(&self) -> Vec<u32> {
+        let components = self.strongly_connected_components();
+        let component_count = components
+            .iter()
+            .map(|&c| c as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut sizes = vec![0usize; component_count];
+        for &c in components.iter() {
+            sizes[c as usize] += 1;
+        }
+        let largest = match (0..component_count).max_by_key(|&c| sizes[c]) {
+            Some(c) => c as u32,
+            None => return Vec::new(),
+        };
+        (0..self.nodes.len() as u32)
+            .filter(|&n| components[n] == largest)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::parse_minimal_graph;
+
+    #[test]
+    fn test_strongly_connected_components() {
+        // 0 <-> 1 -> 2 -> 3 -> 4 -> 2, 5 is isolated
+        let graph = parse_minimal_graph(
+            "1\na\n6\n6\n0 0\n1 0\n2 0\n3 0\n4 0\n5 0\n0 0 1 1 -1 -1\n1 1 0 1 -1 -1\n2 1 2 1 -1 -1\n3 2 3 1 -1 -1\n4 3 4 1 -1 -1\n5 4 2 1 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let components = graph.strongly_connected_components();
+        assert_eq!(components[0u32], components[1u32]);
+        assert_eq!(components[2u32], components[3u32]);
+        assert_eq!(components[2u32], components[4u32]);
+        assert_ne!(components[0u32], components[2u32]);
+        assert_ne!(components[5u32], components[0u32]);
+        assert_ne!(components[5u32], components[2u32]);
+
+        assert_eq!(graph.largest_strongly_connected_component(), vec![2, 3, 4]);
+    }
+}
@@ -0,0 +1,281 @@
+//! Extraction of small subgraphs, e.g. to turn a failing computation on a
+//! large graph into a reproducible test case.
+//!
+//! The node sets are selected with `nodes_around_paths`, `nodes_in_box` or
+//! `nodes_from_level` and passed to `induced_subgraph`. Nodes and edges are
+//! renumbered consecutively. A shortcut keeps its replaced edges if both of
+//! them are part of the subgraph, otherwise it becomes a normal edge with the
+//! same costs. Note that the hierarchy of a subgraph is only guaranteed to be
+//! complete for `nodes_from_level`, as the witnesses of other missing
+//! shortcuts might lie outside of the subgraph.
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Result};
+
+use super::{edge::EdgeDirection, path::Path, Coordinates, Edge, Graph, Node};
+use crate::utils::MyVec;
+
+pub struct Subgraph {
+    pub graph: Graph,
+    /// New id of every node of the original graph, if it was kept
+    pub node_map: MyVec<Option<u32>>,
+    /// New id of every edge of the original graph, if it was kept
+    pub edge_map: MyVec<Option<u32>>,
+}
+
+impl Subgraph {
+    /// Translates a path of the original graph into the subgraph. Returns
+    /// `None` if the path leaves the subgraph.
+    pub fn map_path(&self, path: &Path) -> Option<Path> {
+        let nodes = path
+            .nodes
+            .iter()
+            .map(|&n| self.node_map.get(n as usize).copied().flatten())
+            .collect::<Option<Vec<_>>>()?;
+        let edges = path
+            .edges
+            .iter()
+            .map(|&e| self.edge_map.get(e as usize).copied().flatten())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Path {
+            nodes: MyVec(nodes),
+            edges: MyVec(edges),
+            total_dimension_costs: path.total_dimension_costs.clone(),
+        })
+    }
+}
+
+impl Graph {
+    /// All nodes within `hops` edges of the given paths. Edges are followed in
+    /// both directions, shortcuts are ignored. Fails if a path contains a node
+    /// which is not part of the graph.
+    pub fn nodes_around_paths(&self, paths: &[Path], hops: usize) -> Result<Vec<u32>> {
+        let mut distance = MyVec(vec![None; self.nodes.len()]);
+        let mut queue = VecDeque::new();
+        for (i, path) in paths.iter().enumerate() {
+            if let Some(node) = path.nodes.iter().find(|&&n| n as usize >= self.nodes.len()) {
+                bail!("path {} contains unknown node {}", i, node);
+            }
+        }
+        for &node in paths.iter().flat_map(|p| p.nodes.iter()) {
+            if distance[node].is_none() {
+                distance[node] = Some(0);
+                queue.push_back(node);
+            }
+        }
+
+        while let Some(node) = queue.pop_front() {
+            let next_distance = distance[node].unwrap() + 1;
+            if next_distance > hops {
+                continue;
+            }
+            let neighbors = self
+                .edges_of(node, EdgeDirection::Out)
+                .chain(self.edges_of(node, EdgeDirection::In))
+                .filter(|e| self.edges[e.edge_id].replaced_edges.is_none())
+                .map(|e| e.target_id)
+                .collect::<Vec<_>>();
+            for neighbor in neighbors {
+                if distance[neighbor].is_none() {
+                    distance[neighbor] = Some(next_distance);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        Ok((0..self.nodes.len() as u32)
+            .filter(|&n| distance[n].is_some())
+            .collect())
+    }
+
+    /// All nodes whose coordinates lie within the box spanned by the two
+    /// corners. Nodes without coordinates are never part of it.
+    pub fn nodes_in_box(&self, corner1: Coordinates, corner2: Coordinates) -> Vec<u32> {
+        let (min_lat, max_lat) = (corner1.lat.min(corner2.lat), corner1.lat.max(corner2.lat));
+        let (min_lon, max_lon) = (corner1.lon.min(corner2.lon), corner1.lon.max(corner2.lon));
+        self.nodes
+            .iter()
+            .filter(|n| match n.coordinates {
+                Some(c) => {
+                    min_lat <= c.lat && c.lat <= max_lat && min_lon <= c.lon && c.lon <= max_lon
+                }
+                None => false,
+            })
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// All nodes with a ch level of at least `min_level`. Together with the
+    /// shortcuts between them they form a valid hierarchy on their own.
+    pub fn nodes_from_level(&self, min_level: u32) -> Vec<u32> {
+        self.nodes
+            .iter()
+            .filter(|n| n.ch_level >= min_level)
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Builds the subgraph consisting of `nodes` and all edges between them.
+    pub fn induced_subgraph(&self, nodes: &[u32]) -> Subgraph {
+        let mut node_map = MyVec(vec![None; self.nodes.len()]);
+        for &n in nodes {
+            node_map[n] = Some(0);
+        }
+        // Nodes are sorted by level, so numbering them in order keeps their
+        // ids stable in `Graph::new`
+        let mut new_nodes = Vec::new();
+        for n in self.nodes.iter() {
+            if node_map[n.id].is_some() {
+                let id = new_nodes.len() as u32;
+                node_map[n.id] = Some(id);
+                new_nodes.push(Node {
+                    id,
+                    ch_level: n.ch_level,
+                    coordinates: n.coordinates,
+                });
+            }
+        }
+
+        let mut edge_map = MyVec(vec![None; self.edges.len()]);
+        let mut kept_edges = Vec::new();
+        for e in self.edges.iter() {
+            if let (Some(source), Some(target)) = (node_map[e.source_id], node_map[e.target_id]) {
+                edge_map[e.id] = Some(kept_edges.len() as u32);
+                kept_edges.push((e, source, target));
+            }
+        }
+
+        let new_edges = kept_edges
+            .into_iter()
+            .enumerate()
+            .map(|(id, (e, source, target))| {
+                let replaced_edges = match e.replaced_edges {
+                    Some((edge1, edge2)) => match (edge_map[edge1], edge_map[edge2]) {
+                        (Some(edge1), Some(edge2)) => Some((edge1, edge2)),
+                        _ => None,
+                    },
+                    None => None,
+                };
+                Edge::new(
+                    id as u32,
+                    source,
+                    target,
+                    e.edge_costs.clone(),
+                    replaced_edges,
+                )
+            })
+            .collect();
+
+        Subgraph {
+            graph: Graph::new(new_nodes, new_edges, self.metric_names.0.clone()),
+            node_map,
+            edge_map,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float_eq;
+    use crate::graph::{
+        dijkstra::{find_shortest_path, Dijkstra},
+        parse_graph_file, parse_minimal_graph,
+    };
+    use crate::utils::{equal_weights, same_array};
+
+    #[test]
+    fn test_top_levels_keep_distances() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let max_level = graph.nodes.iter().map(|n| n.ch_level).max().unwrap();
+        let top_nodes = graph.nodes_from_level(max_level / 2);
+        let subgraph = graph.induced_subgraph(&top_nodes);
+        assert_eq!(subgraph.graph.nodes.len(), top_nodes.len());
+        assert!(subgraph.graph.validate().is_valid());
+
+        let alpha = equal_weights(graph.dim as usize);
+        let mut dijkstra = Dijkstra::new(&graph);
+        let mut sub_dijkstra = Dijkstra::new(&subgraph.graph);
+        for &s in &top_nodes {
+            for &t in &top_nodes {
+                let cost = dijkstra.run(s, t, &alpha).map(|r| r.total_cost);
+                let sub_cost = sub_dijkstra
+                    .run(
+                        subgraph.node_map[s].unwrap(),
+                        subgraph.node_map[t].unwrap(),
+                        &alpha,
+                    )
+                    .map(|r| r.total_cost);
+                assert!(float_eq!(cost.unwrap_or(-1.), sub_cost.unwrap_or(-1.)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_paths_are_mapped_into_subgraph() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let alpha = equal_weights(graph.dim as usize);
+        let n = graph.nodes.len() as u32;
+        let path = (0..n)
+            .flat_map(|s| (0..n).map(move |t| (s, t)))
+            .filter_map(|(s, t)| find_shortest_path(&mut dijkstra, &[s, t], &alpha))
+            .find(|p| p.edges.len() >= 3)
+            .unwrap();
+
+        let subgraph = graph.induced_subgraph(
+            &graph
+                .nodes_around_paths(std::slice::from_ref(&path), 0)
+                .unwrap(),
+        );
+        assert_eq!(subgraph.graph.nodes.len(), path.nodes.len());
+        assert!(subgraph.graph.validate().is_valid());
+
+        let mapped = subgraph.map_path(&path).unwrap();
+        for (&e, &mapped_e) in path.edges.iter().zip(mapped.edges.iter()) {
+            let edge = &graph.edges[e];
+            let mapped_edge = &subgraph.graph.edges[mapped_e];
+            assert_eq!(
+                subgraph.node_map[edge.source_id],
+                Some(mapped_edge.source_id)
+            );
+            assert_eq!(
+                subgraph.node_map[edge.target_id],
+                Some(mapped_edge.target_id)
+            );
+            assert!(same_array(&edge.edge_costs, &mapped_edge.edge_costs));
+        }
+
+        let larger = graph
+            .nodes_around_paths(std::slice::from_ref(&path), 1)
+            .unwrap();
+        assert!(larger.len() > subgraph.graph.nodes.len());
+
+        let mut unknown = path;
+        unknown.nodes.push(n);
+        assert!(graph.nodes_around_paths(&[unknown], 0).is_err());
+    }
+
+    #[test]
+    fn test_nodes_in_box() {
+        let graph = parse_minimal_graph(
+            "1\na\n3\n2\n0 0 48.7 9.1\n1 0 48.8 9.2\n2 0 49.5 9.3\n0 0 1 1 -1 -1\n1 1 2 1 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let corner1 = Coordinates {
+            lat: 49.0,
+            lon: 9.0,
+        };
+        let corner2 = Coordinates {
+            lat: 48.0,
+            lon: 9.5,
+        };
+        assert_eq!(graph.nodes_in_box(corner1, corner2), vec![0, 1]);
+
+        let subgraph = graph.induced_subgraph(&graph.nodes_in_box(corner1, corner2));
+        assert_eq!(subgraph.graph.edges.len(), 1);
+        assert_eq!(subgraph.edge_map.0, vec![Some(0), None]);
+    }
+}
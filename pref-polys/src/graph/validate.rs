@@ -0,0 +1,474 @@
+//! Consistency checks for graphs.
+//!
+//! Broken shortcuts usually only show up as a panic in `Graph::unpack_edge`
+//! or as wrong results somewhere in the middle of an experiment. `validate`
+//! checks a graph once up front and reports all problems it finds.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+use super::Graph;
+use crate::float_eq;
+use crate::utils::MyVec;
+
+#[derive(Debug, PartialEq)]
+pub enum GraphIssue {
+    /// A shortcut refers to an edge id that does not exist
+    UnknownReplacedEdge {
+        edge_id: u32,
+        replaced_edge: u32,
+    },
+    /// The replaced edges of a shortcut do not form a path from its source to
+    /// its target
+    DisconnectedShortcut {
+        edge_id: u32,
+        replaced_edges: (u32, u32),
+    },
+    /// Unpacking the shortcut leads back to the shortcut itself
+    CyclicShortcut {
+        edge_id: u32,
+    },
+    /// The costs of a shortcut are not the sum of its replaced edges
+    ShortcutCostMismatch {
+        edge_id: u32,
+        dimension: usize,
+        expected: f64,
+        found: f64,
+    },
+    /// The node skipped by a shortcut is not below both of its end nodes
+    InconsistentChLevel {
+        edge_id: u32,
+        skipped_node: u32,
+    },
+    NegativeCost {
+        edge_id: u32,
+        dimension: usize,
+        cost: f64,
+    },
+    NaNCost {
+        edge_id: u32,
+        dimension: usize,
+    },
+    /// Same source, target and costs as an edge with a lower id
+    DuplicateEdge {
+        edge_id: u32,
+        duplicate_of: u32,
+    },
+    /// Same source and target but different costs than an edge with a lower
+    /// id. This is expected for shortcuts of multi-criteria hierarchies.
+    ParallelEdge {
+        edge_id: u32,
+        parallel_to: u32,
+    },
+    /// The node is not part of the largest strongly connected component, so
+    /// it cannot be reached from it or cannot reach it
+    UnreachableNode {
+        node_id: u32,
+    },
+}
+
+impl GraphIssue {
+    /// Errors break queries or lead to wrong results, everything else is
+    /// only suspicious.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            GraphIssue::DuplicateEdge { .. }
+                | GraphIssue::ParallelEdge { .. }
+                | GraphIssue::UnreachableNode { .. }
+        )
+    }
+
+    /// Short name of the kind of issue, used for summaries
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GraphIssue::UnknownReplacedEdge { .. } => "unknown replaced edge",
+            GraphIssue::DisconnectedShortcut { .. } => "disconnected shortcut",
+            GraphIssue::CyclicShortcut { .. } => "cyclic shortcut",
+            GraphIssue::ShortcutCostMismatch { .. } => "shortcut cost mismatch",
+            GraphIssue::InconsistentChLevel { .. } => "inconsistent ch level",
+            GraphIssue::NegativeCost { .. } => "negative cost",
+            GraphIssue::NaNCost { .. } => "NaN cost",
+            GraphIssue::DuplicateEdge { .. } => "duplicate edge",
+            GraphIssue::ParallelEdge { .. } => "parallel edge",
+            GraphIssue::UnreachableNode { .. } => "unreachable node",
+        }
+    }
+}
+
+impl Display for GraphIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphIssue::UnknownReplacedEdge {
+                edge_id,
+                replaced_edge,
+            } => write!(
+                f,
+                "shortcut {} replaces unknown edge {}",
+                edge_id, replaced_edge
+            ),
+            GraphIssue::DisconnectedShortcut {
+                edge_id,
+                replaced_edges: (edge1, edge2),
+            } => write!(
+                f,
+                "replaced edges {} & {} of shortcut {} are not connected",
+                edge1, edge2, edge_id
+            ),
+            GraphIssue::CyclicShortcut { edge_id } => {
+                write!(f, "shortcut {} contains itself when unpacked", edge_id)
+            }
+            GraphIssue::ShortcutCostMismatch {
+                edge_id,
+                dimension,
+                expected,
+                found,
+            } => write!(
+                f,
+                "shortcut {} has cost {} in dimension {}, but its replaced edges sum up to {}",
+                edge_id, found, dimension, expected
+            ),
+            GraphIssue::InconsistentChLevel {
+                edge_id,
+                skipped_node,
+            } => write!(
+                f,
+                "shortcut {} skips node {} which is not below both of its end nodes",
+                edge_id, skipped_node
+            ),
+            GraphIssue::NegativeCost {
+                edge_id,
+                dimension,
+                cost,
+            } => write!(
+                f,
+                "edge {} has negative cost {} in dimension {}",
+                edge_id, cost, dimension
+            ),
+            GraphIssue::NaNCost { edge_id, dimension } => {
+                write!(
+                    f,
+                    "edge {} has cost NaN in dimension {}",
+                    edge_id, dimension
+                )
+            }
+            GraphIssue::DuplicateEdge {
+                edge_id,
+                duplicate_of,
+            } => write!(
+                f,
+                "edge {} is a duplicate of edge {}",
+                edge_id, duplicate_of
+            ),
+            GraphIssue::ParallelEdge {
+                edge_id,
+                parallel_to,
+            } => write!(f, "edge {} is parallel to edge {}", edge_id, parallel_to),
+            GraphIssue::UnreachableNode { node_id } => write!(
+                f,
+                "node {} is not strongly connected to the largest component",
+                node_id
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<GraphIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the graph is free of errors. Warnings are allowed.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &GraphIssue> {
+        self.issues.iter().filter(|i| i.is_error())
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &GraphIssue> {
+        self.issues.iter().filter(|i| !i.is_error())
+    }
+
+    /// Number of issues per kind, in the order they first occur
+    pub fn summary(&self) -> Vec<(&'static str, usize)> {
+        let mut summary: Vec<(&'static str, usize)> = Vec::new();
+        for issue in &self.issues {
+            match summary.iter_mut().find(|(kind, _)| *kind == issue.kind()) {
+                Some((_, count)) => *count += 1,
+                None => summary.push((issue.kind(), 1)),
+            }
+        }
+        summary
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    New,
+    InProgress,
+    /// In progress and already reported as cyclic
+    Cyclic,
+    Done,
+}
+
+impl Graph {
+    /// Checks the graph for broken shortcuts, invalid costs, duplicate or
+    /// parallel edges and nodes which are not connected to the rest of the
+    /// graph.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        self.check_costs(&mut report);
+        self.check_shortcuts(&mut report);
+        self.check_parallel_edges(&mut report);
+        self.check_connectivity(&mut report);
+        report
+    }
+
+    fn check_costs(&self, report: &mut ValidationReport) {
+        for e in self.edges.iter() {
+            for (dimension, &cost) in e.edge_costs.iter().enumerate() {
+                if cost.is_nan() {
+                    report.issues.push(GraphIssue::NaNCost {
+                        edge_id: e.id,
+                        dimension,
+                    });
+                } else if cost < 0.0 {
+                    report.issues.push(GraphIssue::NegativeCost {
+                        edge_id: e.id,
+                        dimension,
+                        cost,
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_shortcuts(&self, report: &mut ValidationReport) {
+        let mut states = MyVec(vec![VisitState::New; self.edges.len()]);
+        for e in self.edges.iter() {
+            self.check_shortcut(e.id, &mut states, report);
+        }
+    }
+
+    /// Checks a shortcut and, before that, its replaced edges. Returns false if
+    /// the edge can not be unpacked.
+    fn check_shortcut(
+        &self,
+        edge_id: u32,
+        states: &mut MyVec<VisitState>,
+        report: &mut ValidationReport,
+    ) -> bool {
+        match states[edge_id] {
+            VisitState::Done => return true,
+            VisitState::InProgress => {
+                report.issues.push(GraphIssue::CyclicShortcut { edge_id });
+                states[edge_id] = VisitState::Cyclic;
+                return false;
+            }
+            VisitState::Cyclic => return false,
+            VisitState::New => (),
+        }
+
+        let edge = &self.edges[edge_id];
+        let (edge1, edge2) = match edge.replaced_edges {
+            Some(replaced) => replaced,
+            None => {
+                states[edge_id] = VisitState::Done;
+                return true;
+            }
+        };
+
+        let mut valid = true;
+        for &replaced_edge in &[edge1, edge2] {
+            if replaced_edge as usize >= self.edges.len() {
+                report.issues.push(GraphIssue::UnknownReplacedEdge {
+                    edge_id,
+                    replaced_edge,
+                });
+                valid = false;
+            }
+        }
+        if !valid {
+            states[edge_id] = VisitState::Done;
+            return false;
+        }
+
+        states[edge_id] = VisitState::InProgress;
+        valid = self.check_shortcut(edge1, states, report);
+        valid &= self.check_shortcut(edge2, states, report);
+        states[edge_id] = VisitState::Done;
+
+        let first = &self.edges[edge1];
+        let second = &self.edges[edge2];
+        if first.source_id != edge.source_id
+            || first.target_id != second.source_id
+            || second.target_id != edge.target_id
+        {
+            report.issues.push(GraphIssue::DisconnectedShortcut {
+                edge_id,
+                replaced_edges: (edge1, edge2),
+            });
+            return false;
+        }
+
+        for (dimension, &found) in edge.edge_costs.iter().enumerate() {
+            let expected = first.edge_costs[dimension] + second.edge_costs[dimension];
+            if !float_eq!(expected, found) {
+                report.issues.push(GraphIssue::ShortcutCostMismatch {
+                    edge_id,
+                    dimension,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        let skipped_node = first.target_id;
+        let skipped_level = self.nodes[skipped_node].ch_level;
+        if skipped_level >= self.nodes[edge.source_id].ch_level
+            || skipped_level >= self.nodes[edge.target_id].ch_level
+        {
+            report.issues.push(GraphIssue::InconsistentChLevel {
+                edge_id,
+                skipped_node,
+            });
+        }
+
+        valid
+    }
+
+    fn check_parallel_edges(&self, report: &mut ValidationReport) {
+        let mut first_edges: HashMap<(u32, u32), Vec<u32>> = HashMap::new();
+        for e in self.edges.iter() {
+            let previous = first_edges.entry((e.source_id, e.target_id)).or_default();
+            if let Some(&duplicate_of) = previous
+                .iter()
+                .find(|&&p| self.edges[p].edge_costs == e.edge_costs)
+            {
+                report.issues.push(GraphIssue::DuplicateEdge {
+                    edge_id: e.id,
+                    duplicate_of,
+                });
+            } else {
+                if let Some(&parallel_to) = previous.first() {
+                    report.issues.push(GraphIssue::ParallelEdge {
+                        edge_id: e.id,
+                        parallel_to,
+                    });
+                }
+                previous.push(e.id);
+            }
+        }
+    }
+
+    fn check_connectivity(&self, report: &mut ValidationReport) {
+        let component = self.largest_strongly_connected_component();
+        let mut in_component = MyVec(vec![false; self.nodes.len()]);
+        for &node_id in &component {
+            in_component[node_id] = true;
+        }
+        for node_id in 0..self.nodes.len() as u32 {
+            if !in_component[node_id] {
+                report.issues.push(GraphIssue::UnreachableNode { node_id });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{contraction::contract_graph, parse_graph_file, parse_minimal_graph};
+
+    fn validate(content: &str) -> ValidationReport {
+        parse_minimal_graph(content.as_bytes()).unwrap().validate()
+    }
+
+    #[test]
+    fn test_valid_graphs() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let report = graph.validate();
+        assert!(report.is_valid(), "{:?}", report.issues);
+
+        let graph = parse_graph_file("./resources/testGraph").unwrap();
+        assert!(graph.validate().is_valid());
+        let contracted = contract_graph(&graph);
+        let report = contracted.graph.validate();
+        assert!(report.is_valid(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_broken_shortcuts() {
+        // nodes 0 -> 1 -> 2 -> 0, edge 2 is a shortcut with wrong costs
+        let report = validate(
+            "2\na b\n3\n4\n0 1\n1 0\n2 2\n0 0 1 1 2 -1 -1\n1 1 2 3 4 -1 -1\n2 0 2 4 5 0 1\n3 2 0 1 1 -1 -1\n",
+        );
+        assert_eq!(
+            report.issues,
+            vec![GraphIssue::ShortcutCostMismatch {
+                edge_id: 2,
+                dimension: 1,
+                expected: 6.0,
+                found: 5.0
+            }]
+        );
+
+        // the skipped node 1 has the highest level
+        let report = validate(
+            "2\na b\n3\n4\n0 1\n1 5\n2 2\n0 0 1 1 2 -1 -1\n1 1 2 3 4 -1 -1\n2 0 2 4 6 0 1\n3 2 0 1 1 -1 -1\n",
+        );
+        assert_eq!(
+            report.issues,
+            vec![GraphIssue::InconsistentChLevel {
+                edge_id: 2,
+                skipped_node: 2
+            }]
+        );
+
+        // replaced edges in the wrong order
+        let report = validate(
+            "2\na b\n3\n4\n0 1\n1 0\n2 2\n0 0 1 1 2 -1 -1\n1 1 2 3 4 -1 -1\n2 0 2 4 6 1 0\n3 2 0 1 1 -1 -1\n",
+        );
+        assert!(!report.is_valid());
+        assert!(matches!(
+            report.issues[0],
+            GraphIssue::DisconnectedShortcut { edge_id: 2, .. }
+        ));
+
+        // shortcut replacing itself
+        let report = validate("2\na b\n2\n1\n0 0\n1 1\n0 0 1 1 2 0 0\n");
+        let cyclic = report
+            .issues
+            .iter()
+            .filter(|i| **i == GraphIssue::CyclicShortcut { edge_id: 0 })
+            .count();
+        assert_eq!(cyclic, 1);
+    }
+
+    #[test]
+    fn test_costs_and_structure() {
+        // node 3 can be reached from the cycle 0 -> 1 -> 2 -> 0 but not reach it
+        let report = validate(
+            "2\na b\n4\n6\n0 0\n1 0\n2 0\n3 0\n0 0 1 -1 NaN -1 -1\n1 0 1 -1 NaN -1 -1\n2 0 1 1 1 -1 -1\n3 1 2 1 1 -1 -1\n4 2 0 1 1 -1 -1\n5 2 3 1 1 -1 -1\n",
+        );
+        assert_eq!(
+            report.summary(),
+            vec![
+                ("negative cost", 2),
+                ("NaN cost", 2),
+                ("parallel edge", 2),
+                ("unreachable node", 1)
+            ]
+        );
+        assert!(report
+            .issues
+            .contains(&GraphIssue::UnreachableNode { node_id: 3 }));
+        assert!(!report.is_valid());
+        assert_eq!(report.warnings().count(), 3);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+
+use super::Graph;
+
+impl Graph {
+    /// Writes the graph in the format read by `parse_minimal_graph_file`.
+    pub fn write_minimal_graph_file(&self, file_path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.write_minimal_graph(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the graph in the format read by `parse_minimal_graph`.
+    pub fn write_minimal_graph(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "{}", self.dim)?;
+        writeln!(w, "{}", self.metric_names.join(" "))?;
+        writeln!(w, "{}", self.nodes.len())?;
+        writeln!(w, "{}", self.edges.len())?;
+
+        for n in self.nodes.iter() {
+            match n.coordinates {
+                Some(c) => writeln!(w, "{} {} {} {}", n.id, n.ch_level, c.lat, c.lon)?,
+                None => writeln!(w, "{} {}", n.id, n.ch_level)?,
+            }
+        }
+
+        // the parser assigns edge ids by line, so the edges have to be in order
+        for (index, e) in self.edges.iter().enumerate() {
+            assert_eq!(e.id as usize, index, "edges are not sorted by id");
+            write!(w, "{} {} {}", e.id, e.source_id, e.target_id)?;
+            for c in &e.edge_costs {
+                write!(w, " {}", c)?;
+            }
+            match e.replaced_edges {
+                Some((edge1, edge2)) => writeln!(w, " {} {}", edge1, edge2)?,
+                None => writeln!(w, " -1 -1")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{parse_graph_file, parse_minimal_graph};
+
+    #[test]
+    fn test_write_minimal_graph_round_trip() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+
+        let mut buffer = Vec::new();
+        graph.write_minimal_graph(&mut buffer).unwrap();
+        let read = parse_minimal_graph(buffer.as_slice()).unwrap();
+
+        assert_eq!(graph.metric_names.0, read.metric_names.0);
+        assert_eq!(graph.nodes.len(), read.nodes.len());
+        for (a, b) in graph.nodes.iter().zip(read.nodes.iter()) {
+            assert_eq!(
+                (a.id, a.ch_level, a.coordinates),
+                (b.id, b.ch_level, b.coordinates)
+            );
+        }
+        assert_eq!(graph.edges.len(), read.edges.len());
+        for (a, b) in graph.edges.iter().zip(read.edges.iter()) {
+            assert_eq!(
+                (a.id, a.source_id, a.target_id, a.replaced_edges),
+                (b.id, b.source_id, b.target_id, b.replaced_edges)
+            );
+            assert_eq!(a.edge_costs, b.edge_costs);
+        }
+    }
+}
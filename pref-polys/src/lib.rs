@@ -2,6 +2,8 @@ pub mod geom;
 pub mod graph;
 pub mod lp;
 pub mod preference;
+pub mod segmentation;
+pub mod tolerance;
 pub mod utils;
 
 pub const ACCURACY: f64 = 0.000005;
@@ -0,0 +1,152 @@
+use anyhow::Result;
+
+use crate::{
+    utils::{number::Rational, Preference},
+    ACCURACY,
+};
+
+/// Solution of the preference LP in exact arithmetic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExactSolution {
+    pub preference: Vec<Rational>,
+    pub delta: Rational,
+}
+
+/// LP which finds a preference `alpha` maximizing `delta` subject to
+/// `alpha * c >= delta` for all added constraints `c`, with `alpha` being
+/// non-negative and summing up to 1.
+pub trait LpBackend: Sized {
+    fn new(dim: usize) -> Result<Self>;
+
+    /// Removes all constraints.
+    fn reset(&mut self) -> Result<()>;
+
+    /// Whether `solve_rational` is going to be called. Backends which solve
+    /// rationally with a separate LP only keep the constraints for it if
+    /// enabled. Also removes all constraints.
+    fn set_exact(&mut self, exact: bool) -> Result<()>;
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<()>;
+
+    /// Returns the preference and `delta`, or `None` if the LP could not be
+    /// solved. With `exact` the LP is solved in exact arithmetic, which is
+    /// much slower but helps when the floating point solver cycles.
+    fn solve(&mut self, exact: bool) -> Result<Option<(Preference, f64)>>;
+
+    /// Solves the LP with rationals. The constraints are taken as they are,
+    /// without setting small coefficients to zero, so the sign of `delta`
+    /// decides exactly whether a preference satisfies all of them. Fails if
+    /// exact mode is off, see `set_exact`.
+    fn solve_rational(&mut self) -> Result<Option<ExactSolution>>;
+}
+
+/// LP over preferences which are optimal for a path, used to approximate the
+/// size of its preference space. The last component of a preference is
+/// implied by the others, so the objective function and the non basic
+/// constraints have one dimension less than the preferences.
+pub trait SizeApproxBackend: Sized {
+    fn new(dim: usize) -> Result<Self>;
+
+    /// Removes all constraints.
+    fn reset(&mut self) -> Result<()>;
+
+    /// Sets the direction to maximize, `dim - 1` coefficients.
+    fn set_obj_fun(&mut self, coef: &[f64]) -> Result<()>;
+
+    /// Adds the constraint `alpha * costs >= 0` and returns its row.
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<i32>;
+
+    fn solve(&mut self, exact: bool) -> Result<Option<Preference>>;
+
+    /// The constraints which are tight at the last solution, in the lowered
+    /// dimension. The last coefficient is the bound of the constraint.
+    fn non_basic_constraints(&mut self) -> Result<Vec<Vec<f64>>>;
+}
+
+/// Sets coefficients of magnitude below `ACCURACY` to zero, they only lead
+/// to numerical trouble in the solvers.
+pub(super) fn normalize(costs: &[f64]) -> Vec<f64> {
+    costs
+        .iter()
+        .map(|&c| if c.abs() < ACCURACY { 0.0 } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::utils::{number::Number, same_array};
+
+    pub fn strange_lp_behavior<L: LpBackend>() {
+        let mut lp = L::new(4).unwrap();
+        lp.add_constraint(&[-0.0638948999999998, -1.106574, 1.11022302462516e-16, 0.0])
+            .unwrap();
+
+        let (_, delta) = lp.solve(false).unwrap().unwrap();
+        dbg!(delta);
+
+        lp.add_constraint(&[0.9163051, 1.258436, 0.8960761, -1.0])
+            .unwrap();
+
+        let (pref, delta) = lp.solve(false).unwrap().unwrap();
+        dbg!(delta);
+
+        assert!(!same_array(&pref, &[0.0, 0.0, 0.0, 1.0]));
+    }
+
+    pub fn rational_solution<L: LpBackend>() {
+        let mut lp = L::new(3).unwrap();
+        lp.add_constraint(&[1.0, 0.0, 0.0]).unwrap();
+        assert!(lp.solve_rational().is_err());
+
+        lp.set_exact(true).unwrap();
+        lp.add_constraint(&[1.0, 0.0, 0.0]).unwrap();
+        lp.add_constraint(&[0.0, 1.0, 0.0]).unwrap();
+        lp.add_constraint(&[0.0, 0.0, 1.0]).unwrap();
+
+        let third = Rational::from_i64(1) / Rational::from_i64(3);
+        let solution = lp.solve_rational().unwrap().unwrap();
+        assert_eq!(solution.preference, vec![third.clone(); 3]);
+        assert_eq!(solution.delta, third);
+
+        // small coefficients are not set to zero
+        lp.reset().unwrap();
+        lp.add_constraint(&[-1e-6, -1e-6, -1e-6]).unwrap();
+        let (_, delta) = lp.solve(false).unwrap().unwrap();
+        assert_eq!(delta, 0.0);
+        let solution = lp.solve_rational().unwrap().unwrap();
+        assert_eq!(solution.delta, Rational::from_f64(-1e-6));
+    }
+
+    pub fn getting_non_basic_constraints<L: SizeApproxBackend>() {
+        let mut lp = L::new(3).unwrap();
+
+        lp.set_obj_fun(&[-1.0, 1.0]).unwrap();
+
+        lp.add_constraint(&[2.0, -1.0, -2.0]).unwrap();
+        lp.add_constraint(&[-2.0, -2.0, 6.0]).unwrap();
+
+        let sol = lp.solve(false).unwrap().unwrap();
+
+        assert!(same_array(
+            dbg!(&sol.0[..2]),
+            &[0.416666666666, 0.3333333333]
+        ));
+
+        let non_basic = lp.non_basic_constraints().unwrap();
+        assert_eq!(non_basic.len(), 2);
+        assert!(same_array(dbg!(&non_basic[0]), &[4.0, 1.0, 2.0]));
+        assert!(same_array(dbg!(&non_basic[1]), &[-8.0, -8.0, -6.0]));
+
+        lp.set_obj_fun(&[-1.0, -1.0]).unwrap();
+        let sol = lp.solve(false).unwrap().unwrap();
+
+        assert!(same_array(dbg!(&sol.0[..2]), &[0.5, 0.0]));
+
+        let non_basic = lp.non_basic_constraints().unwrap();
+        assert_eq!(non_basic.len(), 2);
+
+        assert!(same_array(dbg!(&non_basic[0]), &[4.0, 1.0, 2.0]));
+        assert!(same_array(dbg!(&non_basic[1]), &[0.0, 1.0, 0.0]));
+    }
+}
@@ -0,0 +1,495 @@
+//! In-process GLPK implementations of the LP backends.
+//!
+//! GLPK keeps its environment per thread, so a problem has to be used and
+//! dropped by the thread which created it. The raw pointer keeps the types
+//! from being sent to other threads.
+
+use std::convert::TryInto;
+use std::ffi::CString;
+use std::os::raw::c_int;
+
+use anyhow::{Context, Result};
+use glpk_sys::*;
+
+use super::{
+    backend::{normalize, ExactSolution, LpBackend, SizeApproxBackend},
+    simplex::SimplexPreferenceLp,
+    size_approx::lower_constraint_dimension,
+};
+use crate::{
+    float_eq,
+    utils::{MyVec, Preference},
+};
+
+const GLP_MAX: c_int = 2; // maximisation
+const GLP_FR: c_int = 1; // free (unbounded) variable
+const GLP_LO: c_int = 2; // variable with lower bound
+const GLP_DB: c_int = 4; // double-bounded variable
+const GLP_FX: c_int = 5; // fixed variable
+const GLP_CV: c_int = 1; // continuous variable
+
+const GLP_ON: c_int = 1; // enable something
+const GLP_OFF: c_int = 0; // disable something
+const GLP_MSG_OFF: c_int = 0; // no output
+
+const GLP_OPT: c_int = 5; // solution is optimal
+const GLP_FEAS: c_int = 2; // solution is feasible
+
+const GLP_BS: c_int = 1; // basic variable
+const GLP_NL: c_int = 2; // non-basic variable on lower bound
+const GLP_NU: c_int = 3; // non-basic variable on upper bound
+const GLP_NF: c_int = 4; // non-basic free (unbounded) variable
+const GLP_NS: c_int = 5; // non-basic fixed variable
+
+/// Owns a GLPK problem object and deletes it when dropped.
+struct Problem {
+    lp: *mut glp_prob,
+    /// Number of solved LPs, used to name the LP files written with the
+    /// debug feature
+    counter: usize,
+}
+
+impl Problem {
+    fn new(counter: usize) -> Self {
+        let lp = unsafe {
+            glp_term_out(GLP_OFF);
+            let lp = glp_create_prob();
+            glp_set_obj_dir(lp, GLP_MAX);
+            lp
+        };
+        Problem { lp, counter }
+    }
+
+    /// Adds continuous columns and returns the index of the first one.
+    fn add_cols(
+        &mut self,
+        names: &str,
+        count: c_int,
+        kind: c_int,
+        lower: f64,
+        upper: f64,
+    ) -> c_int {
+        unsafe {
+            let first = glp_add_cols(self.lp, count);
+            for col in first..first + count {
+                let name = CString::new(format!("{}_{}", names, col - first))
+                    .expect("Column name could not be created");
+                glp_set_col_bnds(self.lp, col, kind, lower, upper);
+                glp_set_col_kind(self.lp, col, GLP_CV);
+                glp_set_obj_coef(self.lp, col, 0.0);
+                glp_set_col_name(self.lp, col, name.as_ptr());
+            }
+            first
+        }
+    }
+
+    /// Adds a row with the given bounds. `cols` and `values` start at index
+    /// 1, as usual for GLPK.
+    fn add_row(
+        &mut self,
+        kind: c_int,
+        lower: f64,
+        upper: f64,
+        cols: &[c_int],
+        values: &[f64],
+    ) -> c_int {
+        assert_eq!(cols.len(), values.len());
+        let len = cols.len() as c_int - 1;
+        unsafe {
+            let row = glp_add_rows(self.lp, 1);
+            glp_set_row_bnds(self.lp, row, kind, lower, upper);
+            glp_set_mat_row(self.lp, row, len, cols.as_ptr(), values.as_ptr());
+            row
+        }
+    }
+
+    #[cfg(feature = "debug")]
+    fn write_debug_file(&self, name: &str) {
+        let filename = CString::new(format!("/tmp/lps/{}-{}.lp", name, self.counter)).unwrap();
+        let file_stat = unsafe { glp_write_lp(self.lp, std::ptr::null(), filename.as_ptr()) };
+        if file_stat != 0 {
+            panic!(
+                "could not write file into {}",
+                filename.into_string().unwrap()
+            );
+        }
+    }
+
+    /// Runs the simplex method and returns whether a solution was found.
+    fn simplex(&mut self, exact: bool, _name: &str) -> bool {
+        #[cfg(feature = "debug")]
+        self.write_debug_file(_name);
+        self.counter += 1;
+
+        unsafe {
+            let mut params = glp_smcp::default();
+            glp_init_smcp(&mut params);
+            params.presolve = GLP_ON;
+            params.msg_lev = GLP_MSG_OFF;
+
+            let status = if exact {
+                glp_exact(self.lp, &params)
+            } else {
+                glp_simplex(self.lp, &params)
+            };
+            status == 0 && matches!(glp_get_status(self.lp), GLP_OPT | GLP_FEAS)
+        }
+    }
+
+    fn col_prim(&self, col: c_int) -> f64 {
+        unsafe { glp_get_col_prim(self.lp, col) }
+    }
+}
+
+impl Drop for Problem {
+    fn drop(&mut self) {
+        unsafe { glp_delete_prob(self.lp) }
+    }
+}
+
+pub struct GlpkPreferenceLp {
+    problem: Problem,
+    dim: c_int,
+    delta_col: c_int,
+    /// `glp_exact` only returns doubles, so rational solutions come from the
+    /// simplex backend with the same constraints, only kept in exact mode.
+    rational: Option<SimplexPreferenceLp>,
+}
+
+impl GlpkPreferenceLp {
+    fn setup(counter: usize, dim: c_int, exact: bool) -> Self {
+        let mut problem = Problem::new(counter);
+        problem.add_cols("alpha", dim, GLP_DB, 0.0, 1.0);
+        let delta_col = problem.add_cols("delta", 1, GLP_FR, 0.0, 0.0);
+        unsafe { glp_set_obj_coef(problem.lp, delta_col, 1.0) };
+
+        // sum of alpha is one
+        let cols: Vec<_> = (0..=dim).collect();
+        problem.add_row(GLP_FX, 1.0, 1.0, &cols, &vec![1.0; cols.len()]);
+
+        GlpkPreferenceLp {
+            problem,
+            dim,
+            delta_col,
+            rational: exact.then(|| SimplexPreferenceLp::with_dim(dim as usize, true)),
+        }
+    }
+}
+
+impl LpBackend for GlpkPreferenceLp {
+    fn new(dim: usize) -> Result<Self> {
+        Ok(Self::setup(0, dim.try_into()?, false))
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        *self = Self::setup(self.problem.counter, self.dim, self.rational.is_some());
+        Ok(())
+    }
+
+    fn set_exact(&mut self, exact: bool) -> Result<()> {
+        *self = Self::setup(self.problem.counter, self.dim, exact);
+        Ok(())
+    }
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<()> {
+        assert_eq!(
+            self.dim as usize,
+            costs.len(),
+            "Tried to add constraint with wrong dimension"
+        );
+        // 0 <= cost(alpha, p_alpha) - cost(alpha, p_trajectory) - delta
+        let cols: Vec<_> = (0..=self.dim)
+            .chain(std::iter::once(self.delta_col))
+            .collect();
+        let values: Vec<_> = std::iter::once(0.0)
+            .chain(normalize(costs))
+            .chain(std::iter::once(-1.0))
+            .collect();
+        self.problem.add_row(GLP_LO, 0.0, 0.0, &cols, &values);
+        match &mut self.rational {
+            Some(rational) => rational.add_constraint(costs),
+            None => Ok(()),
+        }
+    }
+
+    fn solve(&mut self, exact: bool) -> Result<Option<(Preference, f64)>> {
+        if !self.problem.simplex(exact, "my") {
+            return Ok(None);
+        }
+        let pref: Preference = (1..=self.dim)
+            .map(|col| self.problem.col_prim(col).max(0.0))
+            .collect::<Vec<_>>()
+            .into();
+        Ok(Some((pref, self.problem.col_prim(self.delta_col))))
+    }
+
+    fn solve_rational(&mut self) -> Result<Option<ExactSolution>> {
+        self.rational
+            .as_mut()
+            .context("rational solutions need exact mode")?
+            .solve_rational()
+    }
+}
+
+pub struct GlpkSizeApproxLp {
+    problem: Problem,
+    dim: c_int,
+}
+
+impl GlpkSizeApproxLp {
+    fn setup(counter: usize, dim: c_int) -> Self {
+        let mut problem = Problem::new(counter);
+        problem.add_cols("alpha", dim - 1, GLP_LO, 0.0, 1.0);
+
+        // the implicit last component of alpha is not negative:
+        // -sum of alpha >= -1
+        let cols: Vec<_> = (0..dim).collect();
+        problem.add_row(GLP_LO, -1.0, 1.0, &cols, &vec![-1.0; cols.len()]);
+
+        GlpkSizeApproxLp { problem, dim }
+    }
+}
+
+impl SizeApproxBackend for GlpkSizeApproxLp {
+    fn new(dim: usize) -> Result<Self> {
+        Ok(Self::setup(0, dim.try_into()?))
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        *self = Self::setup(self.problem.counter, self.dim);
+        Ok(())
+    }
+
+    fn set_obj_fun(&mut self, coef: &[f64]) -> Result<()> {
+        assert_eq!(
+            self.dim as usize - 1,
+            coef.len(),
+            "Tried to set objective function with wrong dimension"
+        );
+        for (i, &c) in coef.iter().enumerate() {
+            unsafe { glp_set_obj_coef(self.problem.lp, i as c_int + 1, c) };
+        }
+        Ok(())
+    }
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<i32> {
+        assert_eq!(
+            self.dim as usize,
+            costs.len(),
+            "Tried to add constraint with wrong dimension"
+        );
+        let lowered = lower_constraint_dimension(&normalize(costs));
+        let (last, rest) = lowered.split_last().unwrap();
+
+        // 0 <= cost(alpha, p_alpha) - cost(alpha, p_trajectory)
+        let cols: Vec<_> = (0..self.dim).collect();
+        let values: Vec<_> = std::iter::once(0.0).chain(rest.iter().copied()).collect();
+        Ok(self.problem.add_row(GLP_LO, *last, 0.0, &cols, &values))
+    }
+
+    fn solve(&mut self, exact: bool) -> Result<Option<Preference>> {
+        if !self.problem.simplex(exact, "size-approx") {
+            return Ok(None);
+        }
+        let mut result: Vec<f64> = (1..self.dim)
+            .map(|col| self.problem.col_prim(col).max(0.0))
+            .collect();
+        let sum: f64 = result.iter().sum();
+        result.push(1.0 - sum);
+        Ok(Some(MyVec(result)))
+    }
+
+    fn non_basic_constraints(&mut self) -> Result<Vec<Vec<f64>>> {
+        let lp = self.problem.lp;
+        let dim = self.dim as usize;
+        let mut res = Vec::new();
+        unsafe {
+            for i in 1..=glp_get_num_rows(lp) {
+                match glp_get_row_stat(lp, i) {
+                    GLP_BS => {} // skip basic rows
+                    GLP_NL | GLP_NU | GLP_NF | GLP_NS => {
+                        let mut indices = vec![0 as c_int; dim];
+                        let mut values = vec![0.0; dim];
+                        let len = glp_get_mat_row(lp, i, indices.as_mut_ptr(), values.as_mut_ptr());
+                        assert!(len < self.dim);
+
+                        let mut constraint = vec![0.0; dim];
+                        for (&j, &v) in indices.iter().zip(&values).skip(1).take(len as usize) {
+                            constraint[j as usize - 1] = v;
+                        }
+                        let last = constraint.last_mut().unwrap();
+                        assert!(float_eq!(*last, 0.0));
+                        *last = glp_get_row_lb(lp, i);
+                        res.push(constraint);
+                    }
+                    x => panic!("unknown row stat: {}", x),
+                }
+            }
+            for j in 1..=glp_get_num_cols(lp) {
+                match glp_get_col_stat(lp, j) {
+                    GLP_BS => {}
+                    GLP_NL => {
+                        let mut constraint = vec![0.0; dim];
+                        constraint[(j - 1) as usize] = 1.0;
+                        *constraint.last_mut().unwrap() = glp_get_col_lb(lp, j);
+                        res.push(constraint);
+                    }
+                    x => panic!("unknown col stat: {}", x),
+                }
+            }
+        }
+        assert_eq!(res.len(), dim - 1);
+        Ok(res)
+    }
+}
+
+/// Given some point sets, this ConvexHullIntersection finds point which lies in
+/// both convex hulls, if such a point exists
+#[cfg(not(feature = "simplex"))]
+pub struct ConvexHullIntersection {
+    dim: c_int,
+    lp: *mut glp_prob,
+    set_count: usize,
+}
+
+#[cfg(not(feature = "simplex"))]
+impl ConvexHullIntersection {
+    pub fn new(dim: usize) -> Self {
+        let dim = dim.try_into().unwrap();
+        let lp = unsafe {
+            glp_term_out(GLP_OFF);
+            let lp = glp_create_prob();
+            Self::setup_result_vars(lp, dim);
+            lp
+        };
+        Self {
+            dim,
+            lp,
+            set_count: 0,
+        }
+    }
+
+    unsafe fn setup_result_vars(lp: *mut glp_prob, dim: c_int) {
+        glp_add_cols(lp, dim);
+        for i in 0..dim {
+            let name =
+                CString::new(format!("goal_{}", i)).expect("Column name could not be created");
+
+            glp_set_col_bnds(lp, i + 1, GLP_LO, 0.0, 0.0);
+            glp_set_col_kind(lp, i + 1, GLP_CV);
+            glp_set_obj_coef(lp, i + 1, 0.0);
+            glp_set_col_name(lp, i + 1, name.as_ptr());
+        }
+    }
+
+    pub fn add_point_set(&mut self, points: &[Preference]) {
+        let point_len = points.len().try_into().unwrap();
+        unsafe {
+            let col = glp_add_cols(self.lp, point_len);
+            for i in 0..point_len {
+                let name = CString::new(format!("factor_{}_{}", self.set_count, i))
+                    .expect("Column name could not be created");
+
+                glp_set_col_bnds(self.lp, col + i, GLP_DB, 0.0, 1.0);
+                glp_set_col_kind(self.lp, col + i, GLP_CV);
+                glp_set_obj_coef(self.lp, col + i, 0.0);
+                glp_set_col_name(self.lp, col + i, name.as_ptr());
+            }
+            // Constraint: sum of convex combination factors is equal to one
+            let row = glp_add_rows(self.lp, 1);
+            let indices: Vec<_> = std::iter::once(0).chain(col..col + point_len).collect();
+            let values = vec![1.0; points.len() + 1];
+            assert_eq!(indices.len(), values.len());
+            glp_set_row_bnds(self.lp, row, GLP_FX, 1.0, 1.0);
+            glp_set_mat_row(self.lp, row, point_len, indices.as_ptr(), values.as_ptr());
+
+            // Constraints: in each dimension i: sum_j points[j]_i * factor_j = s_i
+            // sum_j points[j]_i * factor_j - s_i = 0
+            for i in 0..self.dim {
+                let row = glp_add_rows(self.lp, 1);
+                let indices: Vec<_> = std::iter::once(0)
+                    .chain(std::iter::once(i + 1)) //s_i
+                    .chain(col..col + point_len) // factor_j_i
+                    .collect();
+                let values: Vec<f64> = std::iter::once(0.)
+                    .chain(std::iter::once(-1.)) // s_i
+                    .chain(points.iter().map(|p| p[i])) // factor_j_i
+                    .collect();
+
+                assert_eq!(&indices.len(), &values.len());
+                glp_set_row_bnds(self.lp, row, GLP_FX, 0.0, 0.0);
+                glp_set_mat_row(
+                    self.lp,
+                    row,
+                    point_len + 1,
+                    indices.as_ptr(),
+                    values.as_ptr(),
+                );
+            }
+        }
+        self.set_count += 1;
+    }
+
+    pub fn reset(&mut self) {
+        self.set_count = 0;
+        unsafe {
+            let old = std::mem::replace(&mut self.lp, glp_create_prob());
+            glp_delete_prob(old);
+            Self::setup_result_vars(self.lp, self.dim);
+        }
+    }
+
+    pub fn solve(&mut self) -> Result<Option<Preference>> {
+        unsafe {
+            let mut params = glp_smcp::default();
+            glp_init_smcp(&mut params);
+            params.presolve = GLP_ON;
+            params.msg_lev = GLP_MSG_OFF;
+
+            let status = glp_simplex(self.lp, &params);
+            if status == 0 {
+                let status = glp_get_status(self.lp);
+                if !(status == GLP_OPT || status == GLP_FEAS) {
+                    return Ok(None);
+                }
+            } else {
+                return Ok(None);
+            }
+            let mut result = vec![0.0; self.dim as usize];
+            for i in 0..self.dim {
+                result[i as usize] = glp_get_col_prim(self.lp, i + 1);
+            }
+            Ok(Some(result.into()))
+        }
+    }
+}
+
+#[cfg(not(feature = "simplex"))]
+impl Drop for ConvexHullIntersection {
+    fn drop(&mut self) {
+        unsafe { glp_delete_prob(self.lp) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::backend::tests::{
+        getting_non_basic_constraints, rational_solution, strange_lp_behavior,
+    };
+    use super::*;
+
+    #[test]
+    fn test_strange_lp_behavior() {
+        strange_lp_behavior::<GlpkPreferenceLp>();
+    }
+
+    #[test]
+    fn test_rational_solution() {
+        rational_solution::<GlpkPreferenceLp>();
+    }
+
+    #[test]
+    fn test_getting_non_basic_constraints() {
+        getting_non_basic_constraints::<GlpkSizeApproxLp>();
+    }
+}
@@ -0,0 +1,775 @@
+//! Dense simplex solver, the LP backend without dependencies.
+//!
+//! The LPs for preferences have only a handful of variables besides the
+//! constraints, so a dense tableau is fast enough. Like in GLPK, every row has
+//! an auxiliary variable for the value of its linear form, and structural and
+//! auxiliary variables are bounded alike. The final basis therefore tells
+//! which rows and columns are non basic in the same way the GLPK backend
+//! does.
+//!
+//! The solver runs the two phase method with Bland's rule, which cannot
+//! cycle on degenerate vertices. The tableau is generic over `Number`, so the
+//! same code solves the LPs exactly with rationals.
+
+use anyhow::{bail, ensure, Context, Result};
+
+use super::{
+    backend::{normalize, ExactSolution, LpBackend, SizeApproxBackend},
+    size_approx::lower_constraint_dimension,
+};
+use crate::utils::{
+    number::{Number, Rational},
+    MyVec, Preference,
+};
+
+/// Pivot elements and reduced costs of smaller magnitude count as zero.
+const PIVOT_TOLERANCE: f64 = 1e-9;
+/// Largest violation of the constraints accepted after the first phase.
+const FEASIBILITY_TOLERANCE: f64 = 1e-7;
+/// Simplex iterations per variable and row after which a phase is aborted.
+const ITERATIONS_PER_VARIABLE: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Bounds {
+    Free,
+    Lower(f64),
+    Double(f64, f64),
+    Fixed(f64),
+}
+
+impl Bounds {
+    /// The lower bound, `-f64::MAX` if there is none like in GLPK.
+    fn lower(self) -> f64 {
+        match self {
+            Bounds::Free => -f64::MAX,
+            Bounds::Lower(l) | Bounds::Double(l, _) | Bounds::Fixed(l) => l,
+        }
+    }
+}
+
+/// LP with bounded columns and rows, the objective is maximized.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Problem {
+    cols: Vec<Bounds>,
+    objective: Vec<f64>,
+    /// Coefficients and bounds of the rows. Rows may be shorter than the
+    /// number of columns, missing coefficients are zero.
+    rows: Vec<(Vec<f64>, Bounds)>,
+    /// Overrides `ITERATIONS_PER_VARIABLE`
+    iterations_per_variable: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Solution<T = f64> {
+    /// Values of the structural variables
+    pub values: Vec<T>,
+    /// Whether the auxiliary variable of a row is basic
+    pub basic_rows: Vec<bool>,
+    /// Whether a structural variable is basic
+    pub basic_cols: Vec<bool>,
+}
+
+impl Problem {
+    /// Adds a column and returns its index.
+    pub fn add_col(&mut self, bounds: Bounds, objective: f64) -> usize {
+        self.cols.push(bounds);
+        self.objective.push(objective);
+        self.cols.len() - 1
+    }
+
+    /// Adds a row and returns its index.
+    pub fn add_row(&mut self, coefficients: Vec<f64>, bounds: Bounds) -> usize {
+        assert!(coefficients.len() <= self.cols.len());
+        self.rows.push((coefficients, bounds));
+        self.rows.len() - 1
+    }
+
+    pub fn set_objective(&mut self, col: usize, coefficient: f64) {
+        self.objective[col] = coefficient;
+    }
+
+    pub fn rows(&self) -> &[(Vec<f64>, Bounds)] {
+        &self.rows
+    }
+
+    /// Returns an optimal solution, or `None` if the LP is infeasible or
+    /// unbounded. The coefficients are converted to `T`, so with rationals
+    /// the LP is solved exactly. Fails if the simplex does not terminate,
+    /// which can only happen due to rounding.
+    pub fn maximize<T: Number>(&self) -> Result<Option<Solution<T>>> {
+        let mut vars = Variables::default();
+        let cols: Vec<_> = self.cols.iter().map(|&b| vars.substitute(b)).collect();
+        let aux: Vec<_> = self.rows.iter().map(|(_, b)| vars.substitute(*b)).collect();
+
+        // every row states `row * x - aux = 0`
+        let mut equations = Vec::with_capacity(self.rows.len() + vars.bounds.len());
+        for ((coefficients, _), &aux) in self.rows.iter().zip(&aux) {
+            let mut equation = Equation::new(vars.count);
+            for (&a, &col) in coefficients.iter().zip(&cols) {
+                equation.add_term(a, col);
+            }
+            equation.add_term(-1.0, aux);
+            equations.push(equation);
+        }
+        for &(var, slack, width) in &vars.bounds {
+            let mut equation = Equation::new(vars.count);
+            equation.coefficients[var] = T::one();
+            equation.coefficients[slack] = T::one();
+            equation.rhs = T::from_f64(width);
+            equations.push(equation);
+        }
+
+        let mut objective = Equation::new(vars.count);
+        for (&c, &col) in self.objective.iter().zip(&cols) {
+            objective.add_term(c, col);
+        }
+
+        let iterations = self
+            .iterations_per_variable
+            .unwrap_or(ITERATIONS_PER_VARIABLE);
+        let mut tableau = Tableau::new(equations, vars.count, iterations);
+        if !tableau.find_feasible_basis()? || !tableau.maximize(objective.coefficients)? {
+            return Ok(None);
+        }
+
+        let values = tableau.values();
+        let basic = tableau.basic();
+        Ok(Some(Solution {
+            values: cols.iter().map(|s| s.value(&values)).collect(),
+            basic_rows: aux.iter().map(|s| s.is_basic(&basic)).collect(),
+            basic_cols: cols.iter().map(|s| s.is_basic(&basic)).collect(),
+        }))
+    }
+}
+
+/// How a bounded variable is expressed by non-negative tableau variables.
+#[derive(Debug, Clone, Copy)]
+enum Substitution {
+    /// `offset + sign * var`, with a slack if the variable has two bounds
+    Shifted {
+        offset: f64,
+        sign: f64,
+        var: usize,
+        slack: Option<usize>,
+    },
+    /// `pos - neg` for free variables
+    Split {
+        pos: usize,
+        neg: usize,
+    },
+    Constant(f64),
+}
+
+impl Substitution {
+    fn value<T: Number>(self, values: &[T]) -> T {
+        match self {
+            Substitution::Shifted {
+                offset, sign, var, ..
+            } => T::from_f64(offset) + T::from_f64(sign) * values[var].clone(),
+            Substitution::Split { pos, neg } => values[pos].clone() - values[neg].clone(),
+            Substitution::Constant(c) => T::from_f64(c),
+        }
+    }
+
+    /// A variable on one of its bounds is non basic. If the slack of the
+    /// upper bound is non basic, the variable is on its upper bound.
+    fn is_basic(self, basic: &[bool]) -> bool {
+        match self {
+            Substitution::Shifted { var, slack, .. } => {
+                basic[var] && slack.is_none_or(|s| basic[s])
+            }
+            Substitution::Split { pos, neg } => basic[pos] || basic[neg],
+            Substitution::Constant(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Variables {
+    count: usize,
+    /// `var + slack = width` for variables with two bounds
+    bounds: Vec<(usize, usize, f64)>,
+}
+
+impl Variables {
+    fn next(&mut self) -> usize {
+        self.count += 1;
+        self.count - 1
+    }
+
+    fn substitute(&mut self, bounds: Bounds) -> Substitution {
+        match bounds {
+            Bounds::Free => Substitution::Split {
+                pos: self.next(),
+                neg: self.next(),
+            },
+            Bounds::Lower(offset) => Substitution::Shifted {
+                offset,
+                sign: 1.0,
+                var: self.next(),
+                slack: None,
+            },
+            Bounds::Double(lower, upper) => {
+                let (var, slack) = (self.next(), self.next());
+                self.bounds.push((var, slack, upper - lower));
+                Substitution::Shifted {
+                    offset: lower,
+                    sign: 1.0,
+                    var,
+                    slack: Some(slack),
+                }
+            }
+            Bounds::Fixed(c) => Substitution::Constant(c),
+        }
+    }
+}
+
+struct Equation<T> {
+    coefficients: Vec<T>,
+    rhs: T,
+}
+
+impl<T: Number> Equation<T> {
+    fn new(var_count: usize) -> Self {
+        Equation {
+            coefficients: vec![T::zero(); var_count],
+            rhs: T::zero(),
+        }
+    }
+
+    fn add_term(&mut self, factor: f64, substitution: Substitution) {
+        let factor = T::from_f64(factor);
+        match substitution {
+            Substitution::Shifted {
+                offset, sign, var, ..
+            } => {
+                self.coefficients[var] += factor.clone() * T::from_f64(sign);
+                self.rhs -= factor * T::from_f64(offset);
+            }
+            Substitution::Split { pos, neg } => {
+                self.coefficients[pos] += factor.clone();
+                self.coefficients[neg] -= factor;
+            }
+            Substitution::Constant(c) => self.rhs -= factor * T::from_f64(c),
+        }
+    }
+}
+
+/// Simplex tableau for `A y = b, y >= 0`. The first phase adds one
+/// artificial variable per row behind the `var_count` variables.
+struct Tableau<T> {
+    /// Rows of `A` followed by the artificial variables and `b`
+    rows: Vec<Vec<T>>,
+    /// Reduced costs of the current objective
+    costs: Vec<T>,
+    basis: Vec<usize>,
+    var_count: usize,
+    iterations_per_variable: usize,
+}
+
+impl<T: Number> Tableau<T> {
+    fn new(equations: Vec<Equation<T>>, var_count: usize, iterations_per_variable: usize) -> Self {
+        let row_count = equations.len();
+        let rows = equations
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| {
+                // the artificial variables need a non negative start value
+                let negate = e.rhs.is_negative();
+                let sign = |v: T| if negate { -v } else { v };
+                let mut row: Vec<T> = e.coefficients.into_iter().map(sign).collect();
+                row.extend((0..row_count).map(|j| if i == j { T::one() } else { T::zero() }));
+                row.push(sign(e.rhs));
+                row
+            })
+            .collect();
+        Tableau {
+            rows,
+            costs: Vec::new(),
+            basis: (var_count..var_count + row_count).collect(),
+            var_count,
+            iterations_per_variable,
+        }
+    }
+
+    fn rhs(&self, row: usize) -> &T {
+        self.rows[row].last().unwrap()
+    }
+
+    /// Minimizes the sum of the artificial variables and pivots them out of
+    /// the basis. Rows which are linear combinations of others are removed.
+    fn find_feasible_basis(&mut self) -> Result<bool> {
+        let width = self.var_count + self.rows.len() + 1;
+        self.costs = vec![T::zero(); width];
+        for row in &self.rows {
+            for j in (0..self.var_count).chain(std::iter::once(width - 1)) {
+                self.costs[j] += row[j].clone();
+            }
+        }
+        if !self.optimize(width - 1)? {
+            return Ok(false);
+        }
+        let infeasibility = (0..self.rows.len())
+            .filter(|&i| self.basis[i] >= self.var_count)
+            .fold(T::zero(), |sum, i| sum + self.rhs(i).clone());
+        if infeasibility > T::tolerance(FEASIBILITY_TOLERANCE) {
+            return Ok(false);
+        }
+
+        let tolerance = T::tolerance(PIVOT_TOLERANCE);
+        let mut i = 0;
+        while i < self.rows.len() {
+            if self.basis[i] >= self.var_count {
+                let row = &self.rows[i];
+                let entering = (0..self.var_count)
+                    .filter(|&j| row[j].abs() > tolerance)
+                    .max_by(|&a, &b| row[a].abs().partial_cmp(&row[b].abs()).unwrap());
+                match entering {
+                    Some(j) => self.pivot(i, j),
+                    None => {
+                        self.rows.remove(i);
+                        self.basis.remove(i);
+                        continue;
+                    }
+                }
+            }
+            i += 1;
+        }
+        Ok(true)
+    }
+
+    /// Maximizes `objective * y` starting from a feasible basis. Returns
+    /// false if the LP is unbounded.
+    fn maximize(&mut self, objective: Vec<T>) -> Result<bool> {
+        let width = self.rows.first().map_or(self.var_count + 1, Vec::len);
+        self.costs = objective;
+        self.costs.resize(width, T::zero());
+        for (row, &b) in self.rows.iter().zip(&self.basis) {
+            let factor = self.costs[b].clone();
+            if !factor.is_zero() {
+                self.costs
+                    .iter_mut()
+                    .zip(row)
+                    .for_each(|(c, r)| *c -= factor.clone() * r.clone());
+            }
+        }
+        self.optimize(self.var_count)
+    }
+
+    /// Runs simplex iterations, only the first `allowed` variables may enter
+    /// the basis. Returns false if the objective is unbounded.
+    fn optimize(&mut self, allowed: usize) -> Result<bool> {
+        let tolerance = T::tolerance(PIVOT_TOLERANCE);
+        // Bland's rule terminates, the limit only guards against rounding
+        let max_iterations = self.iterations_per_variable * (self.rows.len() + allowed + 1);
+        for iteration in 0.. {
+            let entering = match (0..allowed).find(|&j| self.costs[j] > tolerance) {
+                Some(j) => j,
+                None => return Ok(true),
+            };
+            if iteration == max_iterations {
+                bail!(
+                    "the simplex did not terminate within {} iterations",
+                    max_iterations
+                );
+            }
+            let candidates: Vec<_> = (0..self.rows.len())
+                .filter(|&i| self.rows[i][entering] > tolerance)
+                .map(|i| (i, self.rhs(i).clone() / self.rows[i][entering].clone()))
+                .collect();
+            let min_ratio = match candidates
+                .iter()
+                .map(|(_, r)| r)
+                .min_by(|a, b| a.partial_cmp(b).unwrap())
+            {
+                Some(r) => r.clone() + tolerance.clone(),
+                None => return Ok(false),
+            };
+            let (leaving, _) = candidates
+                .into_iter()
+                .filter(|(_, r)| *r <= min_ratio)
+                .min_by_key(|&(i, _)| self.basis[i])
+                .unwrap();
+            self.pivot(leaving, entering);
+        }
+        unreachable!()
+    }
+
+    fn pivot(&mut self, row: usize, col: usize) {
+        let pivot = self.rows[row][col].clone();
+        self.rows[row].iter_mut().for_each(|v| *v /= pivot.clone());
+        let pivot_row = self.rows[row].clone();
+
+        let others = self
+            .rows
+            .iter_mut()
+            .enumerate()
+            .filter(|&(i, _)| i != row)
+            .map(|(_, r)| r)
+            .chain(std::iter::once(&mut self.costs));
+        for r in others {
+            let factor = r[col].clone();
+            if !factor.is_zero() {
+                r.iter_mut()
+                    .zip(&pivot_row)
+                    .for_each(|(v, p)| *v -= factor.clone() * p.clone());
+            }
+        }
+        self.basis[row] = col;
+    }
+
+    fn values(&self) -> Vec<T> {
+        let mut values = vec![T::zero(); self.var_count];
+        for (i, &b) in self.basis.iter().enumerate() {
+            if b < self.var_count && self.rhs(i).is_positive() {
+                values[b] = self.rhs(i).clone();
+            }
+        }
+        values
+    }
+
+    fn basic(&self) -> Vec<bool> {
+        let mut basic = vec![false; self.var_count];
+        for &b in self.basis.iter().filter(|&&b| b < self.var_count) {
+            basic[b] = true;
+        }
+        basic
+    }
+}
+
+pub struct SimplexPreferenceLp {
+    dim: usize,
+    exact: bool,
+    /// The constraints as they were added
+    constraints: Vec<Vec<f64>>,
+}
+
+impl SimplexPreferenceLp {
+    pub(super) fn with_dim(dim: usize, exact: bool) -> Self {
+        SimplexPreferenceLp {
+            dim,
+            exact,
+            constraints: Vec::new(),
+        }
+    }
+
+    fn problem(&self, normalized: bool) -> Problem {
+        let mut problem = Problem::default();
+        for _ in 0..self.dim {
+            problem.add_col(Bounds::Double(0.0, 1.0), 0.0);
+        }
+        // delta
+        problem.add_col(Bounds::Free, 1.0);
+        // sum of alpha is one
+        problem.add_row(vec![1.0; self.dim], Bounds::Fixed(1.0));
+
+        for costs in &self.constraints {
+            // 0 <= cost(alpha, p_alpha) - cost(alpha, p_trajectory) - delta
+            let mut coefficients = if normalized {
+                normalize(costs)
+            } else {
+                costs.clone()
+            };
+            coefficients.push(-1.0);
+            problem.add_row(coefficients, Bounds::Lower(0.0));
+        }
+        problem
+    }
+
+    fn solve_exact(&self) -> Result<Option<ExactSolution>> {
+        Ok(self
+            .problem(false)
+            .maximize::<Rational>()?
+            .map(|mut solution| {
+                let delta = solution.values.pop().unwrap();
+                ExactSolution {
+                    preference: solution.values,
+                    delta,
+                }
+            }))
+    }
+}
+
+impl LpBackend for SimplexPreferenceLp {
+    fn new(dim: usize) -> Result<Self> {
+        Ok(Self::with_dim(dim, false))
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.constraints.clear();
+        Ok(())
+    }
+
+    fn set_exact(&mut self, exact: bool) -> Result<()> {
+        self.exact = exact;
+        self.reset()
+    }
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<()> {
+        assert_eq!(
+            self.dim,
+            costs.len(),
+            "Tried to add constraint with wrong dimension"
+        );
+        self.constraints.push(costs.to_vec());
+        Ok(())
+    }
+
+    /// With `exact` the LP is solved with rationals and the result rounded.
+    fn solve(&mut self, exact: bool) -> Result<Option<(Preference, f64)>> {
+        if exact {
+            return Ok(self.solve_exact()?.map(|solution| {
+                let pref = solution.preference.iter().map(Number::to_f64).collect();
+                (MyVec(pref), solution.delta.to_f64())
+            }));
+        }
+        Ok(self.problem(true).maximize()?.map(|solution| {
+            let pref = solution.values[..self.dim]
+                .iter()
+                .map(|v: &f64| v.max(0.0))
+                .collect::<Vec<_>>();
+            (MyVec(pref), solution.values[self.dim])
+        }))
+    }
+
+    fn solve_rational(&mut self) -> Result<Option<ExactSolution>> {
+        ensure!(self.exact, "rational solutions need exact mode");
+        self.solve_exact()
+    }
+}
+
+pub struct SimplexSizeApproxLp {
+    problem: Problem,
+    dim: usize,
+    solution: Option<Solution>,
+}
+
+impl SimplexSizeApproxLp {
+    fn setup(dim: usize) -> Self {
+        let mut problem = Problem::default();
+        for _ in 0..dim - 1 {
+            problem.add_col(Bounds::Lower(0.0), 0.0);
+        }
+        // the implicit last component of alpha is not negative:
+        // -sum of alpha >= -1
+        problem.add_row(vec![-1.0; dim - 1], Bounds::Lower(-1.0));
+
+        SimplexSizeApproxLp {
+            problem,
+            dim,
+            solution: None,
+        }
+    }
+}
+
+impl SizeApproxBackend for SimplexSizeApproxLp {
+    fn new(dim: usize) -> Result<Self> {
+        Ok(Self::setup(dim))
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        *self = Self::setup(self.dim);
+        Ok(())
+    }
+
+    fn set_obj_fun(&mut self, coef: &[f64]) -> Result<()> {
+        assert_eq!(
+            self.dim - 1,
+            coef.len(),
+            "Tried to set objective function with wrong dimension"
+        );
+        for (i, &c) in coef.iter().enumerate() {
+            self.problem.set_objective(i, c);
+        }
+        Ok(())
+    }
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<i32> {
+        assert_eq!(
+            self.dim,
+            costs.len(),
+            "Tried to add constraint with wrong dimension"
+        );
+        let mut coefficients = lower_constraint_dimension(&normalize(costs));
+        let lower = coefficients.pop().unwrap();
+
+        // 0 <= cost(alpha, p_alpha) - cost(alpha, p_trajectory)
+        let row = self.problem.add_row(coefficients, Bounds::Lower(lower));
+        // GLPK counts rows from 1
+        Ok(row as i32 + 1)
+    }
+
+    /// With `exact` the LP is solved with rationals and the result rounded.
+    fn solve(&mut self, exact: bool) -> Result<Option<Preference>> {
+        self.solution = if exact {
+            self.problem
+                .maximize::<Rational>()?
+                .map(|solution| Solution {
+                    values: solution.values.iter().map(Number::to_f64).collect(),
+                    basic_rows: solution.basic_rows,
+                    basic_cols: solution.basic_cols,
+                })
+        } else {
+            self.problem.maximize()?
+        };
+        Ok(self.solution.as_ref().map(|solution| {
+            let mut result: Vec<f64> = solution.values.iter().map(|v| v.max(0.0)).collect();
+            let sum: f64 = result.iter().sum();
+            result.push(1.0 - sum);
+            MyVec(result)
+        }))
+    }
+
+    fn non_basic_constraints(&mut self) -> Result<Vec<Vec<f64>>> {
+        let solution = self
+            .solution
+            .as_ref()
+            .context("non basic constraints are only known after solving the LP")?;
+
+        let mut res = Vec::new();
+        for ((coefficients, bounds), &basic) in self.problem.rows().iter().zip(&solution.basic_rows)
+        {
+            if !basic {
+                let mut constraint = coefficients.clone();
+                constraint.resize(self.dim - 1, 0.0);
+                constraint.push(bounds.lower());
+                res.push(constraint);
+            }
+        }
+        for (j, &basic) in solution.basic_cols.iter().enumerate() {
+            if !basic {
+                let mut constraint = vec![0.0; self.dim];
+                constraint[j] = 1.0;
+                *constraint.last_mut().unwrap() = self.problem.cols[j].lower();
+                res.push(constraint);
+            }
+        }
+        assert_eq!(res.len(), self.dim - 1);
+        Ok(res)
+    }
+}
+
+/// Given some point sets, this ConvexHullIntersection finds point which lies in
+/// both convex hulls, if such a point exists
+#[cfg(any(feature = "simplex", not(feature = "glpk")))]
+pub struct ConvexHullIntersection {
+    dim: usize,
+    point_sets: Vec<Vec<Preference>>,
+}
+
+#[cfg(any(feature = "simplex", not(feature = "glpk")))]
+impl ConvexHullIntersection {
+    pub fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            point_sets: Vec::new(),
+        }
+    }
+
+    pub fn add_point_set(&mut self, points: &[Preference]) {
+        self.point_sets.push(points.to_vec());
+    }
+
+    pub fn reset(&mut self) {
+        self.point_sets.clear();
+    }
+
+    pub fn solve(&mut self) -> Result<Option<Preference>> {
+        let mut problem = Problem::default();
+        for _ in 0..self.dim {
+            problem.add_col(Bounds::Lower(0.0), 0.0);
+        }
+        for points in &self.point_sets {
+            let factors: Vec<_> = points
+                .iter()
+                .map(|_| problem.add_col(Bounds::Double(0.0, 1.0), 0.0))
+                .collect();
+            let width = problem.cols.len();
+
+            // sum of convex combination factors is equal to one
+            let mut row = vec![0.0; width];
+            factors.iter().for_each(|&f| row[f] = 1.0);
+            problem.add_row(row, Bounds::Fixed(1.0));
+
+            // in each dimension i: sum_j points[j]_i * factor_j - s_i = 0
+            for i in 0..self.dim {
+                let mut row = vec![0.0; width];
+                row[i] = -1.0;
+                for (&f, p) in factors.iter().zip(points) {
+                    row[f] = p[i];
+                }
+                problem.add_row(row, Bounds::Fixed(0.0));
+            }
+        }
+        Ok(problem
+            .maximize()?
+            .map(|solution| MyVec(solution.values[..self.dim].to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::backend::tests::{
+        getting_non_basic_constraints, rational_solution, strange_lp_behavior,
+    };
+    use super::*;
+    use crate::utils::{number::convert, same_array};
+
+    #[test]
+    fn test_strange_lp_behavior() {
+        strange_lp_behavior::<SimplexPreferenceLp>();
+    }
+
+    #[test]
+    fn test_rational_solution() {
+        rational_solution::<SimplexPreferenceLp>();
+    }
+
+    #[test]
+    fn test_getting_non_basic_constraints() {
+        getting_non_basic_constraints::<SimplexSizeApproxLp>();
+    }
+
+    #[test]
+    fn test_bounds_and_infeasibility() {
+        // maximize x + y with x in [1, 2], y >= 0, x + y >= 1 and x - y = -0.5
+        let mut problem = Problem::default();
+        problem.add_col(Bounds::Double(1.0, 2.0), 1.0);
+        problem.add_col(Bounds::Lower(0.0), 1.0);
+        problem.add_row(vec![1.0, 1.0], Bounds::Lower(1.0));
+        problem.add_row(vec![1.0, -1.0], Bounds::Fixed(-0.5));
+
+        let solution = problem.maximize::<f64>().unwrap().unwrap();
+        assert!(same_array(&solution.values, &[2.0, 2.5]));
+        let exact = problem.maximize::<Rational>().unwrap().unwrap();
+        assert_eq!(exact.values, convert::<Rational>(&[2.0, 2.5]));
+        // x is on its upper bound, the second row is fixed
+        assert_eq!(solution.basic_cols, vec![false, true]);
+        assert_eq!(solution.basic_rows, vec![true, false]);
+
+        problem.add_row(vec![0.0, 1.0], Bounds::Lower(3.5));
+        assert!(problem.maximize::<f64>().unwrap().is_none());
+        assert!(problem.maximize::<Rational>().unwrap().is_none());
+
+        let mut unbounded = Problem::default();
+        unbounded.add_col(Bounds::Free, 1.0);
+        unbounded.add_col(Bounds::Lower(0.0), 0.0);
+        unbounded.add_row(vec![-1.0, 1.0], Bounds::Lower(0.0));
+        assert!(unbounded.maximize::<f64>().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_iteration_limit() {
+        let mut problem = Problem::default();
+        problem.add_col(Bounds::Double(0.0, 1.0), 1.0);
+        problem.add_col(Bounds::Double(0.0, 1.0), 1.0);
+        problem.add_row(vec![1.0, 1.0], Bounds::Lower(0.5));
+        assert!(problem.maximize::<f64>().unwrap().is_some());
+
+        // running out of iterations is neither infeasible nor unbounded
+        problem.iterations_per_variable = Some(0);
+        let error = problem.maximize::<f64>().unwrap_err();
+        assert!(error.to_string().contains("did not terminate"));
+        assert!(problem.maximize::<Rational>().is_err());
+    }
+}
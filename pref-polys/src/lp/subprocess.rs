@@ -0,0 +1,270 @@
+//! LP backends which run GLPK in a helper process.
+//!
+//! The helper binaries `lp_preference` and `lp_size_approx` wrap the in-process
+//! GLPK backends. If GLPK crashes, only the helper dies and the error is
+//! reported on the next operation. The helpers are looked up next to the
+//! current executable.
+//!
+//! The protocol starts every message with a control byte, followed by native
+//! endian values:
+//!
+//! - 0: reset
+//! - 1: add a constraint with `dim` values, the size approximation answers
+//!   with the row as `i32`
+//! - 2 and 3: solve, respectively solve exactly. The answer is 1 if no
+//!   solution was found, otherwise 0 followed by the preference and, for the
+//!   preference LP, delta.
+//! - 4: set the objective function with `dim - 1` values
+//! - 5: get the non basic constraints, answered by their count as `usize`
+//!   followed by `dim` values per constraint
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use super::{
+    backend::{ExactSolution, LpBackend, SizeApproxBackend},
+    simplex::SimplexPreferenceLp,
+};
+use crate::utils::{MyVec, Preference, F64_SIZE};
+
+pub const RESET: u8 = 0;
+pub const ADD_CONSTRAINT: u8 = 1;
+pub const SOLVE: u8 = 2;
+pub const SOLVE_EXACT: u8 = 3;
+pub const SET_OBJ_FUN: u8 = 4;
+pub const NON_BASIC_CONSTRAINTS: u8 = 5;
+
+pub const SOLVED: u8 = 0;
+pub const NO_SOLUTION: u8 = 1;
+
+/// Reads `count` native endian floats.
+pub fn read_f64s(r: &mut impl Read, count: usize) -> std::io::Result<Vec<f64>> {
+    let mut buffer = vec![0u8; F64_SIZE * count];
+    r.read_exact(&mut buffer)?;
+    Ok(crate::utils::convert_to_f64_vec(&mut buffer))
+}
+
+/// Writes the values as native endian floats.
+pub fn write_f64s(w: &mut impl Write, values: &[f64]) -> std::io::Result<()> {
+    for v in values {
+        w.write_all(&v.to_ne_bytes())?;
+    }
+    Ok(())
+}
+
+/// Connection to a helper binary.
+struct Helper {
+    child: Child,
+    writer: BufWriter<ChildStdin>,
+    reader: BufReader<ChildStdout>,
+}
+
+impl Helper {
+    fn spawn(name: &str, dim: usize) -> Result<Helper> {
+        let mut path = std::env::current_exe()?;
+        path.pop();
+        path.push(name);
+
+        // In case we run tests, we run from the deps directory...
+        if !path.exists() {
+            path.pop();
+            path.pop();
+            path.push(name);
+        }
+
+        let mut child = Command::new(&path)
+            .arg(dim.to_string())
+            .stdout(Stdio::piped())
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("could not start {}", path.display()))?;
+
+        let writer = BufWriter::new(child.stdin.take().unwrap());
+        let reader = BufReader::new(child.stdout.take().unwrap());
+        Ok(Helper {
+            child,
+            writer,
+            reader,
+        })
+    }
+
+    fn send(&mut self, control_byte: u8, values: &[f64]) -> Result<()> {
+        self.writer
+            .write_all(&[control_byte])
+            .and_then(|_| write_f64s(&mut self.writer, values))
+            .and_then(|_| self.writer.flush())
+            .with_context(|| format!("failed to send message {} to LP", control_byte))
+    }
+
+    /// Reads the answer to a solve message, `None` if there is no solution.
+    fn read_solution(&mut self, count: usize) -> Result<Option<Vec<f64>>> {
+        let mut control_byte = [0u8; 1];
+        self.reader
+            .read_exact(&mut control_byte)
+            .context("failed to read LP status")?;
+        match control_byte[0] {
+            SOLVED => Ok(Some(
+                read_f64s(&mut self.reader, count).context("failed to read LP solution")?,
+            )),
+            NO_SOLUTION => Ok(None),
+            x => bail!("Unknown control byte received on main side: {}", x),
+        }
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub struct SubprocessPreferenceLp {
+    helper: Helper,
+    dim: usize,
+    /// GLPK cannot return rationals, they are computed in this process. Only
+    /// kept in exact mode.
+    rational: Option<SimplexPreferenceLp>,
+}
+
+impl LpBackend for SubprocessPreferenceLp {
+    fn new(dim: usize) -> Result<Self> {
+        Ok(SubprocessPreferenceLp {
+            helper: Helper::spawn("lp_preference", dim)?,
+            dim,
+            rational: None,
+        })
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        if let Some(rational) = &mut self.rational {
+            rational.reset()?;
+        }
+        self.helper.send(RESET, &[])
+    }
+
+    fn set_exact(&mut self, exact: bool) -> Result<()> {
+        self.rational = exact.then(|| SimplexPreferenceLp::with_dim(self.dim, true));
+        self.helper.send(RESET, &[])
+    }
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<()> {
+        assert_eq!(
+            self.dim,
+            costs.len(),
+            "Tried to add constraint with wrong dimension"
+        );
+        if let Some(rational) = &mut self.rational {
+            rational.add_constraint(costs)?;
+        }
+        self.helper.send(ADD_CONSTRAINT, costs)
+    }
+
+    fn solve(&mut self, exact: bool) -> Result<Option<(Preference, f64)>> {
+        self.helper
+            .send(if exact { SOLVE_EXACT } else { SOLVE }, &[])?;
+        Ok(self.helper.read_solution(self.dim + 1)?.map(|mut result| {
+            let delta = result.pop().unwrap();
+            (MyVec(result), delta)
+        }))
+    }
+
+    fn solve_rational(&mut self) -> Result<Option<ExactSolution>> {
+        self.rational
+            .as_mut()
+            .context("rational solutions need exact mode")?
+            .solve_rational()
+    }
+}
+
+pub struct SubprocessSizeApproxLp {
+    helper: Helper,
+    dim: usize,
+}
+
+impl SizeApproxBackend for SubprocessSizeApproxLp {
+    fn new(dim: usize) -> Result<Self> {
+        Ok(SubprocessSizeApproxLp {
+            helper: Helper::spawn("lp_size_approx", dim)?,
+            dim,
+        })
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.helper.send(RESET, &[])
+    }
+
+    fn set_obj_fun(&mut self, coef: &[f64]) -> Result<()> {
+        assert_eq!(
+            self.dim - 1,
+            coef.len(),
+            "Tried to set objective function with wrong dimension"
+        );
+        self.helper.send(SET_OBJ_FUN, coef)
+    }
+
+    fn add_constraint(&mut self, costs: &[f64]) -> Result<i32> {
+        assert_eq!(
+            self.dim,
+            costs.len(),
+            "Tried to add constraint with wrong dimension"
+        );
+        self.helper.send(ADD_CONSTRAINT, costs)?;
+
+        let mut buf = [0u8; 4];
+        self.helper
+            .reader
+            .read_exact(&mut buf)
+            .context("failed to read index for new constraint")?;
+        Ok(i32::from_ne_bytes(buf))
+    }
+
+    fn solve(&mut self, exact: bool) -> Result<Option<Preference>> {
+        self.helper
+            .send(if exact { SOLVE_EXACT } else { SOLVE }, &[])?;
+        Ok(self.helper.read_solution(self.dim)?.map(MyVec))
+    }
+
+    fn non_basic_constraints(&mut self) -> Result<Vec<Vec<f64>>> {
+        self.helper.send(NON_BASIC_CONSTRAINTS, &[])?;
+
+        let mut len = [0u8; 8];
+        self.helper
+            .reader
+            .read_exact(&mut len)
+            .context("failed reading length of non basic constraints")?;
+        let len = usize::from_ne_bytes(len);
+
+        (0..len)
+            .map(|c| {
+                read_f64s(&mut self.helper.reader, self.dim)
+                    .with_context(|| format!("failed reading {}th non basic constraint", c))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::backend::tests::{
+        getting_non_basic_constraints, rational_solution, strange_lp_behavior,
+    };
+    use super::*;
+
+    #[test]
+    fn test_strange_lp_behavior() {
+        strange_lp_behavior::<SubprocessPreferenceLp>();
+    }
+
+    #[test]
+    fn test_rational_solution() {
+        rational_solution::<SubprocessPreferenceLp>();
+    }
+
+    #[test]
+    fn test_getting_non_basic_constraints() {
+        getting_non_basic_constraints::<SubprocessSizeApproxLp>();
+    }
+}
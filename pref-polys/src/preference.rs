@@ -6,22 +6,26 @@ use crate::{
 };
 use std::{cmp::Ordering, collections::VecDeque, convert::TryInto};
 
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use metered::metered;
+use num_traits::Signed;
 use ordered_float::OrderedFloat;
 
 use crate::{
-    float_eq,
-    graph::path::{costs_by_alpha, Path},
-    lp::{PreferenceLp, SizeApproxLp},
-    utils::{same_array, Costs, MyVec, Preference, SquareMatrix},
-    ACCURACY,
+    graph::dijkstra::{exact_shortest_costs, find_path, Dijkstra, QueryPool},
+    utils::{equal_weights, parallel::work_stealing_map},
 };
 use crate::{
-    graph::dijkstra::{find_path, Dijkstra},
-    utils::equal_weights,
+    graph::path::{costs_by_alpha, Path},
+    lp::{LpBackend, PreferenceLp, SizeApproxBackend, SizeApproxLp},
+    tolerance::Tolerance,
+    utils::{
+        number::{dot, Number, Rational},
+        same_array, Costs, MyVec, Preference, SquareMatrix,
+    },
+    ACCURACY,
 };
 
 /// `SetPreferences` calculates preferences for which a subsets of a
@@ -30,10 +34,13 @@ use crate::{
 pub struct SetPreferences<'d, 'p> {
     pub path_set: &'p [Path],
     dijkstra: Dijkstra<'d>,
+    pool: QueryPool<'d>,
     lp: PreferenceLp,
     constraints: Vec<Vec<Costs>>,
     inner_points: Vec<Vec<Preference>>,
     do_dijkstra: BitSet,
+    tolerance: Tolerance,
+    exact: bool,
 }
 lazy_static! {
     static ref SET_PREF_METRICS: SetPrefMetrics = Default::default();
@@ -82,22 +89,45 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
 
         Ok(SetPreferences {
             path_set,
+            pool: QueryPool::new(&dijkstra),
             dijkstra,
             lp,
             constraints,
             inner_points,
             do_dijkstra,
+            tolerance: Tolerance::EXACT,
+            exact: false,
         })
     }
 
+    /// Sets the tolerance for paths which are almost optimal. Cached
+    /// constraints were computed with the old tolerance and are dropped.
+    pub fn set_tolerance(&mut self, tolerance: Tolerance) {
+        if tolerance != self.tolerance {
+            self.constraints.iter_mut().for_each(Vec::clear);
+            self.inner_points.iter_mut().for_each(Vec::clear);
+        }
+        self.tolerance = tolerance;
+    }
+
+    pub fn tolerance(&self) -> Tolerance {
+        self.tolerance
+    }
+
+    /// Decides with rational arithmetic whether a preference exists, see
+    /// `PrefFinder::set_exact`.
+    pub fn set_exact(&mut self, exact: bool) {
+        self.exact = exact;
+    }
+
     /// Calculates a preference for a subset of paths are optimal. The items
     /// yielded by `subset_indices` are interpreted as indices into the path set
     /// of the instance. Any iterator which yields &usize can be used here. For example
     /// ```no_run
-    ///  # let graph = pref_covers::graph::Graph::new(vec![],vec![]);
-    ///  # let dijkstra =  pref_covers::graph::dijkstra::Dijkstra::new(&graph);
+    ///  # let graph = pref_polys::graph::Graph::new(vec![], vec![], vec![]);
+    ///  # let dijkstra =  pref_polys::graph::dijkstra::Dijkstra::new(&graph);
     ///  # let paths = Vec::new();
-    ///  # use pref_covers::preference::SetPreferences;
+    ///  # use pref_polys::preference::SetPreferences;
     ///  let mut set_pref = SetPreferences::new(dijkstra, &paths).unwrap();
     ///  let pref = set_pref.subset_preference([3,6,9].iter().copied());
     /// ```
@@ -106,14 +136,38 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
         &mut self,
         subset_indices: impl Iterator<Item = usize> + Clone,
     ) -> Result<Option<Preference>> {
-        let pref = self.yes_filter(subset_indices.clone());
+        let pref = self.yes_filter(subset_indices.clone())?;
 
         if pref.is_some() {
             return Ok(pref);
         }
 
+        Ok(self
+            .find_preference(subset_indices, false)?
+            .map(|robust| robust.preference))
+    }
+
+    /// Like `subset_preference`, but returns the preference with the largest
+    /// margin to the border of the preference space of the subset, see
+    /// `PrefFinder::max_margin_preference`.
+    pub fn subset_max_margin_preference(
+        &mut self,
+        subset_indices: impl Iterator<Item = usize> + Clone,
+    ) -> Result<Option<RobustPreference>> {
+        self.find_preference(subset_indices, true)
+    }
+
+    /// Runs the `PrefFinder` on the subset with the cached constraints and
+    /// caches the new ones.
+    fn find_preference(
+        &mut self,
+        subset_indices: impl Iterator<Item = usize> + Clone,
+        max_margin: bool,
+    ) -> Result<Option<RobustPreference>> {
         let mut pref_finder =
             PrefFinder::new(&mut self.lp, self.dijkstra.graph.dim.try_into().unwrap());
+        pref_finder.set_tolerance(self.tolerance);
+        pref_finder.set_exact(self.exact);
 
         let mut all_paths = std::mem::take(&mut self.path_set);
         let mut constraints = std::mem::take(&mut self.constraints);
@@ -129,11 +183,8 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
 
         let constr_iter = subset_indices.flat_map(|i| -> &[Costs] { &constraints[i] });
 
-        let res = pref_finder.constrained_multi_path_preference(
-            &mut self.dijkstra,
-            path_iter,
-            constr_iter,
-        );
+        let res =
+            pref_finder.find_preference(&mut self.dijkstra, path_iter, constr_iter, max_margin);
 
         let (pref, constr) = match res {
             Ok((pref, constr)) => (pref, constr),
@@ -144,17 +195,15 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
             }
         };
 
-        only_with_dijkstra
-            .zip(constr.into_iter())
-            .for_each(|(i, c)| {
-                constraints[i].extend(c);
-                constraints[i].sort_by(|a, b| {
-                    a.iter().zip(b.iter()).fold(Ordering::Equal, |acc, (a, b)| {
-                        acc.then(a.partial_cmp(b).unwrap())
-                    })
-                });
-                constraints[i].dedup_by(|a, b| same_array(a, b));
+        only_with_dijkstra.zip(constr).for_each(|(i, c)| {
+            constraints[i].extend(c);
+            constraints[i].sort_by(|a, b| {
+                a.iter().zip(b.iter()).fold(Ordering::Equal, |acc, (a, b)| {
+                    acc.then(a.partial_cmp(b).unwrap())
+                })
             });
+            constraints[i].dedup_by(|a, b| same_array(a, b));
+        });
 
         std::mem::swap(&mut self.path_set, &mut all_paths);
         std::mem::swap(&mut self.constraints, &mut constraints);
@@ -163,7 +212,10 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
     }
 
     #[measure(YesNoTime)]
-    fn yes_filter(&mut self, subset_indices: impl Iterator<Item = usize>) -> Option<Preference> {
+    fn yes_filter(
+        &mut self,
+        subset_indices: impl Iterator<Item = usize>,
+    ) -> Result<Option<Preference>> {
         let mut inner_filter_applicable = true;
         let mut chi = ConvexHullIntersection::new(self.path_set[0].total_dimension_costs.len());
         for i in subset_indices {
@@ -176,7 +228,7 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
         if inner_filter_applicable {
             return chi.solve();
         }
-        None
+        Ok(None)
     }
 
     pub fn approximate_pref_spaces(
@@ -184,39 +236,24 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
         directions: impl Iterator<Item = Preference> + Clone + Send,
     ) -> Result<Vec<SizeApproximation>> {
         let dim = self.dijkstra.graph.dim.try_into().unwrap();
-
-        let thread_count = num_cpus::get().min(self.path_set.len());
-        let item_per_thread = self.path_set.len() / thread_count;
-
-        let thread_res = crossbeam::scope(|scope| {
-            let chunks = self.path_set.chunks(item_per_thread);
-            let mut handles = Vec::new();
-
-            for chunk in chunks.into_iter() {
-                let directions = directions.clone();
-                let dijkstra = self.dijkstra.clone();
-                let handle = scope.spawn(move |_| {
-                    let mut dijkstra = dijkstra;
-                    let mut lp =
-                        SizeApproxLp::new(dim).expect("could no create size approximation LP");
-                    let mut approximator = PrefSizeApproximator::new(&mut lp, &mut dijkstra);
-
-                    chunk
-                        .iter()
-                        .map(|p| approximator.approx(p, directions.clone()).unwrap())
-                        .collect::<Vec<_>>()
-                });
-                handles.push(handle);
-            }
-
-            let mut inner_res = Vec::with_capacity(self.path_set.len());
-            for handle in handles {
-                inner_res.extend(handle.join().expect("Cannot join thread"))
-            }
-            inner_res
-        });
-
-        Ok(thread_res.expect("Threading for approximation failed"))
+        let pool = &self.pool;
+        let tolerance = self.tolerance;
+        let directions: Vec<_> = directions.collect();
+
+        work_stealing_map(
+            self.path_set,
+            || SizeApproxLp::new(dim).map(|lp| (pool.get(), lp)),
+            |state, p| {
+                let (dijkstra, lp) = state
+                    .as_mut()
+                    .map_err(|err| anyhow!("could not create size approximation LP: {:#}", err))?;
+                let mut approximator = PrefSizeApproximator::new(lp, dijkstra);
+                approximator.set_tolerance(tolerance);
+                approximator.approx(p, directions.iter().cloned())
+            },
+        )
+        .into_iter()
+        .collect()
     }
     pub fn constraints(&self, index: usize) -> &[Costs] {
         &self.constraints[index]
@@ -259,8 +296,14 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
         let mut points = Vec::new();
         while !candidates.is_empty() {
             let candidate = candidates.pop_front().unwrap();
-            let path_check = find_constraint_for_path(p, &mut self.dijkstra, &candidate.point);
-            if float_eq!(path_check.dif, 0.0) {
+            let path_check = find_constraint_for_path(
+                p,
+                &mut self.dijkstra,
+                &candidate.point,
+                self.tolerance,
+                false,
+            );
+            if path_check.dif <= ACCURACY {
                 points.push(candidate);
                 continue;
             }
@@ -307,12 +350,13 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
         &mut self,
         subset_indices: impl Iterator<Item = usize> + Clone,
     ) -> Result<Option<Preference>> {
-        let pref = self.yes_filter(subset_indices.clone());
+        let pref = self.yes_filter(subset_indices.clone())?;
         if pref.is_some() {
             return Ok(pref);
         }
         let mut pref_finder =
             PrefFinder::new(&mut self.lp, self.dijkstra.graph.dim.try_into().unwrap());
+        pref_finder.set_exact(self.exact);
 
         let mut constraints = std::mem::take(&mut self.constraints);
 
@@ -322,17 +366,147 @@ impl<'d, 'p> SetPreferences<'d, 'p> {
         std::mem::swap(&mut self.constraints, &mut constraints);
         res
     }
+
+    /// Explains why the paths of the subset have no common preference. Returns
+    /// `None` if they have one, otherwise a minimal subset of the paths
+    /// without a common preference and the constraints which certify this.
+    ///
+    /// Both are found by deletion filtering: a path, respectively
+    /// constraint, is left out if the rest still has no common preference.
+    /// Each proper subset of the returned paths has a preference, as far as
+    /// `subset_preference` can tell.
+    pub fn explain_infeasibility(
+        &mut self,
+        subset_indices: impl Iterator<Item = usize>,
+    ) -> Result<Option<Infeasibility>> {
+        let mut paths: Vec<usize> = subset_indices.collect();
+        if self.subset_preference(paths.iter().copied())?.is_some() {
+            return Ok(None);
+        }
+
+        let mut i = 0;
+        while i < paths.len() {
+            let removed = paths.remove(i);
+            if self.subset_preference(paths.iter().copied())?.is_some() {
+                paths.insert(i, removed);
+                i += 1;
+            }
+        }
+
+        let mut constraints: Vec<_> = paths
+            .iter()
+            .flat_map(|&p| self.constraints[p].iter().map(move |c| (p, c.clone())))
+            .collect();
+        ensure!(
+            !self.has_common_preference(&constraints)?,
+            "the constraints found for paths {:?} do not exclude all preferences",
+            paths
+        );
+
+        let mut i = 0;
+        while i < constraints.len() {
+            let removed = constraints.remove(i);
+            if self.has_common_preference(&constraints)? {
+                constraints.insert(i, removed);
+                i += 1;
+            }
+        }
+
+        Ok(Some(Infeasibility { paths, constraints }))
+    }
+
+    fn has_common_preference(&mut self, constraints: &[(usize, Costs)]) -> Result<bool> {
+        let mut pref_finder =
+            PrefFinder::new(&mut self.lp, self.dijkstra.graph.dim.try_into().unwrap());
+        pref_finder.set_exact(self.exact);
+        Ok(pref_finder
+            .constrained_multi_path_preference_lp_only(constraints.iter().map(|(_, c)| c))?
+            .is_some())
+    }
+}
+
+/// Why a set of paths has no common preference.
+#[derive(Debug, Clone)]
+pub struct Infeasibility {
+    /// Indices of paths without a common preference, all proper subsets have
+    /// one
+    pub paths: Vec<usize>,
+    /// Constraints of these paths, each with the index of its path, for which
+    /// no preference exists. They are the cost differences to alternative
+    /// routes, relaxed by the tolerance, and none of them can be left out.
+    pub constraints: Vec<(usize, Costs)>,
 }
 
-pub struct PrefFinder<'b> {
-    lp: &'b mut PreferenceLp,
+pub struct PrefFinder<'b, L: LpBackend = PreferenceLp> {
+    lp: &'b mut L,
     dim: usize,
+    tolerance: Tolerance,
+    exact: bool,
 }
 
-impl<'b> PrefFinder<'b> {
-    pub fn new(lp: &'b mut PreferenceLp, dim: usize) -> Self {
-        lp.reset().expect("Could not reset lp");
-        PrefFinder { lp, dim }
+impl<'b, L: LpBackend> PrefFinder<'b, L> {
+    pub fn new(lp: &'b mut L, dim: usize) -> Self {
+        lp.set_exact(false).expect("Could not reset lp");
+        PrefFinder {
+            lp,
+            dim,
+            tolerance: Tolerance::EXACT,
+            exact: false,
+        }
+    }
+
+    /// Paths count as optimal for a preference if they are within
+    /// `tolerance` of the optimum.
+    pub fn set_tolerance(&mut self, tolerance: Tolerance) {
+        self.tolerance = tolerance;
+    }
+
+    /// In exact mode the LP is solved with rationals and a path violates the
+    /// preference iff its constraint is negative in exact arithmetic, instead
+    /// of comparing with `ACCURACY`. The constraints are built with
+    /// `Tolerance::exact_constraint`. The shortest paths are searched with
+    /// the rounded preference. Unless a path is below the bound of the
+    /// tolerance by more than `ACCURACY` relative to the optimum, the optimum
+    /// is searched again with `exact_shortest_costs` for the exact preference.
+    ///
+    /// This is exact as long as the costs are integers, so that they add up
+    /// without rounding, and the costs of the routes found with the rounded
+    /// preference are off by less than `ACCURACY` relative to the optimum.
+    /// `max_margin_preference` ignores exact mode.
+    pub fn set_exact(&mut self, exact: bool) {
+        self.lp.set_exact(exact).expect("Could not reset lp");
+        self.exact = exact;
+    }
+
+    fn solve_lp(&mut self, repeating_constraints: bool, exact: bool) -> Result<LpResult> {
+        if exact {
+            return Ok(match self.lp.solve_rational()? {
+                Some(solution) if solution.delta.is_negative() => LpResult::Infeasible,
+                Some(solution) => {
+                    let pref = solution.preference.iter().map(Number::to_f64).collect();
+                    let delta = solution.delta.to_f64();
+                    LpResult::Preference(MyVec(pref), delta, Some(solution.preference))
+                }
+                None => LpResult::NoSolution,
+            });
+        }
+        Ok(match self.lp.solve(repeating_constraints)? {
+            Some((_, delta)) if delta + ACCURACY < 0.0 => LpResult::Infeasible,
+            Some((pref, delta)) => LpResult::Preference(pref, delta, None),
+            None => LpResult::NoSolution,
+        })
+    }
+
+    /// Adds a constraint to the LP, for the margin scaled with
+    /// `margin_constraint`.
+    fn add_lp_constraint(&mut self, constraint: &[f64], max_margin: bool) -> Result<()> {
+        if !max_margin {
+            return self.lp.add_constraint(constraint);
+        }
+        match margin_constraint(constraint) {
+            Some(scaled) => self.lp.add_constraint(&scaled),
+            None => Ok(()),
+        }
     }
 
     pub fn path_preference(
@@ -359,29 +533,87 @@ impl<'b> PrefFinder<'b> {
         paths: impl Iterator<Item = &'p Path> + Clone,
         constraints: impl Iterator<Item = &'p Costs>,
     ) -> Result<(Option<Preference>, Vec<Vec<Costs>>)> {
+        let (pref, constraints_by_path) =
+            self.find_preference(dijkstra, paths, constraints, false)?;
+        Ok((pref.map(|robust| robust.preference), constraints_by_path))
+    }
+
+    /// Finds the preference for which all paths are optimal and which lies
+    /// deepest inside their preference space: the center of the largest ball
+    /// in the plane of preferences summing up to 1 which fits between the
+    /// constraints and the borders of the simplex, together with its radius.
+    ///
+    /// The constraints are generated like in
+    /// `constrained_multi_path_preference`. Before the ball is returned, the
+    /// paths are also checked at its extreme points along every direction
+    /// `alpha_i - alpha_j`, and the constraints of cheaper routes found there
+    /// shrink the ball. Exact mode is ignored, the LP and the optimality
+    /// checks use floating point arithmetic.
+    pub fn max_margin_preference<'p>(
+        &mut self,
+        dijkstra: &mut Dijkstra,
+        paths: impl Iterator<Item = &'p Path> + Clone,
+        constraints: impl Iterator<Item = &'p Costs>,
+    ) -> Result<(Option<RobustPreference>, Vec<Vec<Costs>>)> {
+        self.find_preference(dijkstra, paths, constraints, true)
+    }
+
+    /// Alternates between solving the LP and adding constraints for paths
+    /// which are not optimal for its solution. With `max_margin` the
+    /// constraints are normalized, such that `delta` is the margin, otherwise
+    /// the returned margin is the unscaled `delta`.
+    fn find_preference<'p>(
+        &mut self,
+        dijkstra: &mut Dijkstra,
+        paths: impl Iterator<Item = &'p Path> + Clone,
+        constraints: impl Iterator<Item = &'p Costs>,
+        max_margin: bool,
+    ) -> Result<(Option<RobustPreference>, Vec<Vec<Costs>>)> {
         self.lp.reset().expect("LP Process could not be reset");
+        let exact = self.exact && !max_margin;
         let mut no_constraints = true;
+        if max_margin {
+            // alpha_i >= 0
+            for i in 0..self.dim {
+                let mut border = vec![0.0; self.dim];
+                border[i] = 1.0;
+                self.add_lp_constraint(&border, true)?;
+            }
+            no_constraints = false;
+        }
         for c in constraints {
             no_constraints = false;
-            self.lp.add_constraint(c)?;
+            self.add_lp_constraint(c, max_margin)?;
         }
 
         let mut constraints_by_path: Vec<Vec<Costs>> = vec![Vec::new(); paths.clone().count()];
 
         let mut alpha;
+        let mut delta;
+        let mut exact_alpha;
 
         let mut repeating_constraints = false;
         loop {
-            match self.lp.solve(repeating_constraints)? {
-                Some((pref, delta)) => {
-                    if delta + ACCURACY < 0.0 {
-                        return Ok((None, constraints_by_path));
-                    }
+            match self.solve_lp(repeating_constraints, exact)? {
+                LpResult::Preference(pref, d, exact) => {
                     alpha = pref;
+                    delta = d;
+                    exact_alpha = exact;
                 }
-                None => {
+                LpResult::Infeasible => return Ok((None, constraints_by_path)),
+                LpResult::NoSolution => {
                     if no_constraints {
-                        alpha = equal_weights(dijkstra.graph.dim.try_into().unwrap());
+                        let dim = dijkstra.graph.dim.try_into().unwrap();
+                        alpha = equal_weights(dim);
+                        delta = 0.0;
+                        exact_alpha = if exact {
+                            Some(vec![
+                                Rational::from_i64(1) / Rational::from_i64(dim as i64);
+                                dim
+                            ])
+                        } else {
+                            None
+                        };
                         no_constraints = false;
                     } else {
                         return Ok((None, constraints_by_path));
@@ -390,14 +622,44 @@ impl<'b> PrefFinder<'b> {
             }
             repeating_constraints = false;
 
-            let paths = paths.clone();
             let mut sum_dif = 0.0;
+            let mut any_violated = false;
             let mut no_constraints = true;
-            for (i, path) in paths.enumerate() {
-                let res = find_constraint_for_path(path, dijkstra, &alpha);
-                sum_dif += res.dif;
-                if !float_eq!(res.dif, 0.0) {
-                    self.lp.add_constraint(&res.constraint)?;
+            for (i, path) in paths.clone().enumerate() {
+                let mut res = find_constraint_for_path(
+                    path,
+                    dijkstra,
+                    &alpha,
+                    self.tolerance,
+                    exact_alpha.is_some(),
+                );
+                // paths within the tolerance may be cheaper than the bound,
+                // which must not hide the excess of other paths
+                sum_dif += res.dif.max(0.0);
+                let violated = match &exact_alpha {
+                    Some(exact) => {
+                        if !dot(&res.constraint, exact).is_negative()
+                            && res.dif > -ACCURACY * res.optimal_cost.abs().max(1.0)
+                        {
+                            // The route was found with the rounded preference,
+                            // one which is cheaper for the exact one may have
+                            // been missed
+                            let (source, target) = (path.nodes[0], *path.nodes.last().unwrap());
+                            if let Some(costs) =
+                                exact_shortest_costs(dijkstra.graph, source, target, exact)
+                            {
+                                res.constraint = self
+                                    .tolerance
+                                    .exact_constraint(&path.total_dimension_costs, &costs);
+                            }
+                        }
+                        dot(&res.constraint, exact).is_negative()
+                    }
+                    None => res.dif > ACCURACY,
+                };
+                if violated {
+                    any_violated = true;
+                    self.add_lp_constraint(&res.constraint, max_margin)?;
                     if constraints_by_path[i]
                         .last()
                         .map_or(false, |l| same_array(l, &res.constraint))
@@ -412,14 +674,76 @@ impl<'b> PrefFinder<'b> {
             if repeating_constraints {
                 continue;
             }
-            if sum_dif - ACCURACY <= 0.0 {
-                return Ok((Some(alpha), constraints_by_path));
+            let optimal = if exact_alpha.is_some() {
+                !any_violated
+            } else {
+                sum_dif - ACCURACY <= 0.0
+            };
+            if optimal {
+                if max_margin
+                    && self.check_margin(
+                        dijkstra,
+                        paths.clone(),
+                        &alpha,
+                        delta,
+                        &mut constraints_by_path,
+                    )?
+                {
+                    continue;
+                }
+                let robust = RobustPreference {
+                    preference: alpha,
+                    margin: delta.max(0.0),
+                };
+                return Ok((Some(robust), constraints_by_path));
             } else if no_constraints {
                 return Ok((None, constraints_by_path));
             }
         }
     }
 
+    /// Checks the paths at the points of the ball around `alpha` with radius
+    /// `margin` which are farthest along the directions `alpha_i - alpha_j`.
+    /// Constraints of routes which are cheaper there are added to the LP.
+    /// Returns whether any constraint was added.
+    fn check_margin<'p>(
+        &mut self,
+        dijkstra: &mut Dijkstra,
+        paths: impl Iterator<Item = &'p Path> + Clone,
+        alpha: &Preference,
+        margin: f64,
+        constraints_by_path: &mut [Vec<Costs>],
+    ) -> Result<bool> {
+        if margin < ACCURACY {
+            return Ok(false);
+        }
+        // (e_i - e_j) / sqrt(2) is a unit vector in the plane of preferences
+        let step = margin / 2.0f64.sqrt();
+        let mut added = false;
+        for (i, j) in (0..self.dim).cartesian_product(0..self.dim) {
+            if i == j {
+                continue;
+            }
+            let mut point = alpha.clone();
+            point[i] += step;
+            // the ball touches the border of the simplex at most
+            point[j] = (point[j] - step).max(0.0);
+            for (k, path) in paths.clone().enumerate() {
+                let res = find_constraint_for_path(path, dijkstra, &point, self.tolerance, false);
+                if res.dif > ACCURACY
+                    && !constraints_by_path[k]
+                        .iter()
+                        .any(|c| same_array(c, &res.constraint))
+                {
+                    self.add_lp_constraint(&res.constraint, true)?;
+                    constraints_by_path[k].push(res.constraint);
+                    added = true;
+                }
+            }
+        }
+        Ok(added)
+    }
+
     pub fn constrained_multi_path_preference_lp_only<'p>(
         &mut self,
         constraints: impl Iterator<Item = &'p Costs>,
@@ -436,29 +760,72 @@ impl<'b> PrefFinder<'b> {
         if no_constraints {
             return Ok(Some(equal_weights(self.dim)));
         }
-        match self.lp.solve(false)? {
-            Some((pref, delta)) => {
-                if delta + ACCURACY < 0.0 {
-                    Ok(None)
-                } else {
-                    Ok(Some(pref))
-                }
-            }
-            None => Ok(None),
+        match self.solve_lp(false, self.exact)? {
+            LpResult::Preference(pref, _, _) => Ok(Some(pref)),
+            LpResult::Infeasible | LpResult::NoSolution => Ok(None),
         }
     }
 }
 
+/// A preference together with its distance to the border of the preference
+/// space.
+#[derive(Debug, Clone)]
+pub struct RobustPreference {
+    pub preference: Preference,
+    /// Euclidean distance to the closest constraint or border of the simplex,
+    /// measured in the plane of preferences summing up to 1. The paths are
+    /// checked at the center and the extreme points of the ball, a route
+    /// which is only cheaper somewhere else inside the ball can still be
+    /// missed.
+    pub margin: f64,
+}
+
+/// Scales `constraint` such that `alpha * constraint` is the signed distance
+/// of `alpha` to the hyperplane where the constraint is tight, for `alpha`
+/// summing up to 1. Only the part of the constraint orthogonal to `(1, ...,
+/// 1)` moves within that plane, so its norm is the scale. Returns `None` for
+/// constraints which hold for all preferences.
+fn margin_constraint(constraint: &[f64]) -> Option<Costs> {
+    let mean = constraint.iter().sum::<f64>() / constraint.len() as f64;
+    let norm = constraint
+        .iter()
+        .map(|c| (c - mean) * (c - mean))
+        .sum::<f64>()
+        .sqrt();
+    if norm < ACCURACY {
+        // the constraint is the same for all preferences
+        return if mean < 0.0 {
+            Some(MyVec(constraint.to_vec()))
+        } else {
+            None
+        };
+    }
+    Some(MyVec(constraint.iter().map(|c| c / norm).collect()))
+}
+
+enum LpResult {
+    /// A preference satisfying all constraints with `delta`, in exact mode
+    /// also with rationals
+    Preference(Preference, f64, Option<Vec<Rational>>),
+    Infeasible,
+    NoSolution,
+}
+
 #[derive(Debug)]
 struct PathCheckResult {
     dif: f64,
+    optimal_cost: f64,
     constraint: Costs,
 }
 
+/// Compares `path` to the route found by the Dijkstra for `alpha`. With
+/// `exact` the constraint is built with `Tolerance::exact_constraint`.
 fn find_constraint_for_path(
     path: &Path,
     dijkstra: &mut Dijkstra,
     alpha: &[f64],
+    tolerance: Tolerance,
+    exact: bool,
 ) -> PathCheckResult {
     let result = find_path(
         dijkstra,
@@ -467,25 +834,28 @@ fn find_constraint_for_path(
     )
     .unwrap();
 
-    let dif = costs_by_alpha(&path.total_dimension_costs, alpha)
-        - costs_by_alpha(&result.total_dimension_costs, alpha);
-
-    let cost_dif = result
-        .total_dimension_costs
-        .iter()
-        .zip(path.total_dimension_costs.iter())
-        .map(|(r, p)| r - p)
-        .collect::<Vec<_>>();
+    let optimal_cost = costs_by_alpha(&result.total_dimension_costs, alpha);
+    let dif = tolerance.excess(
+        costs_by_alpha(&path.total_dimension_costs, alpha),
+        optimal_cost,
+    );
+    let constraint = if exact {
+        tolerance.exact_constraint(&path.total_dimension_costs, &result.total_dimension_costs)
+    } else {
+        tolerance.constraint(&path.total_dimension_costs, &result.total_dimension_costs)
+    };
 
     PathCheckResult {
         dif,
-        constraint: cost_dif.into(),
+        optimal_cost,
+        constraint,
     }
 }
 
-pub struct PrefSizeApproximator<'b, 'g, 'd> {
-    lp: &'b mut SizeApproxLp,
+pub struct PrefSizeApproximator<'b, 'g, 'd, L: SizeApproxBackend = SizeApproxLp> {
+    lp: &'b mut L,
     dijkstra: &'d mut Dijkstra<'g>,
+    tolerance: Tolerance,
 }
 
 #[derive(Debug, Clone)]
@@ -582,11 +952,22 @@ impl ApproxPoint {
 }
 
 #[metered(registry = ApproxMetrics, registry_expr = APPROX_METRICS )]
-impl<'d, 'b, 'g> PrefSizeApproximator<'b, 'g, 'd> {
-    pub fn new(lp: &'b mut SizeApproxLp, dijkstra: &'d mut Dijkstra<'g>) -> Self {
+impl<'d, 'b, 'g, L: SizeApproxBackend> PrefSizeApproximator<'b, 'g, 'd, L> {
+    pub fn new(lp: &'b mut L, dijkstra: &'d mut Dijkstra<'g>) -> Self {
         lp.reset().expect("Could not reset lp");
-        PrefSizeApproximator { lp, dijkstra }
+        PrefSizeApproximator {
+            lp,
+            dijkstra,
+            tolerance: Tolerance::EXACT,
+        }
+    }
+
+    /// Paths count as optimal for a preference if they are within
+    /// `tolerance` of the optimum.
+    pub fn set_tolerance(&mut self, tolerance: Tolerance) {
+        self.tolerance = tolerance;
     }
+
     #[measure(SimpleTime)]
     pub fn constrained_approx(
         &mut self,
@@ -626,8 +1007,9 @@ impl<'d, 'b, 'g> PrefSizeApproximator<'b, 'g, 'd> {
                 };
                 repeating_constraints = false;
 
-                let res = find_constraint_for_path(path, &mut self.dijkstra, &alpha);
-                if float_eq!(res.dif, 0.0) {
+                let res =
+                    find_constraint_for_path(path, self.dijkstra, &alpha, self.tolerance, false);
+                if res.dif <= ACCURACY {
                     inner_points.push(alpha);
                     point_constraints.push(self.lp.non_basic_constraints()?);
                     break;
@@ -696,6 +1078,7 @@ fn rotation_matrix(dim: usize, axis1: usize, axis2: usize, angle: f64) -> Square
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::float_eq;
     use crate::graph::dijkstra::find_shortest_path;
 
     use crate::graph::parse_minimal_graph_file;
@@ -730,20 +1113,25 @@ mod tests {
         }
     }
 
+    /// The paths from 0 to 1 in `simple_pref_cover_test_2` which are optimal
+    /// for a single metric each. They have no common preference.
+    fn unit_preference_paths(dijkstra: &mut Dijkstra) -> Vec<Path> {
+        (0..4)
+            .map(|i| {
+                let mut alpha = [0.0; 4];
+                alpha[i] = 1.0;
+                find_shortest_path(dijkstra, &[0, 1], &alpha).unwrap()
+            })
+            .collect()
+    }
+
     #[test]
     fn test_four_paths_without_common_pref() {
-        use crate::graph::parse_minimal_graph_file;
         use crate::utils::{BitSet, BitSetFns};
 
         let graph = parse_minimal_graph_file("resources/simple_pref_cover_test_2").unwrap();
         let mut d = Dijkstra::new(&graph);
-
-        let paths = vec![
-            find_shortest_path(&mut d, &[0, 1], &[1., 0., 0., 0.]).unwrap(),
-            find_shortest_path(&mut d, &[0, 1], &[0., 1., 0., 0.]).unwrap(),
-            find_shortest_path(&mut d, &[0, 1], &[0., 0., 1., 0.]).unwrap(),
-            find_shortest_path(&mut d, &[0, 1], &[0., 0., 0., 1.]).unwrap(),
-        ];
+        let paths = unit_preference_paths(&mut d);
 
         let mut set_pref = SetPreferences::new(d, &paths).unwrap();
 
@@ -758,6 +1146,188 @@ mod tests {
         assert!(option_pref.is_none());
     }
 
+    #[test]
+    fn test_explain_infeasibility() {
+        let graph = parse_minimal_graph_file("resources/simple_pref_cover_test_2").unwrap();
+        let mut d = Dijkstra::new(&graph);
+        let paths = unit_preference_paths(&mut d);
+
+        let mut set_pref = SetPreferences::new(d, &paths).unwrap();
+        assert!(set_pref
+            .explain_infeasibility(std::iter::once(0))
+            .unwrap()
+            .is_none());
+
+        let explanation = set_pref.explain_infeasibility(0..4).unwrap().unwrap();
+        let conflict = &explanation.paths;
+        assert!(conflict.len() > 1);
+        assert!(set_pref
+            .subset_preference(conflict.iter().copied())
+            .unwrap()
+            .is_none());
+        for i in 0..conflict.len() {
+            let mut rest = conflict.clone();
+            rest.remove(i);
+            assert!(set_pref
+                .subset_preference(rest.into_iter())
+                .unwrap()
+                .is_some());
+        }
+
+        let constraints = &explanation.constraints;
+        assert!(constraints.iter().all(|(p, _)| conflict.contains(p)));
+        assert!(!set_pref.has_common_preference(constraints).unwrap());
+        for i in 0..constraints.len() {
+            let mut rest = constraints.clone();
+            rest.remove(i);
+            assert!(set_pref.has_common_preference(&rest).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_margin_constraint() {
+        let border = margin_constraint(&[1.0, 0.0, 0.0]).unwrap();
+        let center = equal_weights(3);
+        assert!(float_eq!(
+            costs_by_alpha(&border, &center),
+            1.0 / 6.0f64.sqrt()
+        ));
+        // moving along the border does not change the distance
+        let on_border = [0.0, 0.25, 0.75];
+        assert!(float_eq!(costs_by_alpha(&border, &on_border), 0.0));
+
+        assert!(margin_constraint(&[2.0, 2.0, 2.0]).is_none());
+        assert!(margin_constraint(&[-2.0, -2.0, -2.0]).is_some());
+    }
+
+    #[test]
+    fn test_max_margin_preference() {
+        let graph = parse_minimal_graph_file("resources/simple_pref_cover_test_2").unwrap();
+        let mut d = Dijkstra::new(&graph);
+        let paths = unit_preference_paths(&mut d);
+
+        let mut set_pref = SetPreferences::new(d, &paths).unwrap();
+        for (i, path) in paths.iter().enumerate() {
+            let robust = set_pref
+                .subset_max_margin_preference(std::iter::once(i))
+                .unwrap()
+                .unwrap();
+            assert!(robust.margin > 0.0);
+
+            // the path stays optimal when moving the preference by less
+            // than the margin
+            for (j, k) in (0..4).tuple_combinations() {
+                for &sign in &[-1.0, 1.0] {
+                    let step = sign * 0.99 * robust.margin / 2.0f64.sqrt();
+                    let mut pref = robust.preference.clone();
+                    pref[j] += step;
+                    pref[k] -= step;
+                    assert!(pref.iter().all(|&p| p >= 0.0));
+
+                    let optimal =
+                        find_shortest_path(&mut set_pref.dijkstra, &[0, 1], &pref).unwrap();
+                    assert!(float_eq!(
+                        costs_by_alpha(&path.total_dimension_costs, &pref),
+                        costs_by_alpha(&optimal.total_dimension_costs, &pref)
+                    ));
+                }
+            }
+        }
+        assert!(set_pref
+            .subset_max_margin_preference(0..4)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_max_margin_checks_extreme_points() {
+        // The direct edge from 0 to 1 is optimal for 0.4 <= alpha_0 <= 0.6.
+        // At the center of the simplex both detours are more expensive.
+        let graph = crate::graph::parse_minimal_graph(
+            "2\na b\n4\n5\n0 0\n1 0\n2 0\n3 0\n\
+             0 0 1 2 2 -1 -1\n1 0 2 0 2.5 -1 -1\n2 2 1 0 2.5 -1 -1\n\
+             3 0 3 2.5 0 -1 -1\n4 3 1 2.5 0 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let paths = vec![Path {
+            nodes: MyVec(vec![0, 1]),
+            edges: MyVec(vec![0]),
+            total_dimension_costs: MyVec(vec![2.0, 2.0]),
+        }];
+        let mut set_pref = SetPreferences::new(Dijkstra::new(&graph), &paths).unwrap();
+        let robust = set_pref
+            .subset_max_margin_preference(0..1)
+            .unwrap()
+            .unwrap();
+        assert!(same_array(&robust.preference, &[0.5, 0.5]));
+        assert!(float_eq!(robust.margin, 0.1 * 2.0f64.sqrt()));
+    }
+
+    #[test]
+    fn test_exact_subset_preference() {
+        let graph = parse_minimal_graph_file("resources/simple_pref_cover_test_2").unwrap();
+        let mut d = Dijkstra::new(&graph);
+        let paths = unit_preference_paths(&mut d);
+
+        let mut set_pref = SetPreferences::new(d, &paths).unwrap();
+        set_pref.set_exact(true);
+
+        for (i, path) in paths.iter().enumerate() {
+            let pref = set_pref
+                .subset_preference(std::iter::once(i))
+                .unwrap()
+                .unwrap();
+            let optimal = find_shortest_path(&mut set_pref.dijkstra, &[0, 1], &pref).unwrap();
+            assert!(float_eq!(
+                costs_by_alpha(&path.total_dimension_costs, &pref),
+                costs_by_alpha(&optimal.total_dimension_costs, &pref)
+            ));
+        }
+        assert!(set_pref.subset_preference(0..4).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_exact_and_float_decisions_differ() {
+        // The direct edge from 0 to 1 is slightly more expensive than the
+        // route over 2 for every preference, by less than `ACCURACY`
+        let graph = crate::graph::parse_minimal_graph(
+            "2\na b\n3\n3\n0 0\n1 0\n2 0\n\
+             0 0 1 1.0000001 1.0000001 -1 -1\n1 0 2 0.5 0.5 -1 -1\n2 2 1 0.5 0.5 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let paths = vec![Path {
+            nodes: MyVec(vec![0, 1]),
+            edges: MyVec(vec![0]),
+            total_dimension_costs: MyVec(vec![1.0000001, 1.0000001]),
+        }];
+        let mut set_pref = SetPreferences::new(Dijkstra::new(&graph), &paths).unwrap();
+        assert!(set_pref.subset_preference(0..1).unwrap().is_some());
+        set_pref.set_exact(true);
+        assert!(set_pref.subset_preference(0..1).unwrap().is_none());
+
+        // The constraints of `strange_lp_behavior` and one which forbids the
+        // third metric, but only in exact arithmetic. Then the first one
+        // forbids the first two metrics and the second one the last.
+        let constraints: Vec<Costs> = vec![
+            vec![-0.0638948999999998, -1.106574, 1.11022302462516e-16, 0.0].into(),
+            vec![0.9163051, 1.258436, 0.8960761, -1.0].into(),
+            vec![0.0, 0.0, -2.2e-16, 0.0].into(),
+        ];
+        let mut lp = PreferenceLp::new(4).unwrap();
+        let mut finder = PrefFinder::new(&mut lp, 4);
+        assert!(finder
+            .constrained_multi_path_preference_lp_only(constraints.iter())
+            .unwrap()
+            .is_some());
+        finder.set_exact(true);
+        assert!(finder
+            .constrained_multi_path_preference_lp_only(constraints.iter())
+            .unwrap()
+            .is_none());
+    }
+
     #[test]
     fn test_exact_pref_space() {
         let graph = parse_minimal_graph_file("resources/lp_only_test_graph").unwrap();
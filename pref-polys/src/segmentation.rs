@@ -0,0 +1,501 @@
+//! Splitting trajectories into subpaths which are optimal for a preference.
+//!
+//! Recorded trajectories are rarely optimal for a single preference from
+//! start to end, e.g. because the driver stopped somewhere on the way. Every
+//! subpath of an optimal path is optimal for the same preference, so such a
+//! trajectory can still be described by a few optimal segments.
+//!
+//! Segments are given by split points, which are indices into `path.nodes`.
+//! Consecutive segments share the node at their split point.
+
+use anyhow::Result;
+
+use crate::{
+    graph::{
+        dijkstra::Dijkstra,
+        path::{costs_by_alpha, Path},
+        Graph,
+    },
+    lp::LpBackend,
+    preference::PrefFinder,
+    utils::{equal_weights, Preference},
+    ACCURACY,
+};
+
+/// Segmentation of a path where every segment is optimal for the same
+/// preference.
+#[derive(Debug, Clone)]
+pub struct CommonSegmentation {
+    /// Indices of the nodes at which a new segment starts, without the first
+    /// and the last node of the path
+    pub split_points: Vec<u32>,
+    pub preference: Preference,
+}
+
+/// Segmentation of a path where every segment has its own preference.
+#[derive(Debug, Clone)]
+pub struct Segmentation {
+    /// Indices of the nodes at which a new segment starts, without the first
+    /// and the last node of the path
+    pub split_points: Vec<u32>,
+    /// One preference per segment
+    pub preferences: Vec<Preference>,
+}
+
+impl CommonSegmentation {
+    pub fn segments(&self, graph: &Graph, path: &Path) -> Vec<Path> {
+        segments(graph, path, &self.split_points)
+    }
+}
+
+impl Segmentation {
+    pub fn segments(&self, graph: &Graph, path: &Path) -> Vec<Path> {
+        segments(graph, path, &self.split_points)
+    }
+}
+
+/// Splits `path` at the split points, the segments share their end nodes.
+pub fn segments(graph: &Graph, path: &Path, split_points: &[u32]) -> Vec<Path> {
+    let last = path.nodes.len() as u32;
+    std::iter::once(0)
+        .chain(split_points.iter().copied())
+        .zip(
+            split_points
+                .iter()
+                .copied()
+                .chain(std::iter::once(last - 1)),
+        )
+        .map(|(start, end)| path.get_subpath(graph, start, end + 1))
+        .collect()
+}
+
+/// Largest `end` in `start + 1..=last` for which `fits` holds, or `None` if
+/// not even the segment with a single edge fits. Since subpaths of a fitting
+/// segment fit as well, the end can be found by binary search.
+fn longest_segment(
+    start: usize,
+    last: usize,
+    mut fits: impl FnMut(usize) -> Result<bool>,
+) -> Result<Option<usize>> {
+    if !fits(start + 1)? {
+        return Ok(None);
+    }
+    let (mut low, mut high) = (start + 1, last);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if fits(mid)? {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(Some(low))
+}
+
+/// Splits `path` into the fewest segments which are optimal for `alpha`.
+/// Returns `None` if a single edge of the path is not optimal for `alpha`.
+///
+/// Extending every segment as far as possible gives the minimal number of
+/// segments, since a segment that ends later never forces more segments
+/// afterwards.
+pub fn split_for_preference(
+    dijkstra: &mut Dijkstra,
+    path: &Path,
+    alpha: &[f64],
+) -> Option<Vec<u32>> {
+    let graph = dijkstra.graph;
+    let mut prefix_costs = vec![vec![0.0; graph.dim as usize]];
+    for &edge in path.edges.iter() {
+        let mut costs = prefix_costs.last().unwrap().clone();
+        crate::graph::path::add_edge_costs(&mut costs, &graph.edges[edge].edge_costs);
+        prefix_costs.push(costs);
+    }
+
+    let mut is_optimal = |start: usize, end: usize| {
+        let costs: Vec<f64> = prefix_costs[end]
+            .iter()
+            .zip(&prefix_costs[start])
+            .map(|(e, s)| e - s)
+            .collect();
+        let cost = costs_by_alpha(&costs, alpha);
+        match dijkstra.run(path.nodes[start], path.nodes[end], alpha) {
+            Some(result) => cost - result.total_cost <= ACCURACY,
+            None => false,
+        }
+    };
+
+    let last = path.nodes.len().saturating_sub(1);
+    let mut split_points = Vec::new();
+    let mut start = 0;
+    while start < last {
+        let end = longest_segment(start, last, |end| Ok(is_optimal(start, end))).unwrap()?;
+        if end < last {
+            split_points.push(end as u32);
+        }
+        start = end;
+    }
+    Some(split_points)
+}
+
+/// Splits `path` into segments which are each optimal for their own
+/// preference. The number of segments is minimal, and it is a lower bound for
+/// `segment_with_common_preference`. Returns `None` if a single edge of the
+/// path is not optimal for any preference.
+pub fn segment_with_individual_preferences(
+    lp: &mut impl LpBackend,
+    dijkstra: &mut Dijkstra,
+    path: &Path,
+) -> Result<Option<Segmentation>> {
+    let graph = dijkstra.graph;
+    let mut finder = PrefFinder::new(lp, graph.dim as usize);
+
+    let last = path.nodes.len().saturating_sub(1);
+    let mut segmentation = Segmentation {
+        split_points: Vec::new(),
+        preferences: Vec::new(),
+    };
+    let mut start = 0;
+    while start < last {
+        let mut fits = |end: usize| -> Result<bool> {
+            let segment = path.get_subpath(graph, start as u32, end as u32 + 1);
+            Ok(finder.path_preference(dijkstra, &segment)?.is_some())
+        };
+        let end = match longest_segment(start, last, &mut fits)? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let segment = path.get_subpath(graph, start as u32, end as u32 + 1);
+        let preference = finder
+            .path_preference(dijkstra, &segment)?
+            .expect("the segment was optimal before");
+        segmentation.preferences.push(preference);
+        if end < last {
+            segmentation.split_points.push(end as u32);
+        }
+        start = end;
+    }
+    if segmentation.preferences.is_empty() {
+        // a path without edges is optimal for every preference
+        segmentation
+            .preferences
+            .push(equal_weights(graph.dim as usize));
+    }
+    Ok(Some(segmentation))
+}
+
+/// Splits `path` into the fewest segments which are all optimal for one
+/// common preference. Returns `None` if no preference exists for which every
+/// single edge of the path is optimal.
+///
+/// A long first segment may rule out the preference that suits the rest of
+/// the path, so the segments can not be chosen greedily. Instead, the number
+/// of segments is found by binary search between the number of segments of
+/// `segment_with_individual_preferences` and the best segmentation of
+/// `split_for_preference` for a few candidate preferences. Each step searches
+/// all split points with backtracking, which is exponential in the worst case.
+pub fn segment_with_common_preference(
+    lp: &mut impl LpBackend,
+    dijkstra: &mut Dijkstra,
+    path: &Path,
+) -> Result<Option<CommonSegmentation>> {
+    let graph = dijkstra.graph;
+    let individual = match segment_with_individual_preferences(lp, dijkstra, path)? {
+        Some(individual) => individual,
+        None => return Ok(None),
+    };
+    if individual.preferences.len() == 1 {
+        return Ok(Some(CommonSegmentation {
+            split_points: Vec::new(),
+            preference: individual.preferences[0].clone(),
+        }));
+    }
+
+    // Every segmentation with a common preference also works with one edge
+    // per segment, so this decides whether there is any
+    let mut finder = PrefFinder::new(lp, graph.dim as usize);
+    let last = path.nodes.len() - 1;
+    let edges: Vec<Path> = (0..last)
+        .map(|i| path.get_subpath(graph, i as u32, i as u32 + 2))
+        .collect();
+    let all_edges = match finder.multi_path_preference(dijkstra, &edges)? {
+        Some(preference) => preference,
+        None => return Ok(None),
+    };
+
+    let mut best = CommonSegmentation {
+        split_points: (1..last as u32).collect(),
+        preference: all_edges.clone(),
+    };
+    for preference in std::iter::once(all_edges).chain(individual.preferences) {
+        if let Some(split_points) = split_for_preference(dijkstra, path, &preference) {
+            if split_points.len() < best.split_points.len() {
+                best = CommonSegmentation {
+                    split_points,
+                    preference,
+                };
+            }
+        }
+    }
+
+    // The best segmentation so far has `high` segments, none exists with
+    // fewer than `low`
+    let mut low = individual.split_points.len() + 1;
+    let mut high = best.split_points.len() + 1;
+    while low < high {
+        let mid = (low + high) / 2;
+        let mut search = SplitSearch {
+            finder: &mut finder,
+            dijkstra,
+            path,
+            last,
+            max_segments: mid,
+            segments: Vec::new(),
+            split_points: Vec::new(),
+        };
+        match search.run(0)? {
+            Some(preference) => {
+                high = search.split_points.len() + 1;
+                best = CommonSegmentation {
+                    split_points: search.split_points,
+                    preference,
+                };
+            }
+            None => low = mid + 1,
+        }
+    }
+    Ok(Some(best))
+}
+
+/// Backtracking search for a segmentation with at most `max_segments`
+/// segments and a common preference.
+struct SplitSearch<'a, 'b, 'g, L: LpBackend> {
+    finder: &'a mut PrefFinder<'b, L>,
+    dijkstra: &'a mut Dijkstra<'g>,
+    path: &'a Path,
+    last: usize,
+    max_segments: usize,
+    segments: Vec<Path>,
+    split_points: Vec<u32>,
+}
+
+impl<L: LpBackend> SplitSearch<'_, '_, '_, L> {
+    /// Tries all ends of the segment starting at `start`, longest first.
+    /// Returns the common preference once the path is covered, with the
+    /// segments and split points left in place.
+    fn run(&mut self, start: usize) -> Result<Option<Preference>> {
+        let graph = self.dijkstra.graph;
+        let last = self.last;
+        let mut fits = |end: usize| -> Result<bool> {
+            self.segments
+                .push(self.path.get_subpath(graph, start as u32, end as u32 + 1));
+            let fits = self
+                .finder
+                .multi_path_preference(self.dijkstra, &self.segments)?
+                .is_some();
+            self.segments.pop();
+            Ok(fits)
+        };
+        let longest = match longest_segment(start, last, &mut fits)? {
+            Some(longest) => longest,
+            None => return Ok(None),
+        };
+        // The last allowed segment has to reach the end of the path
+        let shortest = if self.segments.len() + 1 == self.max_segments {
+            self.last
+        } else {
+            start + 1
+        };
+        for end in (shortest..=longest).rev() {
+            self.segments
+                .push(self.path.get_subpath(graph, start as u32, end as u32 + 1));
+            if end == self.last {
+                return self
+                    .finder
+                    .multi_path_preference(self.dijkstra, &self.segments);
+            }
+            self.split_points.push(end as u32);
+            if let Some(preference) = self.run(end)? {
+                return Ok(Some(preference));
+            }
+            self.split_points.pop();
+            self.segments.pop();
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{dijkstra::find_shortest_path, parse_graph_file, parse_minimal_graph};
+    use crate::lp::PreferenceLp;
+    use crate::utils::MyVec;
+
+    /// A path from `s` over `v` to `t` which is not optimal for `alpha`, made
+    /// of two paths which are.
+    fn detour(dijkstra: &mut Dijkstra, alpha: &[f64]) -> Option<Path> {
+        let n = dijkstra.graph.nodes.len() as u32;
+        for (s, v, t) in itertools::iproduct!(0..n, 0..n, 0..n) {
+            if s == v || v == t {
+                continue;
+            }
+            let direct = match find_shortest_path(dijkstra, &[s, t], alpha) {
+                Some(direct) => direct,
+                None => continue,
+            };
+            let path = match find_shortest_path(dijkstra, &[s, v, t], alpha) {
+                Some(path) => path,
+                None => continue,
+            };
+            let cost = costs_by_alpha(&path.total_dimension_costs, alpha);
+            if cost > costs_by_alpha(&direct.total_dimension_costs, alpha) + 1.0 {
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_split_for_preference() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let alpha = equal_weights(graph.dim as usize);
+
+        let path = detour(&mut dijkstra, &alpha).expect("graph should contain a detour");
+        let split_points = split_for_preference(&mut dijkstra, &path, &alpha).unwrap();
+        assert_eq!(split_points.len(), 1);
+
+        for segment in segments(&graph, &path, &split_points) {
+            assert!(segment.validate(&graph).is_empty());
+            assert!(split_for_preference(&mut dijkstra, &segment, &alpha)
+                .unwrap()
+                .is_empty());
+        }
+
+        let segments = segments(&graph, &path, &split_points);
+        assert_eq!(segments[0].nodes.first(), path.nodes.first());
+        assert_eq!(segments[1].nodes.last(), path.nodes.last());
+        assert_eq!(segments[0].nodes.last(), segments[1].nodes.first());
+    }
+
+    #[test]
+    fn test_segmentation_with_lp() {
+        let graph = parse_graph_file("./resources/concTestGraph").unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let mut lp = PreferenceLp::new(graph.dim as usize).unwrap();
+        let alpha = equal_weights(graph.dim as usize);
+        let path = detour(&mut dijkstra, &alpha).expect("graph should contain a detour");
+
+        let individual = segment_with_individual_preferences(&mut lp, &mut dijkstra, &path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            individual.preferences.len(),
+            individual.split_points.len() + 1
+        );
+        for (segment, pref) in individual
+            .segments(&graph, &path)
+            .iter()
+            .zip(&individual.preferences)
+        {
+            assert!(split_for_preference(&mut dijkstra, segment, pref)
+                .unwrap()
+                .is_empty());
+        }
+
+        let common = segment_with_common_preference(&mut lp, &mut dijkstra, &path)
+            .unwrap()
+            .unwrap();
+        assert!(common.split_points.len() >= individual.split_points.len());
+        assert!(common.split_points.len() <= 1);
+        for segment in common.segments(&graph, &path) {
+            assert!(
+                split_for_preference(&mut dijkstra, &segment, &common.preference)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn test_disjoint_preference_regions() {
+        // 0 -> 1 is only optimal for alpha[1] <= 0.4 because of the detour
+        // over 3, and 1 -> 2 only for alpha[1] >= 0.6 because of the detour
+        // over 4
+        let graph = parse_minimal_graph(
+            "2\na b\n5\n6\n0 0\n1 0\n2 0\n3 0\n4 0\n\
+             0 0 1 2 3 -1 -1\n1 1 2 3 2 -1 -1\n2 0 3 2 0 -1 -1\n\
+             3 3 1 2 0 -1 -1\n4 1 4 0 2 -1 -1\n5 4 2 0 2 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let mut lp = PreferenceLp::new(graph.dim as usize).unwrap();
+        let path = Path {
+            nodes: MyVec(vec![0, 1, 2]),
+            edges: MyVec(vec![0, 1]),
+            total_dimension_costs: MyVec(vec![5.0, 5.0]),
+        };
+
+        let individual = segment_with_individual_preferences(&mut lp, &mut dijkstra, &path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(individual.split_points, vec![1]);
+        assert!(
+            segment_with_common_preference(&mut lp, &mut dijkstra, &path)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_greedy_segment_is_not_optimal() {
+        // 0 -> 1 -> 2 is only optimal for alpha[1] <= 0.4 because of the
+        // detour over 4, and 2 -> 3 only for alpha[1] >= 0.6 because of the
+        // detour over 5. Extending the first segment to node 2 leaves no
+        // preference for the last edge, the minimum splits at node 1.
+        let graph = parse_minimal_graph(
+            "2\na b\n6\n7\n0 0\n1 0\n2 0\n3 0\n4 0\n5 0\n\
+             0 0 1 1 1 -1 -1\n1 1 2 1 2 -1 -1\n2 2 3 3 2 -1 -1\n3 0 4 2 0 -1 -1\n\
+             4 4 2 2 0 -1 -1\n5 2 5 0 2 -1 -1\n6 5 3 0 2 -1 -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut dijkstra = Dijkstra::new(&graph);
+        let mut lp = PreferenceLp::new(graph.dim as usize).unwrap();
+        let path = Path {
+            nodes: MyVec(vec![0, 1, 2, 3]),
+            edges: MyVec(vec![0, 1, 2]),
+            total_dimension_costs: MyVec(vec![5.0, 5.0]),
+        };
+
+        // the backtracking has to give up the longest first segment
+        let mut finder = PrefFinder::new(&mut lp, graph.dim as usize);
+        for (max_segments, expected) in [(1, None), (2, Some(vec![1]))] {
+            let mut search = SplitSearch {
+                finder: &mut finder,
+                dijkstra: &mut dijkstra,
+                path: &path,
+                last: 3,
+                max_segments,
+                segments: Vec::new(),
+                split_points: Vec::new(),
+            };
+            let found = search.run(0).unwrap().map(|_| search.split_points);
+            assert_eq!(found, expected);
+        }
+
+        let common = segment_with_common_preference(&mut lp, &mut dijkstra, &path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(common.split_points, vec![1]);
+        assert!(common.preference[1] >= 0.6 - ACCURACY);
+        for segment in common.segments(&graph, &path) {
+            assert!(
+                split_for_preference(&mut dijkstra, &segment, &common.preference)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Tolerance for paths which are almost optimal.
+//!
+//! Trajectories derived from GPS traces are rarely exactly optimal for any
+//! preference. With a tolerance a path counts as optimal for `alpha` if its
+//! cost is at most `(1 + epsilon)` times the optimal cost plus `slack`.
+//!
+//! Preferences are normalized to sum up to 1, so the slack can be spread over
+//! all metrics and the relaxed condition
+//! `alpha * path <= (1 + epsilon) * alpha * optimum + slack`
+//! is again a homogeneous linear constraint on `alpha`. Everything that works
+//! on the constraints, i.e. the LPs, the corner cutting and the .space files,
+//! therefore uses the relaxed constraints without further changes.
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{utils::Costs, ACCURACY};
+
+/// Relaxed constraints with integer coefficients are scaled by this factor,
+/// so epsilon and slack must have at most three decimal places. Tools reading
+/// .space files expect the coefficients to fit into 32 bits, so the factor is
+/// kept small.
+pub const INTEGER_SCALE: i64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Tolerance {
+    /// Relative tolerance, paths within `(1 + epsilon)` times the optimal
+    /// cost count as optimal
+    pub epsilon: f64,
+    /// Absolute tolerance in the cost of the preference
+    pub slack: f64,
+}
+
+impl Tolerance {
+    /// Only exactly optimal paths count as optimal.
+    pub const EXACT: Tolerance = Tolerance {
+        epsilon: 0.0,
+        slack: 0.0,
+    };
+
+    /// Fails if epsilon or slack is negative or has more than three decimal
+    /// places, since the integer constraints could not represent it exactly.
+    pub fn new(epsilon: f64, slack: f64) -> Result<Self> {
+        for (name, value) in [("epsilon", epsilon), ("slack", slack)] {
+            ensure!(
+                value.is_finite() && value >= 0.0,
+                "{} must not be negative, got {}",
+                name,
+                value
+            );
+            let scaled = value * INTEGER_SCALE as f64;
+            ensure!(
+                (scaled - scaled.round()).abs() <= ACCURACY,
+                "{} must be a multiple of 1/{}, got {}",
+                name,
+                INTEGER_SCALE,
+                value
+            );
+        }
+        Ok(Tolerance { epsilon, slack })
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.epsilon == 0.0 && self.slack == 0.0
+    }
+
+    /// By how much `cost` exceeds the largest cost which still counts as
+    /// optimal if `optimal_cost` is the optimum. A path is optimal within
+    /// the tolerance if this is not positive.
+    pub fn excess(&self, cost: f64, optimal_cost: f64) -> f64 {
+        cost - (1.0 + self.epsilon) * optimal_cost - self.slack
+    }
+
+    /// The relaxed constraint `(1 + epsilon) * optimal + slack - path`. For a
+    /// preference `alpha` summing up to 1, `alpha * constraint >= 0` iff the
+    /// path is optimal within the tolerance compared to the path with
+    /// `optimal_costs`. Without tolerance this is the usual cost difference.
+    pub fn constraint(&self, path_costs: &[f64], optimal_costs: &[f64]) -> Costs {
+        path_costs
+            .iter()
+            .zip(optimal_costs)
+            .map(|(p, o)| (1.0 + self.epsilon) * o + self.slack - p)
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// Like `constraint`, but without rounding if all costs are integers:
+    /// then the coefficients of `integer_constraint` are used, which are
+    /// scaled by a positive factor. Falls back to `constraint` otherwise.
+    pub fn exact_constraint(&self, path_costs: &[f64], optimal_costs: &[f64]) -> Costs {
+        // integers up to 2^53 are exact in floating point
+        let as_integers = |costs: &[f64]| -> Option<Vec<i64>> {
+            costs
+                .iter()
+                .map(|&c| (c.fract() == 0.0 && c.abs() <= 9e15).then_some(c as i64))
+                .collect()
+        };
+        match (as_integers(path_costs), as_integers(optimal_costs)) {
+            (Some(path), Some(optimal)) => self
+                .integer_constraint(&path, &optimal)
+                .into_iter()
+                .map(|c| c as f64)
+                .collect::<Vec<_>>()
+                .into(),
+            _ => self.constraint(path_costs, optimal_costs),
+        }
+    }
+
+    /// Like `constraint`, but for integer costs and with integer coefficients.
+    /// Without tolerance the coefficients are the plain cost differences,
+    /// otherwise they are scaled by `INTEGER_SCALE` and divided by their
+    /// greatest common divisor.
+    pub fn integer_constraint(&self, path_costs: &[i64], optimal_costs: &[i64]) -> Vec<i64> {
+        let scale = if self.is_exact() { 1 } else { INTEGER_SCALE };
+        let epsilon = (self.epsilon * scale as f64).round() as i64;
+        let slack = (self.slack * scale as f64).round() as i64;
+        let mut constraint: Vec<i64> = path_costs
+            .iter()
+            .zip(optimal_costs)
+            .map(|(p, o)| (scale + epsilon) * o + slack - scale * p)
+            .collect();
+        if scale > 1 {
+            let divisor = constraint.iter().fold(0, |a, &b| gcd(a, b.abs()));
+            if divisor > 1 {
+                constraint.iter_mut().for_each(|c| *c /= divisor);
+            }
+        }
+        constraint
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl std::fmt::Display for Tolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "epsilon {} slack {}", self.epsilon, self.slack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::path::costs_by_alpha;
+    use crate::utils::same_array;
+
+    #[test]
+    fn test_relaxed_constraints() {
+        let path = [12.0, 20.0, 3.0];
+        let optimum = [10.0, 18.0, 3.0];
+        let alpha = [0.5, 0.25, 0.25];
+        let cost = costs_by_alpha(&path, &alpha);
+        let optimal_cost = costs_by_alpha(&optimum, &alpha);
+
+        let exact = Tolerance::EXACT.constraint(&path, &optimum);
+        assert!(same_array(&exact, &[-2.0, -2.0, 0.0]));
+        assert!(Tolerance::EXACT.excess(cost, optimal_cost) > 0.0);
+
+        for tolerance in &[
+            Tolerance::new(0.2, 0.0).unwrap(),
+            Tolerance::new(0.0, 1.5).unwrap(),
+        ] {
+            let constraint = tolerance.constraint(&path, &optimum);
+            assert!(crate::float_eq!(
+                costs_by_alpha(&constraint, &alpha),
+                -tolerance.excess(cost, optimal_cost)
+            ));
+            assert!(tolerance.excess(cost, optimal_cost) <= 0.0);
+        }
+        assert!(
+            Tolerance::new(0.05, 0.0)
+                .unwrap()
+                .excess(cost, optimal_cost)
+                > 0.0
+        );
+
+        let integer = Tolerance::EXACT.integer_constraint(&[12, 20, 3], &[10, 18, 3]);
+        assert_eq!(integer, vec![-2, -2, 0]);
+        // 1500 * optimum + 1000 - 1000 * path, divided by 500
+        let integer = Tolerance::new(0.5, 1.0)
+            .unwrap()
+            .integer_constraint(&[12, 20, 3], &[10, 18, 3]);
+        assert_eq!(integer, vec![8, 16, 5]);
+        let relaxed = Tolerance::new(0.5, 1.0)
+            .unwrap()
+            .constraint(&path, &optimum);
+        for (i, r) in integer.iter().zip(relaxed.iter()) {
+            assert!(crate::float_eq!(*i as f64 / 2.0, r));
+        }
+    }
+
+    #[test]
+    fn test_exact_constraint() {
+        // (1 + 0.1) * 50 - 55 is not zero in floating point
+        let tolerance = Tolerance::new(0.1, 0.0).unwrap();
+        let rounded = tolerance.constraint(&[55.0, 0.0], &[50.0, 0.0]);
+        assert_ne!(rounded[0], 0.0);
+        let exact = tolerance.exact_constraint(&[55.0, 0.0], &[50.0, 0.0]);
+        assert_eq!(&exact[..], &[0.0, 0.0]);
+
+        // costs which are not integers are rounded like before
+        let exact = tolerance.exact_constraint(&[55.5, 0.0], &[50.0, 0.0]);
+        let rounded = tolerance.constraint(&[55.5, 0.0], &[50.0, 0.0]);
+        assert_eq!(&exact[..], &rounded[..]);
+    }
+
+    #[test]
+    fn test_invalid_tolerances() {
+        assert!(Tolerance::new(-0.1, 0.0).is_err());
+        assert!(Tolerance::new(0.0, f64::NAN).is_err());
+        assert!(Tolerance::new(0.0005, 0.0).is_err());
+        assert!(Tolerance::new(0.1, 2.0001).is_err());
+        assert_eq!(
+            Tolerance::new(0.001, 12.5).unwrap(),
+            Tolerance {
+                epsilon: 0.001,
+                slack: 12.5
+            }
+        );
+    }
+}
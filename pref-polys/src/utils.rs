@@ -9,6 +9,8 @@ pub mod io;
 mod matrix;
 pub mod metrics;
 mod mytypes;
+pub mod number;
+pub mod parallel;
 
 pub use bitset::{BitSet, BitSetFns, BitSetIter, GrowingBitSet, GrowingBitSetIter};
 pub use matrix::SquareMatrix;
@@ -6,6 +6,7 @@ use std::{
 };
 
 use crate::graph::path::Path;
+use crate::tolerance::Tolerance;
 
 use super::MyVec;
 
@@ -123,3 +124,20 @@ pub fn write_out_paths(file_name: &str, paths: Vec<Path>) -> std::io::Result<()>
     fs::write(file_name, data).expect("Unable to write file");
     Ok(())
 }
+
+/// The comment line naming the metrics of the costs in a paths or .space file.
+pub fn metrics_header(metric_names: &[String]) -> String {
+    format!("# metrics: {}", metric_names.join(" "))
+}
+
+/// The comment lines which start every .space file. They name the metrics the
+/// preference spaces were computed for and, if paths did not need to be
+/// exactly optimal, the tolerance.
+pub fn space_header(metric_names: &[String], tolerance: Tolerance) -> String {
+    let header = metrics_header(metric_names);
+    if tolerance.is_exact() {
+        header
+    } else {
+        format!("{}\n# tolerance: {}", header, tolerance)
+    }
+}
@@ -0,0 +1,105 @@
+//! Numbers for geometric decisions and LPs.
+//!
+//! Floating point numbers compare with a tolerance, so whether a preference
+//! lies inside a constraint or an LP is feasible can depend on rounding.
+//! Edge costs are integers, so the same computations can be done exactly
+//! with rationals. Code which is generic over `Number` runs in both modes.
+
+use std::fmt::{Debug, Display};
+
+use num_traits::{NumAssignOps, Signed};
+
+pub type Rational = num_rational::BigRational;
+
+pub trait Number: Signed + NumAssignOps + Clone + PartialOrd + Debug + Display {
+    /// `tolerance` for floating point numbers, zero for exact numbers.
+    fn tolerance(tolerance: f64) -> Self;
+
+    /// Converts `value` exactly, if the number type allows it.
+    fn from_f64(value: f64) -> Self;
+
+    fn from_i64(value: i64) -> Self;
+
+    /// The closest floating point number.
+    fn to_f64(&self) -> f64;
+}
+
+impl Number for f64 {
+    fn tolerance(tolerance: f64) -> Self {
+        tolerance
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn from_i64(value: i64) -> Self {
+        value as f64
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+}
+
+impl Number for Rational {
+    fn tolerance(_tolerance: f64) -> Self {
+        Rational::from_integer(0.into())
+    }
+
+    /// Panics if `value` is not finite.
+    fn from_f64(value: f64) -> Self {
+        Rational::from_float(value).expect("only finite numbers can be rational")
+    }
+
+    fn from_i64(value: i64) -> Self {
+        Rational::from_integer(value.into())
+    }
+
+    fn to_f64(&self) -> f64 {
+        num_traits::ToPrimitive::to_f64(self).unwrap_or_else(|| {
+            if self.is_negative() {
+                f64::MIN
+            } else {
+                f64::MAX
+            }
+        })
+    }
+}
+
+/// Converts all values with `Number::from_f64`.
+pub fn convert<T: Number>(values: &[f64]) -> Vec<T> {
+    values.iter().map(|&v| T::from_f64(v)).collect()
+}
+
+/// Scalar product of floating point `costs` and a preference, evaluated in
+/// `T`.
+pub fn dot<T: Number>(costs: &[f64], alpha: &[T]) -> T {
+    assert_eq!(costs.len(), alpha.len());
+    costs
+        .iter()
+        .zip(alpha)
+        .fold(T::zero(), |sum, (&c, a)| sum + T::from_f64(c) * a.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::Zero;
+
+    #[test]
+    fn test_rational_conversion() {
+        let third = Rational::from_i64(1) / Rational::from_i64(3);
+        assert_eq!(third.to_string(), "1/3");
+        assert!(crate::float_eq!(third.to_f64(), 1.0 / 3.0));
+
+        // floating point numbers are converted without rounding
+        let tenth = Rational::from_f64(0.1);
+        assert_ne!(tenth, Rational::from_i64(1) / Rational::from_i64(10));
+        assert_eq!(tenth.to_f64(), 0.1);
+        assert_eq!(Rational::from_f64(-2.5).to_string(), "-5/2");
+
+        assert!(Rational::tolerance(0.1).is_zero());
+        assert_eq!(f64::tolerance(0.1), 0.1);
+    }
+}
@@ -0,0 +1,97 @@
+//! Parallel processing of independent work items.
+
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+
+fn find_task<T>(local: &Worker<T>, global: &Injector<T>, stealers: &[Stealer<T>]) -> Option<T> {
+    local.pop().or_else(|| {
+        std::iter::repeat_with(|| {
+            global
+                .steal_batch_and_pop(local)
+                .or_else(|| stealers.iter().map(|s| s.steal()).collect())
+        })
+        .find(|s| !s.is_retry())
+        .and_then(Steal::success)
+    })
+}
+
+/// Applies `f` to all items on all cores and returns the results in the order
+/// of the items. Every thread creates its own state with `init` once, e.g. to
+/// take a search engine from a `QueryPool`. Items are distributed by work
+/// stealing, so a thread which got expensive items does not hold back the
+/// others.
+pub fn work_stealing_map<T, R, S>(
+    items: &[T],
+    init: impl Fn() -> S + Sync,
+    f: impl Fn(&mut S, &T) -> R + Sync,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let thread_count = num_cpus::get().min(items.len());
+    if thread_count == 0 {
+        return Vec::new();
+    }
+
+    let injector = Injector::new();
+    (0..items.len()).for_each(|i| injector.push(i));
+    let workers: Vec<_> = (0..thread_count).map(|_| Worker::new_fifo()).collect();
+    let stealers: Vec<_> = workers.iter().map(Worker::stealer).collect();
+
+    let thread_res = crossbeam::scope(|scope| {
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|worker| {
+                let (injector, stealers, init, f) = (&injector, &stealers, &init, &f);
+                scope.spawn(move |_| {
+                    let mut state = init();
+                    let mut results = Vec::new();
+                    while let Some(i) = find_task(&worker, injector, stealers) {
+                        results.push((i, f(&mut state, &items[i])));
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        let mut results: Vec<Option<R>> = (0..items.len()).map(|_| None).collect();
+        for handle in handles {
+            for (i, r) in handle.join().expect("Cannot join thread") {
+                results[i] = Some(r);
+            }
+        }
+        results
+    });
+
+    thread_res
+        .expect("Threading failed")
+        .into_iter()
+        .map(|r| r.expect("Every item is processed once"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_results_keep_order() {
+        let items: Vec<u64> = (0..100).collect();
+        let squares = work_stealing_map(
+            &items,
+            || (),
+            |_, &i| {
+                // some items are much more expensive than others
+                if i % 17 == 0 {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+                i * i
+            },
+        );
+        assert_eq!(squares, items.iter().map(|i| i * i).collect::<Vec<_>>());
+
+        let empty: Vec<u64> = Vec::new();
+        assert!(work_stealing_map(&empty, || (), |_, &i| i).is_empty());
+        assert_eq!(work_stealing_map(&[3u64], || (), |_, &i| i + 1), vec![4]);
+    }
+}